@@ -0,0 +1,135 @@
+//! `--cached` / `--verify-cache` for `id` and `attest`: skip hashing a file
+//! whose identifier is already cached and whose metadata has not changed (see
+//! [`terrapin::IdCache`]; the default attribute cache does not check ctime).
+//!
+//! With `--verify-cache` the file is always rehashed; a cached identifier that
+//! disagrees is replaced and reported as an `identifier mismatch`. A cache that
+//! cannot be written (e.g. a read-only file with the attribute backend) only
+//! warns: the command's result does not depend on it.
+
+use std::path::{Path, PathBuf};
+
+use structopt::StructOpt;
+use terrapin::{CacheEntry, Error, FileKey, HeadFormat, IdCache, PersistedTree};
+
+use crate::{fail, output, usage};
+
+#[derive(StructOpt)]
+pub struct CacheOpts {
+    /// Reuse the identifier cached for <input> if the file is unchanged, and
+    /// cache it otherwise. By default the cache is a user.terrapin.* extended
+    /// attribute, which cannot record the file's ctime: it misses after a
+    /// change of size, mtime or inode, but not after a chmod or a same-size
+    /// rewrite that restores the mtime. --cache-db also checks the ctime.
+    #[structopt(long)]
+    cached: bool,
+    /// Rehash even on a cache hit and fail if the cached identifier differs
+    /// (the entry is corrected).
+    #[structopt(long = "verify-cache")]
+    verify_cache: bool,
+    /// Keep the cache in this database file instead of extended attributes
+    /// ("default": $XDG_CACHE_HOME/terrapin/ids). Implies --cached.
+    #[structopt(long = "cache-db", parse(from_os_str))]
+    cache_db: Option<PathBuf>,
+}
+
+/// The cache state of one input file.
+pub struct Cached {
+    cache: IdCache,
+    /// The file's key before hashing, so a file changed meanwhile is not cached.
+    before: FileKey,
+    entry: Option<CacheEntry>,
+    verify: bool,
+}
+
+impl CacheOpts {
+    /// Look `input` up, if any cache option is given.
+    pub fn lookup(&self, input: &Path, profile: &str) -> Option<Cached> {
+        if !(self.cached || self.verify_cache || self.cache_db.is_some()) {
+            return None;
+        }
+        let cache = match &self.cache_db {
+            None => IdCache::Xattr,
+            Some(db) if db.as_os_str() == "default" => IdCache::Db(
+                IdCache::default_db().unwrap_or_else(|| usage("--cache-db default: HOME not set")),
+            ),
+            Some(db) => IdCache::Db(db.clone()),
+        };
+        let before = FileKey::of(input).unwrap_or_else(|e| fail(&e));
+        let entry = cache.get(input, profile).unwrap_or_else(|e| fail(&e));
+        Some(Cached {
+            cache,
+            before,
+            entry,
+            verify: self.verify_cache,
+        })
+    }
+}
+
+impl Cached {
+    /// The cached identifier, unless verifying.
+    pub fn hit(&self) -> Option<&str> {
+        let entry = self.entry.as_ref().filter(|_| !self.verify)?;
+        output::set("cache", "hit");
+        Some(&entry.identifier)
+    }
+
+    /// The tree `attest` would write at `base` in this layout, if the cache
+    /// says it was written for this file and it still checks out in full.
+    pub fn tree(&self, base: &Path, single: bool, format: &HeadFormat) -> Option<PersistedTree> {
+        let entry = self.entry.as_ref().filter(|_| !self.verify)?;
+        if entry.tree.as_deref() != Some(base) {
+            return None;
+        }
+        let pt = PersistedTree::read(base).ok()?;
+        let (version, extensions) = match format {
+            HeadFormat::V1 => (1, None),
+            HeadFormat::V2 { extensions } => (2, Some(extensions)),
+        };
+        let same = pt.identifier == entry.identifier
+            && pt.version() == version
+            && extensions.is_none_or(|e| *e == pt.extensions)
+            && (pt.blocks_offset() > 0) == single;
+        if !same || pt.verify_structure().is_err() {
+            return None;
+        }
+        output::set("cache", "hit");
+        Some(pt)
+    }
+
+    /// Record the freshly computed `identifier` (and `tree`) for `input`;
+    /// with `--verify-cache`, fail if the cache held another identifier.
+    pub fn record(&self, input: &Path, identifier: &str, tree: Option<&Path>) {
+        let entry = CacheEntry {
+            identifier: identifier.to_string(),
+            tree: tree.map(Path::to_path_buf),
+        };
+        match self.cache.put(input, &self.before, &entry) {
+            Ok(true) => {}
+            Ok(false) => output::set("cache_error", "input changed while hashing"),
+            Err(e) => {
+                if !output::json() {
+                    eprintln!("warning: {}", e);
+                }
+                output::set("cache_error", e.message());
+            }
+        }
+        let stale = self.entry.as_ref().map(|e| e.identifier.as_str());
+        output::set(
+            "cache",
+            match stale {
+                Some(id) if self.verify && id == identifier => "verified",
+                Some(_) if self.verify => "mismatch",
+                _ => "miss",
+            },
+        );
+        if let Some(stale) = stale.filter(|&id| self.verify && id != identifier) {
+            fail(&Error::mismatch(format!(
+                "identifier mismatch: cache has {} for {}, rehashing gives {}",
+                stale,
+                input.display(),
+                identifier
+            )));
+        }
+    }
+}
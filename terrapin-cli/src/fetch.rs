@@ -0,0 +1,195 @@
+//! `terrapin fetch`: download a dataset (or a byte range) over HTTP, keeping
+//! only bytes verified against a trusted identifier.
+//!
+//! The tree's head is fetched first and bound to the trusted identifier with
+//! [`PersistedTree::check_against`]. It is the header of a single-file tree at
+//! the tree URL itself, range-fetched through
+//! [`PersistedTree::from_single_header`], or else `<tree URL>.head`. Each data
+//! block covering the range is then range-fetched and checked with a
+//! [`terrapin::BlockVerifier`] whose hash-file groups are themselves
+//! range-fetched from `.blocks`, or from the single file past its
+//! [`PersistedTree::blocks_offset`]: only the groups on the requested blocks'
+//! paths, each once. A block is written only after it verifies.
+//!
+//! The output holds `[start, end)` at offset 0. Re-running over an existing
+//! output resumes: every full block already present is re-verified in place
+//! and only the blocks that are missing or fail are downloaded again. On error
+//! the output is cut back to its verified prefix, so it never holds a byte that
+//! has not been checked.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use terrapin::{Error, PersistedTree, BLOCK};
+
+use crate::{http, output};
+
+/// Bytes of a single-file tree that always hold its header length.
+const SINGLE_PREFIX: u64 = 4096;
+
+/// What a fetch did.
+pub struct Report {
+    /// Bytes in the output (`end - start`).
+    pub bytes: u64,
+    /// Data blocks downloaded and verified.
+    pub downloaded: u64,
+    /// Data blocks already present in the output and re-verified in place.
+    pub reused: u64,
+    /// Length of the output prefix verified so far.
+    verified: u64,
+}
+
+/// Fetch `[start, end)` of the dataset at `url`, whose tree is the single file
+/// `<tree_url>` or lives at `<tree_url>.head` / `<tree_url>.blocks`, into `out`.
+pub fn run(
+    url: &str,
+    tree_url: &str,
+    identifier: &str,
+    start: Option<u64>,
+    end: Option<u64>,
+    out: &Path,
+) -> Result<Report, Error> {
+    let (tree, groups_url) = fetch_tree(tree_url)?;
+    tree.check_against(identifier)?;
+    output::tree(&tree);
+
+    let start = start.unwrap_or(0);
+    let end = end.unwrap_or(tree.length);
+    output::range(Some(start), Some(end), tree.length);
+    if start > end || end > tree.length {
+        return Err(Error::out_of_range(format!(
+            "range {}..{} out of bounds for length {}",
+            start, end, tree.length
+        )));
+    }
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(out)
+        .map_err(|e| Error::io(format!("cannot open {}: {}", out.display(), e)))?;
+    let existing = file
+        .metadata()
+        .map_err(|e| Error::io(format!("stat {}: {}", out.display(), e)))?
+        .len();
+
+    let mut report = Report {
+        bytes: end - start,
+        downloaded: 0,
+        reused: 0,
+        verified: 0,
+    };
+    let result = fetch_blocks(
+        url,
+        &groups_url,
+        &tree,
+        (start, end),
+        &mut file,
+        existing,
+        &mut report,
+    );
+    let keep = if result.is_ok() {
+        end - start
+    } else {
+        report.verified
+    };
+    file.set_len(keep)
+        .map_err(|e| Error::io(format!("truncate {}: {}", out.display(), e)))?;
+    result.map(|()| report)
+}
+
+/// Fetch the tree at `tree_url`, with the URL its hash-file groups are
+/// range-fetched from. A single-file tree at `tree_url` itself is tried first:
+/// its first 4096 bytes give the header length, and only the header is read.
+fn fetch_tree(tree_url: &str) -> Result<(PersistedTree, String), Error> {
+    if let Ok(mut prefix) = http::get(tree_url, Some((0, SINGLE_PREFIX))) {
+        if let Ok(offset) = PersistedTree::single_header_len(&prefix) {
+            if offset > SINGLE_PREFIX {
+                prefix.extend(http::get(tree_url, Some((SINGLE_PREFIX, offset)))?);
+            }
+            let tree = PersistedTree::from_single_header(&prefix)?;
+            return Ok((tree, tree_url.to_string()));
+        }
+    }
+    let head = http::get(&format!("{}.head", tree_url), None)?;
+    let head = String::from_utf8(head).map_err(|_| Error::malformed("head: non-utf8"))?;
+    let tree = PersistedTree::from_head(&head)?;
+    Ok((tree, format!("{}.blocks", tree_url)))
+}
+
+fn fetch_blocks(
+    url: &str,
+    groups_url: &str,
+    tree: &PersistedTree,
+    (start, end): (u64, u64),
+    file: &mut File,
+    existing: u64,
+    report: &mut Report,
+) -> Result<(), Error> {
+    let mut verifier = tree.verifier()?;
+    // Groups sit past the header in a single file; `.blocks` starts at 0.
+    let base = tree.blocks_offset();
+    let mut fetch_group =
+        |off: u64, len: usize| http::get(groups_url, Some((base + off, base + off + len as u64)));
+
+    if tree.length == 0 {
+        return verifier.verify_block(0, b"", &mut fetch_group);
+    }
+    if start == end {
+        return Ok(());
+    }
+
+    let b_lo = start / BLOCK as u64;
+    let b_hi = (end - 1) / BLOCK as u64;
+    for i in b_lo..=b_hi {
+        let block_off = i * BLOCK as u64;
+        let block_len = verifier.block_len(i);
+        let s = start.max(block_off);
+        let e = end.min(block_off + block_len as u64);
+        let out_off = s - start;
+
+        // Resume: a whole block already on disk is re-verified, not re-fetched.
+        let whole = s == block_off && e == block_off + block_len as u64;
+        if whole && out_off + block_len as u64 <= existing {
+            let mut buf = vec![0u8; block_len];
+            file.seek(SeekFrom::Start(out_off))
+                .and_then(|_| file.read_exact(&mut buf))
+                .map_err(|e| Error::io(format!("read output: {}", e)))?;
+            if verifier.verify_block(i, &buf, &mut fetch_group).is_ok() {
+                report.reused += 1;
+                report.verified = out_off + block_len as u64;
+                block_event(i, true, report);
+                continue;
+            }
+        }
+
+        let block = http::get(url, Some((block_off, block_off + block_len as u64)))?;
+        verifier.verify_block(i, &block, &mut fetch_group)?;
+        let lo = (s - block_off) as usize;
+        let hi = (e - block_off) as usize;
+        file.seek(SeekFrom::Start(out_off))
+            .and_then(|_| file.write_all(&block[lo..hi]))
+            .map_err(|e| Error::io(format!("write output: {}", e)))?;
+        report.downloaded += 1;
+        report.verified = e - start;
+        block_event(i, false, report);
+    }
+    file.flush()
+        .map_err(|e| Error::io(format!("write output: {}", e)))
+}
+
+/// JSON-lines event for a verified block (`--format json`).
+fn block_event(index: u64, reused: bool, report: &Report) {
+    output::event(
+        "block",
+        serde_json::json!({
+            "index": index,
+            "reused": reused,
+            "bytes": report.verified,
+            "total": report.bytes,
+        }),
+    );
+}
@@ -0,0 +1,282 @@
+//! Minimal HTTP/1.1 plumbing shared by the network commands.
+//!
+//! Only what terrapin needs: one request per connection, `GET`/`HEAD`, and a
+//! single `bytes=` range (RFC 9110 section 14). No keep-alive, no chunked
+//! transfer coding; every body is sent with an exact `Content-Length`. Plain
+//! `http://` only — put a TLS-terminating proxy in front for `https`.
+#![cfg_attr(not(feature = "serve"), allow(dead_code))]
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use terrapin::Error;
+
+/// Socket read/write timeout for client requests.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Upper bound on the request head, so a misbehaving peer cannot grow it forever.
+const MAX_HEAD: usize = 64 * 1024;
+
+/// A parsed request line plus headers.
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl Request {
+    /// First header named `name` (case-insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Read one request head (request line and headers up to the blank line).
+pub fn read_request<R: BufRead>(r: &mut R) -> Result<Request, String> {
+    let mut lines = Vec::new();
+    let mut total = 0;
+    loop {
+        let mut line = String::new();
+        let n = r
+            .read_line(&mut line)
+            .map_err(|e| format!("read request: {}", e))?;
+        if n == 0 {
+            return Err("request: connection closed before end of head".into());
+        }
+        total += n;
+        if total > MAX_HEAD {
+            return Err("request: head too large".into());
+        }
+        let line = line.trim_end_matches(['\r', '\n']).to_string();
+        if line.is_empty() {
+            break;
+        }
+        lines.push(line);
+    }
+    let mut it = lines.into_iter();
+    let request_line = it.next().ok_or("request: empty")?;
+    let mut parts = request_line.split(' ');
+    let (method, path, version) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(m), Some(p), Some(v)) if parts.next().is_none() => (m, p, v),
+        _ => return Err(format!("request: bad request line {:?}", request_line)),
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err(format!("request: unsupported version {}", version));
+    }
+    let mut headers = Vec::new();
+    for line in it {
+        let (k, v) = line
+            .split_once(':')
+            .ok_or_else(|| format!("request: bad header {:?}", line))?;
+        headers.push((k.trim().to_string(), v.trim().to_string()));
+    }
+    Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        headers,
+    })
+}
+
+/// Resolve a `Range` header value against a representation of `len` bytes.
+///
+/// Returns `Ok(None)` when the header should be ignored (absent, malformed or a
+/// multi-range request, which RFC 9110 allows a server to ignore),
+/// `Ok(Some((start, end)))` for a half-open satisfiable range, and `Err` when
+/// the range is syntactically valid but unsatisfiable (status 416).
+pub fn parse_range(value: Option<&str>, len: u64) -> Result<Option<(u64, u64)>, String> {
+    let spec = match value.and_then(|v| v.trim().strip_prefix("bytes=")) {
+        Some(s) if !s.contains(',') => s.trim(),
+        _ => return Ok(None),
+    };
+    let (first, last) = match spec.split_once('-') {
+        Some(p) => p,
+        None => return Ok(None),
+    };
+    let unsatisfiable = || {
+        Err(format!(
+            "range {:?} not satisfiable for length {}",
+            spec, len
+        ))
+    };
+    if first.is_empty() {
+        // Suffix range: the final `n` bytes.
+        let n: u64 = match last.parse() {
+            Ok(n) => n,
+            Err(_) => return Ok(None),
+        };
+        if n == 0 || len == 0 {
+            return unsatisfiable();
+        }
+        return Ok(Some((len - n.min(len), len)));
+    }
+    let start: u64 = match first.parse() {
+        Ok(n) => n,
+        Err(_) => return Ok(None),
+    };
+    let end = if last.is_empty() {
+        len
+    } else {
+        match last.parse::<u64>() {
+            Ok(n) if n >= start => n.saturating_add(1).min(len),
+            _ => return Ok(None),
+        }
+    };
+    if start >= len {
+        return unsatisfiable();
+    }
+    Ok(Some((start, end)))
+}
+
+/// Write a status line and headers, followed by the blank line.
+pub fn write_head<W: Write>(w: &mut W, status: u16, headers: &[(&str, String)]) -> io::Result<()> {
+    write!(w, "HTTP/1.1 {} {}\r\n", status, reason(status))?;
+    for (k, v) in headers {
+        write!(w, "{}: {}\r\n", k, v)?;
+    }
+    w.write_all(b"Connection: close\r\n\r\n")
+}
+
+/// A complete `text/plain` response; used for errors and small documents.
+pub fn write_text<W: Write>(w: &mut W, status: u16, body: &str, head_only: bool) -> io::Result<()> {
+    write_head(
+        w,
+        status,
+        &[
+            ("Content-Type", "text/plain; charset=utf-8".into()),
+            ("Content-Length", body.len().to_string()),
+        ],
+    )?;
+    if !head_only {
+        w.write_all(body.as_bytes())?;
+    }
+    w.flush()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        206 => "Partial Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        416 => "Range Not Satisfiable",
+        500 => "Internal Server Error",
+        _ => "Unknown",
+    }
+}
+
+/// `GET url`, optionally for the half-open byte range `[start, end)`.
+///
+/// Returns exactly the requested bytes; any non-2xx status, or a body of the
+/// wrong length, is an error carrying the server's first line of explanation.
+pub fn get(url: &str, range: Option<(u64, u64)>) -> Result<Vec<u8>, Error> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| Error::other(format!("{}: only http:// URLs are supported", url)))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let addr = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{}:80", authority)
+    };
+    if let Some((s, e)) = range {
+        if s == e {
+            return Ok(Vec::new());
+        }
+    }
+
+    let io_err = |e: io::Error| Error::io(format!("GET {}: {}", url, e));
+    let mut stream = TcpStream::connect(&addr).map_err(io_err)?;
+    stream
+        .set_read_timeout(Some(CLIENT_TIMEOUT))
+        .map_err(io_err)?;
+    stream
+        .set_write_timeout(Some(CLIENT_TIMEOUT))
+        .map_err(io_err)?;
+    let mut req = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n",
+        path, authority
+    );
+    if let Some((s, e)) = range {
+        req.push_str(&format!("Range: bytes={}-{}\r\n", s, e - 1));
+    }
+    req.push_str("\r\n");
+    stream.write_all(req.as_bytes()).map_err(io_err)?;
+
+    let mut r = BufReader::new(stream);
+    let mut status_line = String::new();
+    r.read_line(&mut status_line).map_err(io_err)?;
+    let status: u16 = status_line
+        .split(' ')
+        .nth(1)
+        .and_then(|c| c.parse().ok())
+        .ok_or_else(|| {
+            Error::io(format!(
+                "GET {}: bad status line {:?}",
+                url,
+                status_line.trim_end()
+            ))
+        })?;
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if r.read_line(&mut line).map_err(io_err)? == 0 {
+            return Err(Error::io(format!(
+                "GET {}: connection closed in headers",
+                url
+            )));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((k, v)) = line.split_once(':') {
+            if k.trim().eq_ignore_ascii_case("content-length") {
+                content_length = v.trim().parse::<u64>().ok();
+            }
+        }
+    }
+    let mut body = Vec::new();
+    match content_length {
+        Some(n) => r.take(n).read_to_end(&mut body),
+        None => r.read_to_end(&mut body),
+    }
+    .map_err(io_err)?;
+    if let Some(n) = content_length {
+        if body.len() as u64 != n {
+            return Err(Error::io(format!(
+                "GET {}: body truncated ({} of {} bytes)",
+                url,
+                body.len(),
+                n
+            )));
+        }
+    }
+
+    match (status, range) {
+        (200, None) => Ok(body),
+        // A server may ignore Range and send the whole representation.
+        (200, Some((s, e))) if body.len() as u64 >= e => Ok(body[s as usize..e as usize].to_vec()),
+        (206, Some((s, e))) if body.len() as u64 == e - s => Ok(body),
+        (200..=299, _) => Err(Error::io(format!(
+            "GET {}: unexpected HTTP {} response",
+            url, status
+        ))),
+        _ => {
+            let why = String::from_utf8_lossy(&body);
+            Err(Error::io(format!(
+                "GET {}: HTTP {} {}",
+                url,
+                status,
+                why.lines().next().unwrap_or_default()
+            )))
+        }
+    }
+}
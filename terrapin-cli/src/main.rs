@@ -1,21 +1,56 @@
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use std::process::exit;
 
 use structopt::StructOpt;
-use terrapin::{build_from_reader, identifier_from_reader, PersistedTree};
+use terrapin::{
+    attest_dir, build_from_file_with, dir_listing, profile_by_name, to_hex, BuiltTree, DirListing,
+    Envelope, Error, HeadFormat, InTotoStatement, OciDataset, PersistedTree, Profile, PublicKey,
+    Sbom, SbomEntry, SbomFormat, SecretKey, SignatureAlgorithm, SignedStatement, Statement, Store,
+    StoreMode, BLOCK,
+};
+
+mod cache;
+mod fetch;
+mod http;
+mod output;
+#[cfg(feature = "serve")]
+mod serve;
+
+use cache::CacheOpts;
+use output::Progress;
 
 #[derive(StructOpt)]
 #[structopt(
     name = "terrapin",
     about = "Parallel content addressing and slice validation for very large datasets."
 )]
+struct Cli {
+    /// Output format: text, or json for one JSON object per command (after
+    /// JSON-lines progress events for long operations). Goes before the
+    /// command, e.g. `terrapin --format json id <file>`.
+    #[structopt(long, default_value = "text", possible_values = &["text", "json"])]
+    format: String,
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(StructOpt)]
 enum Command {
-    /// Print the terrapin-sha256 identifier of a file.
+    /// Print the terrapin-sha256 identifier of a file (or, with --dir, the
+    /// terrapin-dir-sha256 identifier of a directory).
     Id {
         #[structopt(parse(from_os_str))]
         input: PathBuf,
+        /// Address <input> as a directory dataset.
+        #[structopt(long)]
+        dir: bool,
+        /// Hash profile: terrapin-sha256 or terrapin-blake3.
+        #[structopt(long, default_value = "terrapin-sha256")]
+        profile: String,
+        #[structopt(flatten)]
+        cache: CacheOpts,
     },
     /// Build and write the publishable tree (<out>.head + <out>.blocks) and
     /// print the identifier.
@@ -25,6 +60,34 @@ enum Command {
         /// Output base name (default: <input>.terra).
         #[structopt(long, parse(from_os_str))]
         out: Option<PathBuf>,
+        /// Attest a directory: writes <out>.listing and one tree per file
+        /// under <out>.d/.
+        #[structopt(long)]
+        dir: bool,
+        /// Write the tree as the single file <out> instead of <out>.head and
+        /// <out>.blocks.
+        #[structopt(long, conflicts_with = "dir")]
+        single: bool,
+        /// Head format version: 1, or 2 (canonical, with the .blocks size,
+        /// top-layer hash and extension fields).
+        #[structopt(long = "head-version", default_value = "1", possible_values = &["1", "2"])]
+        head_version: u32,
+        /// Extension field <namespace>.<name>=<value> for a version 2 head
+        /// (repeatable), e.g. terrapin.media_type=text/plain.
+        #[structopt(long = "ext", number_of_values = 1)]
+        ext: Vec<String>,
+        /// Hash profile: terrapin-sha256 or terrapin-blake3.
+        #[structopt(long, default_value = "terrapin-sha256")]
+        profile: String,
+        /// Also write an in-toto Statement v1 naming <input> with its
+        /// identifier as digest (a DSSE envelope with --key).
+        #[structopt(long = "in-toto", parse(from_os_str), conflicts_with = "dir")]
+        in_toto: Option<PathBuf>,
+        /// Secret key (.key from `keygen`) to sign the --in-toto statement with.
+        #[structopt(long, parse(from_os_str), requires = "in-toto")]
+        key: Option<PathBuf>,
+        #[structopt(flatten)]
+        cache: CacheOpts,
     },
     /// Validate a file (or a byte range) against a published tree.
     Validate {
@@ -34,8 +97,20 @@ enum Command {
         #[structopt(long, parse(from_os_str))]
         tree: PathBuf,
         /// Trusted identifier (terrapin-sha256:...); the tree must match it.
+        /// With --member, the directory's terrapin-dir-sha256 identifier.
         #[structopt(long)]
         identifier: Option<String>,
+        /// Validate <input> as this member path of the directory attested at
+        /// --tree (from `attest --dir`).
+        #[structopt(long)]
+        member: Option<String>,
+        /// Take the trusted identifier from this signature file (from `sign`),
+        /// once it verifies with --pubkey.
+        #[structopt(long, parse(from_os_str), requires = "pubkey")]
+        signature: Option<PathBuf>,
+        /// Public key (.pub from `keygen`) the --signature must verify with.
+        #[structopt(long, parse(from_os_str), requires = "signature")]
+        pubkey: Option<PathBuf>,
         #[structopt(long)]
         start: Option<u64>,
         #[structopt(long)]
@@ -52,48 +127,462 @@ enum Command {
         #[structopt(long)]
         end: Option<u64>,
     },
+    /// Show a tree's header, layer layout, `.blocks` size and manifest, and
+    /// whether the identifier binds to it; or one hash, or a verification path.
+    Inspect {
+        /// Tree base name (the <name> of <name>.head / <name>.blocks).
+        #[structopt(parse(from_os_str))]
+        tree: PathBuf,
+        /// Print hash --index of this layer (0 = leaves).
+        #[structopt(long, requires = "index", conflicts_with = "offset")]
+        layer: Option<usize>,
+        #[structopt(long, requires = "layer")]
+        index: Option<u64>,
+        /// Print the verification path of the data block holding this byte
+        /// offset: group and position at each layer.
+        #[structopt(long)]
+        offset: Option<u64>,
+    },
+    /// Check a whole tree without the data: the `.blocks` size, every layer
+    /// recomputed from the one below, the root, and the identifier binding.
+    VerifyTree {
+        /// Tree base name (the <name> of <name>.head / <name>.blocks).
+        #[structopt(parse(from_os_str))]
+        tree: PathBuf,
+        /// Trusted identifier (terrapin-sha256:...); the tree must match it.
+        #[structopt(long)]
+        identifier: Option<String>,
+    },
+    /// Download a file (or a byte range) over HTTP, writing only bytes verified
+    /// against a trusted identifier. Re-running resumes an interrupted download.
+    Fetch {
+        /// Data URL (http://host:port/path).
+        url: String,
+        /// Trusted identifier (terrapin-sha256:...); the published tree must match it.
+        #[structopt(long)]
+        identifier: String,
+        /// Tree base URL (default: <url>.terra, the layout `serve` publishes).
+        #[structopt(long)]
+        tree_url: Option<String>,
+        #[structopt(long)]
+        start: Option<u64>,
+        #[structopt(long)]
+        end: Option<u64>,
+        /// Output file.
+        #[structopt(short, long, parse(from_os_str))]
+        output: PathBuf,
+    },
+    /// Generate a signing key pair: <out>.key (secret) and <out>.pub.
+    Keygen {
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+        /// ed25519 or ecdsa-p256.
+        #[structopt(long, default_value = "ed25519")]
+        algorithm: String,
+    },
+    /// Sign a statement of an identifier and length, taken from an attested
+    /// tree or given explicitly, and write the signature file.
+    Sign {
+        /// Secret key (.key from `keygen`).
+        #[structopt(long, parse(from_os_str))]
+        key: PathBuf,
+        /// Sign the identifier and length of this tree (base name).
+        #[structopt(long, parse(from_os_str), required_unless = "identifier")]
+        tree: Option<PathBuf>,
+        /// Identifier to sign (with --length), e.g. a terrapin-dir-sha256 one.
+        #[structopt(long, conflicts_with = "tree", requires = "length")]
+        identifier: Option<String>,
+        #[structopt(long)]
+        length: Option<u64>,
+        /// Metadata to include, as key=value (repeatable).
+        #[structopt(long = "meta")]
+        meta: Vec<String>,
+        /// Output file (default: stdout).
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Verify a signature file with a public key and print the signed
+    /// identifier.
+    VerifySig {
+        #[structopt(parse(from_os_str))]
+        signature: PathBuf,
+        /// Public key (.pub from `keygen`).
+        #[structopt(long, parse(from_os_str))]
+        pubkey: PathBuf,
+    },
+    /// Verify a DSSE-signed in-toto attestation (from `attest --in-toto
+    /// --key`), then validate <input> against the subject's identifier.
+    VerifyInToto {
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+        /// The DSSE envelope.
+        #[structopt(long, parse(from_os_str))]
+        attestation: PathBuf,
+        /// Public key (.pub from `keygen`).
+        #[structopt(long, parse(from_os_str))]
+        pubkey: PathBuf,
+        /// Tree base name (default: <input>.terra).
+        #[structopt(long, parse(from_os_str))]
+        tree: Option<PathBuf>,
+        /// Subject name to use (default: the file name of <input>).
+        #[structopt(long)]
+        subject: Option<String>,
+    },
+    /// Manage a local content-addressed store of datasets keyed by identifier.
+    Store(StoreCommand),
+    /// Write or check SPDX / CycloneDX SBOMs listing files by identifier.
+    Sbom(SbomCommand),
+    /// Export a dataset and its tree to an OCI image layout, or validate one.
+    Oci(OciCommand),
+    /// Serve files over HTTP with Range support, returning only bytes verified
+    /// against each file's attested tree (<file>.terra).
+    #[cfg(feature = "serve")]
+    Serve {
+        #[structopt(parse(from_os_str), required = true)]
+        inputs: Vec<PathBuf>,
+        /// Address to listen on (port 0 picks a free port).
+        #[structopt(long, default_value = "127.0.0.1:8080")]
+        bind: String,
+    },
+}
+
+#[derive(StructOpt)]
+enum StoreCommand {
+    /// Create a store, choosing how it keeps data (default: one file per dataset).
+    Init {
+        /// Keep each unique 2 MiB data block once, shared across datasets.
+        #[structopt(long)]
+        blocks: bool,
+        #[structopt(flatten)]
+        store: StoreDir,
+    },
+    /// Ingest a file (data + tree) and point a ref at its identifier. Data
+    /// already stored under the same identifier is not stored twice.
+    Add {
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+        /// Ref name (default: the file name); it may not contain `:`.
+        #[structopt(long)]
+        name: Option<String>,
+        #[structopt(flatten)]
+        store: StoreDir,
+    },
+    /// Validate then write a stored dataset (or a byte range) to stdout or a file.
+    Get {
+        /// Identifier (terrapin-sha256:...) or ref name.
+        object: String,
+        #[structopt(long)]
+        start: Option<u64>,
+        #[structopt(long)]
+        end: Option<u64>,
+        /// Output file (default: stdout).
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+        #[structopt(flatten)]
+        store: StoreDir,
+    },
+    /// List stored objects: identifier, length, and the refs pointing at each.
+    Ls {
+        #[structopt(flatten)]
+        store: StoreDir,
+    },
+    /// Delete a ref; its object is removed by the next `gc` if unreferenced.
+    Rm {
+        name: String,
+        #[structopt(flatten)]
+        store: StoreDir,
+    },
+    /// Remove every object no ref points at.
+    Gc {
+        #[structopt(flatten)]
+        store: StoreDir,
+    },
+    /// Report logical versus stored bytes and the deduplication ratio.
+    Stats {
+        #[structopt(flatten)]
+        store: StoreDir,
+    },
+}
+
+#[derive(StructOpt)]
+enum SbomCommand {
+    /// Attest each file (writing <file>.terra) and print an SBOM listing it
+    /// with its identifier.
+    Create {
+        #[structopt(parse(from_os_str), required = true)]
+        inputs: Vec<PathBuf>,
+        /// spdx (SPDX 2.3) or cyclonedx (CycloneDX 1.5).
+        #[structopt(long, default_value = "spdx")]
+        format: String,
+        /// Also record each file's plain SHA-256.
+        #[structopt(long)]
+        sha256: bool,
+        /// Document name.
+        #[structopt(long, default_value = "terrapin-sbom")]
+        name: String,
+        /// Output file (default: stdout).
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Validate every file an SBOM lists by terrapin identifier, against its
+    /// <file>.terra tree if present, else by re-hashing it.
+    Verify {
+        #[structopt(parse(from_os_str))]
+        sbom: PathBuf,
+        /// Directory the listed names are relative to.
+        #[structopt(long, parse(from_os_str), default_value = ".")]
+        root: PathBuf,
+    },
+}
+
+#[derive(StructOpt)]
+enum OciCommand {
+    /// Write <input> and its tree as layers of an artifact manifest in the
+    /// OCI image layout <layout> (created if missing); print the identifier.
+    Export {
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+        #[structopt(parse(from_os_str))]
+        layout: PathBuf,
+        /// Ref name in index.json (default: the file name).
+        #[structopt(long = "ref")]
+        reference: Option<String>,
+    },
+    /// Validate the dataset layer (or a byte range of it) in an OCI image
+    /// layout against the tree layers.
+    Verify {
+        #[structopt(parse(from_os_str))]
+        layout: PathBuf,
+        /// Ref name (default: the only terrapin dataset in the layout).
+        #[structopt(long = "ref")]
+        reference: Option<String>,
+        /// Trusted identifier; the annotated one must match it.
+        #[structopt(long)]
+        identifier: Option<String>,
+        #[structopt(long)]
+        start: Option<u64>,
+        #[structopt(long)]
+        end: Option<u64>,
+    },
+}
+
+#[derive(StructOpt)]
+struct StoreDir {
+    /// Store directory (created if missing).
+    #[structopt(long = "store", parse(from_os_str), default_value = "terrapin-store")]
+    path: PathBuf,
+}
+
+impl StoreDir {
+    fn open(&self) -> Store {
+        Store::open(&self.path).unwrap_or_else(|e| fail(&e))
+    }
+}
+
+impl Command {
+    /// The command's name in JSON output, e.g. `store add`.
+    fn name(&self) -> &'static str {
+        match self {
+            Command::Id { .. } => "id",
+            Command::Attest { .. } => "attest",
+            Command::Validate { .. } => "validate",
+            Command::Cat { .. } => "cat",
+            Command::Inspect { .. } => "inspect",
+            Command::VerifyTree { .. } => "verify-tree",
+            Command::Fetch { .. } => "fetch",
+            Command::Keygen { .. } => "keygen",
+            Command::Sign { .. } => "sign",
+            Command::VerifySig { .. } => "verify-sig",
+            Command::VerifyInToto { .. } => "verify-in-toto",
+            Command::Store(cmd) => match cmd {
+                StoreCommand::Init { .. } => "store init",
+                StoreCommand::Add { .. } => "store add",
+                StoreCommand::Get { .. } => "store get",
+                StoreCommand::Ls { .. } => "store ls",
+                StoreCommand::Rm { .. } => "store rm",
+                StoreCommand::Gc { .. } => "store gc",
+                StoreCommand::Stats { .. } => "store stats",
+            },
+            Command::Sbom(SbomCommand::Create { .. }) => "sbom create",
+            Command::Sbom(SbomCommand::Verify { .. }) => "sbom verify",
+            Command::Oci(OciCommand::Export { .. }) => "oci export",
+            Command::Oci(OciCommand::Verify { .. }) => "oci verify",
+            #[cfg(feature = "serve")]
+            Command::Serve { .. } => "serve",
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() {
-    match Command::from_args() {
-        Command::Id { input } => {
-            let reader = open(&input);
-            let id = identifier_from_reader(reader)
-                .await
-                .unwrap_or_else(|e| fail(&format!("hashing failed: {}", e)));
-            println!("{}", id);
-        }
-        Command::Attest { input, out } => {
-            let reader = open(&input);
-            let tree = build_from_reader(reader)
-                .await
-                .unwrap_or_else(|e| fail(&format!("hashing failed: {}", e)));
+    let cli = Cli::from_args();
+    output::init(cli.format == "json", cli.command.name());
+    match cli.command {
+        Command::Id {
+            input,
+            dir: true,
+            profile,
+            ..
+        } => {
+            sha256_only(&profile);
+            let listing = dir_listing(&input).unwrap_or_else(|e| fail(&e));
+            output::set("identifier", listing.identifier());
+            output::set("files", listing.entries().len());
+            output::done(&listing.identifier());
+        }
+        Command::Id {
+            input,
+            dir: false,
+            profile,
+            cache,
+        } => {
+            let cached = cache.lookup(&input, &profile);
+            if let Some(id) = cached.as_ref().and_then(|c| c.hit()) {
+                output::set("identifier", id);
+                output::set("profile", profile.as_str());
+                return output::done(id);
+            }
+            let tree = hash(&input, &profile).await;
+            built(&tree, &profile);
+            if let Some(cached) = cached {
+                cached.record(&input, &tree.identifier(), None);
+            }
+            output::done(&tree.identifier());
+        }
+        Command::Attest {
+            input,
+            out,
+            dir: true,
+            profile,
+            ..
+        } => {
+            sha256_only(&profile);
+            let base = out.unwrap_or_else(|| with_terra(&input));
+            let listing = attest_dir(&input, &base).unwrap_or_else(|e| fail(&e));
+            output::set("identifier", listing.identifier());
+            output::set("files", listing.entries().len());
+            output::set("out", base.display().to_string());
+            output::done(&listing.identifier());
+        }
+        Command::Attest {
+            input,
+            out,
+            dir: false,
+            single,
+            head_version,
+            ext,
+            profile,
+            in_toto,
+            key,
+            cache,
+        } => {
+            let format = head_format(head_version, &ext);
             let base = out.unwrap_or_else(|| with_terra(&input));
-            PersistedTree::write(&base, &tree)
-                .unwrap_or_else(|e| fail(&format!("writing tree failed: {}", e)));
-            println!("{}", tree.identifier());
+            let cached = cache.lookup(&input, &profile);
+            let reused = cached.as_ref().and_then(|c| c.tree(&base, single, &format));
+            let (identifier, length, tree_hex) = match reused {
+                Some(pt) => {
+                    output::tree(&pt);
+                    output::set("profile", profile.as_str());
+                    (pt.identifier, pt.length, pt.tree_hex)
+                }
+                None => {
+                    let tree = hash(&input, &profile).await;
+                    built(&tree, &profile);
+                    let written = if single {
+                        PersistedTree::write_single_as(&base, &tree, &format)
+                    } else {
+                        PersistedTree::write_as(&base, &tree, &format)
+                    };
+                    written.unwrap_or_else(|e| {
+                        fail(&Error::io(format!("writing tree failed: {}", e)))
+                    });
+                    if let Some(cached) = &cached {
+                        cached.record(&input, &tree.identifier(), Some(&base));
+                    }
+                    (tree.identifier(), tree.length, tree.tree_hex())
+                }
+            };
+            output::set("out", base.display().to_string());
+            if let Some(path) = in_toto {
+                let statement = InTotoStatement::for_tree(
+                    &subject_name(&input),
+                    &identifier,
+                    length,
+                    &tree_hex,
+                )
+                .unwrap_or_else(|e| fail(&e));
+                let json = match key {
+                    Some(key) => {
+                        let key = SecretKey::read(&key).unwrap_or_else(|e| fail(&e));
+                        Envelope::sign(&statement, &key)
+                            .unwrap_or_else(|e| fail(&e))
+                            .to_json()
+                    }
+                    None => statement.to_json(),
+                };
+                std::fs::write(&path, json).unwrap_or_else(|e| {
+                    fail(&Error::io(format!(
+                        "cannot write {}: {}",
+                        path.display(),
+                        e
+                    )))
+                });
+                output::set("attestation", path.display().to_string());
+            }
+            output::done(&identifier);
         }
         Command::Validate {
             input,
             tree,
             identifier,
+            member,
+            signature,
+            pubkey,
             start,
             end,
         } => {
-            let pt = PersistedTree::read(&tree).unwrap_or_else(|e| fail(&e));
-            if let Some(trusted) = identifier.as_deref() {
-                if let Err(e) = pt.check_against(trusted) {
-                    eprintln!("Validation failed: {}", e);
-                    exit(1);
+            let signed = signature.map(|sig| verified_statement(&sig, &pubkey.unwrap()));
+            let identifier = match (identifier, &signed) {
+                (Some(id), Some(st)) if id != st.identifier => invalid(&Error::mismatch(format!(
+                    "--identifier {} is not the signed identifier {}",
+                    id, st.identifier
+                ))),
+                (id, st) => id.or_else(|| st.as_ref().map(|st| st.identifier.clone())),
+            };
+            let pt = match member.as_deref() {
+                Some(path) => {
+                    let listing = DirListing::read(&with_ext(&tree, "listing"))
+                        .unwrap_or_else(|e| fail(&e));
+                    output::set("member", path);
+                    let bound = identifier
+                        .as_deref()
+                        .map_or(Ok(()), |trusted| listing.check_against(trusted))
+                        .and_then(|()| listing.member_tree(&tree, path));
+                    bound.unwrap_or_else(|e| invalid(&e))
                 }
-            }
-            match pt.validate(&input, start, end, None) {
-                Ok(()) => println!("Validation successful: the data matches the tree."),
-                Err(e) => {
-                    eprintln!("Validation failed: {}", e);
-                    exit(1);
+                None => {
+                    let pt = PersistedTree::read(&tree).unwrap_or_else(|e| fail(&e));
+                    if let Some(trusted) = identifier.as_deref() {
+                        if let Err(e) = pt.check_against(trusted) {
+                            invalid(&e);
+                        }
+                    }
+                    if let Some(st) = signed.filter(|st| st.length != pt.length) {
+                        invalid(&Error::mismatch(format!(
+                            "signed length {} but tree length {}",
+                            st.length, pt.length
+                        )));
+                    }
+                    pt
                 }
+            };
+            output::tree(&pt);
+            output::range(start, end, pt.length);
+            let mut progress = Progress::new(io::sink(), "validate", Some(pt.length));
+            match pt.validate(&input, start, end, Some(&mut progress)) {
+                Ok(()) => output::done("Validation successful: the data matches the tree."),
+                Err(e) => invalid(&e),
             }
         }
         Command::Cat {
@@ -102,29 +591,627 @@ async fn main() {
             start,
             end,
         } => {
+            output::data_on_stdout();
             let pt = PersistedTree::read(&tree).unwrap_or_else(|e| fail(&e));
+            output::tree(&pt);
+            output::range(start, end, pt.length);
             let stdout = io::stdout();
-            let mut handle = stdout.lock();
+            let mut handle = Progress::new(stdout.lock(), "validate", Some(pt.length));
             if let Err(e) = pt.validate(&input, start, end, Some(&mut handle)) {
-                eprintln!("Validation failed: {}", e);
-                exit(1);
+                invalid(&e);
             }
             let _ = handle.flush();
+            output::done("");
         }
+        Command::Inspect {
+            tree,
+            layer,
+            index,
+            offset,
+        } => inspect(&tree, layer.zip(index), offset),
+        Command::VerifyTree { tree, identifier } => {
+            let pt = PersistedTree::read(&tree).unwrap_or_else(|e| fail(&e));
+            output::tree(&pt);
+            let checked = identifier
+                .as_deref()
+                .map_or(Ok(()), |trusted| pt.check_against(trusted))
+                .and_then(|()| pt.verify_structure());
+            match checked {
+                Ok(()) => output::done("Tree verified: every layer reduces to the identifier."),
+                Err(e) => invalid(&e),
+            }
+        }
+        Command::Fetch {
+            url,
+            identifier,
+            tree_url,
+            start,
+            end,
+            output,
+        } => {
+            let tree_url = tree_url.unwrap_or_else(|| format!("{}.terra", url));
+            match fetch::run(&url, &tree_url, &identifier, start, end, &output) {
+                Ok(r) => {
+                    output::set("bytes", r.bytes);
+                    output::set("downloaded", r.downloaded);
+                    output::set("reused", r.reused);
+                    output::done(&format!(
+                        "Fetched {} verified bytes ({} blocks downloaded, {} reused).",
+                        r.bytes, r.downloaded, r.reused
+                    ));
+                }
+                Err(e) => output::failure(Some("Fetch failed"), &e),
+            }
+        }
+        Command::Keygen { out, algorithm } => {
+            let algorithm = SignatureAlgorithm::from_name(&algorithm)
+                .unwrap_or_else(|| usage(&format!("unknown algorithm {}", algorithm)));
+            let key = SecretKey::generate(algorithm).unwrap_or_else(|e| fail(&e));
+            let public = key.public_key().unwrap_or_else(|e| fail(&e));
+            let key_path = with_ext(&out, "key");
+            key.write(&key_path).unwrap_or_else(|e| fail(&e));
+            let pub_path = with_ext(&out, "pub");
+            std::fs::write(&pub_path, public.to_text()).unwrap_or_else(|e| {
+                fail(&Error::io(format!(
+                    "cannot write {}: {}",
+                    pub_path.display(),
+                    e
+                )))
+            });
+            output::set("algorithm", algorithm.name());
+            output::set("key_id", public.key_id());
+            output::set("secret_key", key_path.display().to_string());
+            output::set("public_key", pub_path.display().to_string());
+            output::done(&pub_path.display().to_string());
+        }
+        Command::Sign {
+            key,
+            tree,
+            identifier,
+            length,
+            meta,
+            output,
+        } => {
+            let key = SecretKey::read(&key).unwrap_or_else(|e| fail(&e));
+            let (identifier, length) = match tree {
+                Some(tree) => {
+                    let pt = PersistedTree::read(&tree).unwrap_or_else(|e| fail(&e));
+                    // Refuse to vouch for a head that does not bind its tree.
+                    pt.verifier().unwrap_or_else(|e| fail(&e));
+                    (pt.identifier, pt.length)
+                }
+                None => (identifier.unwrap(), length.unwrap()),
+            };
+            let mut st = Statement::new(&identifier, length).unwrap_or_else(|e| fail(&e));
+            for kv in &meta {
+                let (k, v) = kv
+                    .split_once('=')
+                    .unwrap_or_else(|| usage(&format!("--meta {:?}: expected key=value", kv)));
+                st = st.with_metadata(k, v).unwrap_or_else(|e| fail(&e));
+            }
+            output::set("identifier", identifier);
+            output::set("length", length);
+            output::set("algorithm", key.algorithm().name());
+            let text = key.sign(&st).unwrap_or_else(|e| fail(&e)).to_text();
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, text).unwrap_or_else(|e| {
+                        fail(&Error::io(format!(
+                            "cannot write {}: {}",
+                            path.display(),
+                            e
+                        )))
+                    });
+                    output::set("signature", path.display().to_string());
+                }
+                None => {
+                    output::data_on_stdout();
+                    print!("{}", text);
+                }
+            }
+            output::done("");
+        }
+        Command::VerifySig { signature, pubkey } => {
+            let st = verified_statement(&signature, &pubkey);
+            output::set("identifier", st.identifier.as_str());
+            output::set("length", st.length);
+            output::set("metadata", serde_json::json!(st.metadata));
+            output::done(&st.identifier);
+        }
+        Command::VerifyInToto {
+            input,
+            attestation,
+            pubkey,
+            tree,
+            subject,
+        } => {
+            let key = PublicKey::read(&pubkey).unwrap_or_else(|e| fail(&e));
+            let envelope = std::fs::read(&attestation)
+                .map_err(|e| Error::io(format!("cannot read {}: {}", attestation.display(), e)))
+                .and_then(|bytes| Envelope::parse(&bytes))
+                .unwrap_or_else(|e| fail(&e));
+            let statement = envelope
+                .verify(&key)
+                .unwrap_or_else(|e| output::failure(Some("Attestation verification failed"), &e));
+            let name = subject.unwrap_or_else(|| subject_name(&input));
+            output::set("subject", name.as_str());
+            let trusted = statement
+                .identifier_for(&name)
+                .unwrap_or_else(|e| fail(&e));
+            let pt = PersistedTree::read(&tree.unwrap_or_else(|| with_terra(&input)))
+                .unwrap_or_else(|e| fail(&e));
+            output::tree(&pt);
+            let mut progress = Progress::new(io::sink(), "validate", Some(pt.length));
+            match pt
+                .check_against(&trusted)
+                .and_then(|()| pt.validate(&input, None, None, Some(&mut progress)))
+            {
+                Ok(()) => output::done(&trusted),
+                Err(e) => invalid(&e),
+            }
+        }
+        Command::Store(cmd) => store(cmd),
+        Command::Sbom(cmd) => sbom(cmd),
+        Command::Oci(cmd) => oci(cmd),
+        #[cfg(feature = "serve")]
+        Command::Serve { inputs, bind } => {
+            let datasets = inputs
+                .iter()
+                .map(|input| serve::Dataset::open(input, &with_terra(input)))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap_or_else(|e| fail(&e));
+            serve::run(&bind, datasets).unwrap_or_else(|e| fail(&e));
+        }
+    }
+}
+
+/// The head format `attest --head-version/--ext` asks for.
+fn head_format(version: u32, ext: &[String]) -> HeadFormat {
+    if version == 1 {
+        if !ext.is_empty() {
+            usage("--ext needs --head-version 2");
+        }
+        return HeadFormat::V1;
+    }
+    let mut extensions = BTreeMap::new();
+    for e in ext {
+        let Some((key, value)) = e.split_once('=') else {
+            usage(&format!("--ext {}: expected <namespace>.<name>=<value>", e));
+        };
+        if extensions.contains_key(key) {
+            usage(&format!("--ext {}: repeated", key));
+        }
+        extensions.insert(key.to_string(), value.to_string());
+    }
+    HeadFormat::V2 { extensions }
+}
+
+fn inspect(base: &Path, hash: Option<(usize, u64)>, offset: Option<u64>) {
+    let pt = PersistedTree::read(base).unwrap_or_else(|e| fail(&e));
+    output::tree(&pt);
+    if let Some((layer, index)) = hash {
+        let h = pt
+            .layer_hashes(layer, index, 1)
+            .unwrap_or_else(|e| fail(&e))[0];
+        output::set("layer", layer);
+        output::set("index", index);
+        output::set("blocks_offset", pt.offsets()[layer] + index * 32);
+        output::set("hash", to_hex(&h));
+        return output::done(&to_hex(&h));
+    }
+    if let Some(offset) = offset {
+        let steps = pt.path(offset).unwrap_or_else(|e| fail(&e));
+        let mut text = format!("offset {}: block {}\n", offset, steps[0].index);
+        let mut path = Vec::new();
+        for st in &steps {
+            text += &format!(
+                "layer {}: index {}, group {}, position {}, .blocks offset {}, hash {}\n",
+                st.layer,
+                st.index,
+                st.group,
+                st.position,
+                st.blocks_offset,
+                to_hex(&st.hash)
+            );
+            path.push(serde_json::json!({
+                "layer": st.layer,
+                "index": st.index,
+                "group": st.group,
+                "position": st.position,
+                "blocks_offset": st.blocks_offset,
+                "hash": to_hex(&st.hash),
+            }));
+        }
+        text += &format!("root: {}", pt.tree_hex);
+        output::set("offset", offset);
+        output::set("block", steps[0].index);
+        output::set("path", path);
+        return output::done(&text);
+    }
+
+    let actual = pt.stored_blocks_size().ok();
+    let manifest = String::from_utf8_lossy(&pt.manifest()).into_owned();
+    output::set("version", pt.version());
+    output::set("algorithm", pt.profile().name());
+    output::set("block_size", BLOCK);
+    output::set("offsets", pt.offsets());
+    output::set("blocks_offset", pt.blocks_offset());
+    output::set(
+        "blocks_size",
+        serde_json::json!({"expected": pt.blocks_size(), "actual": actual}),
+    );
+    output::set("manifest", manifest.as_str());
+    output::set("binds", pt.binds());
+    output::set("extensions", serde_json::to_value(&pt.extensions).unwrap());
+
+    let mut text = format!("terrapin-tree: {}\n", pt.version());
+    text += &format!(
+        "algorithm: {}\nblock_size: {}\nlength: {}\ntree: {}\nidentifier: {}\n",
+        pt.profile().name(),
+        BLOCK,
+        pt.length,
+        pt.tree_hex,
+        pt.identifier
+    );
+    for (layer, (count, off)) in pt.counts.iter().zip(pt.offsets()).enumerate() {
+        text += &format!("layer {}: {} hashes at .blocks offset {}\n", layer, count, off);
+    }
+    let actual = actual.map_or("missing".to_string(), |n| n.to_string());
+    if pt.blocks_offset() > 0 {
+        text += &format!("single file: .blocks at byte {}\n", pt.blocks_offset());
+    }
+    text += &format!("blocks_size: expected {}, actual {}\n", pt.blocks_size(), actual);
+    text += "manifest:\n";
+    for line in manifest.lines() {
+        text += &format!("  {}\n", line);
+    }
+    for (key, value) in &pt.extensions {
+        text += &format!("ext.{}: {}\n", key, value);
+    }
+    let binds = if pt.binds() { "yes" } else { "no" };
+    text += &format!("identifier binds to manifest: {}", binds);
+    output::done(&text);
+}
+
+fn oci(cmd: OciCommand) {
+    match cmd {
+        OciCommand::Export {
+            input,
+            layout,
+            reference,
+        } => {
+            let export = terrapin::oci_export(&input, &layout, reference.as_deref())
+                .unwrap_or_else(|e| fail(&e));
+            output::set("identifier", export.identifier.as_str());
+            output::set("manifest_digest", export.manifest_digest);
+            output::set("reference", export.reference);
+            output::done(&export.identifier);
+        }
+        OciCommand::Verify {
+            layout,
+            reference,
+            identifier,
+            start,
+            end,
+        } => {
+            let dataset =
+                OciDataset::open(&layout, reference.as_deref()).unwrap_or_else(|e| fail(&e));
+            output::set("reference", dataset.reference.as_str());
+            output::set("manifest_digest", dataset.manifest_digest.as_str());
+            output::tree(dataset.tree());
+            output::range(start, end, dataset.tree().length);
+            let mut progress = Progress::new(io::sink(), "validate", Some(dataset.tree().length));
+            let checked = identifier
+                .as_deref()
+                .map_or(Ok(()), |trusted| dataset.tree().check_against(trusted))
+                .and_then(|()| dataset.validate(start, end, Some(&mut progress)));
+            match checked {
+                Ok(()) => output::done("Validation successful: the data matches the tree."),
+                Err(e) => invalid(&e),
+            }
+        }
+    }
+}
+
+fn sbom(cmd: SbomCommand) {
+    match cmd {
+        SbomCommand::Create {
+            inputs,
+            format,
+            sha256,
+            name,
+            output,
+        } => {
+            let format = SbomFormat::from_name(&format)
+                .unwrap_or_else(|| usage(&format!("unknown SBOM format {}", format)));
+            let entries = inputs
+                .iter()
+                .map(|input| {
+                    let listed = input.to_string_lossy().replace('\\', "/");
+                    let entry = SbomEntry::attest(input, &listed, &with_terra(input), sha256)?;
+                    output::event(
+                        "entry",
+                        serde_json::json!({"name": entry.name, "identifier": entry.identifier}),
+                    );
+                    Ok(entry)
+                })
+                .collect::<Result<Vec<_>, Error>>()
+                .unwrap_or_else(|e| fail(&e));
+            output::set("files", entries.len());
+            let doc = Sbom { name, entries }.to_json(format);
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, doc).unwrap_or_else(|e| {
+                        fail(&Error::io(format!(
+                            "cannot write {}: {}",
+                            path.display(),
+                            e
+                        )))
+                    });
+                    output::set("sbom", path.display().to_string());
+                    output::done("");
+                }
+                None => {
+                    output::data_on_stdout();
+                    println!("{}", String::from_utf8_lossy(&doc));
+                    output::done("");
+                }
+            }
+        }
+        SbomCommand::Verify { sbom, root } => {
+            let doc = std::fs::read(&sbom)
+                .map_err(|e| Error::io(format!("cannot read {}: {}", sbom.display(), e)))
+                .and_then(|bytes| Sbom::parse(&bytes))
+                .unwrap_or_else(|e| fail(&e));
+            if doc.entries.is_empty() {
+                fail(&Error::malformed(format!(
+                    "{} lists no terrapin identifiers",
+                    sbom.display()
+                )));
+            }
+            let mut failed = 0;
+            for entry in &doc.entries {
+                let path = root.join(&entry.name);
+                let result = entry.validate(&path, &with_terra(&path));
+                output::event(
+                    "entry",
+                    serde_json::json!({
+                        "name": entry.name,
+                        "identifier": entry.identifier,
+                        "outcome": if result.is_ok() { "ok" } else { "failed" },
+                        "error": result.as_ref().err().map(|e| e.message()),
+                    }),
+                );
+                match result {
+                    Ok(()) if output::json() => {}
+                    Ok(()) => println!("ok {}", entry.name),
+                    Err(e) => {
+                        if !output::json() {
+                            println!("FAILED {}: {}", entry.name, e);
+                        }
+                        failed += 1;
+                    }
+                }
+            }
+            output::set("files", doc.entries.len());
+            output::set("failed", failed);
+            if failed > 0 {
+                invalid(&Error::validation(format!(
+                    "{} of {} files",
+                    failed,
+                    doc.entries.len()
+                )));
+            }
+            output::done("");
+        }
+    }
+}
+
+fn store(cmd: StoreCommand) {
+    match cmd {
+        StoreCommand::Init { blocks, store } => {
+            let mode = if blocks {
+                StoreMode::Blocks
+            } else {
+                StoreMode::Whole
+            };
+            Store::init(&store.path, mode).unwrap_or_else(|e| fail(&e));
+            output::set("store", store.path.display().to_string());
+            output::set("mode", mode.as_str());
+            output::done("");
+        }
+        StoreCommand::Add { input, name, store } => {
+            let added = store
+                .open()
+                .add(&input, name.as_deref())
+                .unwrap_or_else(|e| fail(&e));
+            if added.deduplicated && !output::json() {
+                eprintln!("already stored; ref updated");
+            }
+            output::set("identifier", added.identifier.as_str());
+            output::set("length", added.length);
+            output::set("deduplicated", added.deduplicated);
+            output::done(&added.identifier);
+        }
+        StoreCommand::Get {
+            object,
+            start,
+            end,
+            output,
+            store,
+        } => {
+            let st = store.open();
+            let id = if object.contains(':') {
+                object
+            } else {
+                st.resolve(&object).unwrap_or_else(|e| fail(&e))
+            };
+            output::set("identifier", id.as_str());
+            let result = match output {
+                Some(path) => {
+                    let f = File::create(&path).unwrap_or_else(|e| {
+                        fail(&Error::io(format!(
+                            "cannot create {}: {}",
+                            path.display(),
+                            e
+                        )))
+                    });
+                    let mut f = Progress::new(f, "validate", None);
+                    st.get(&id, start, end, Some(&mut f))
+                }
+                None => {
+                    output::data_on_stdout();
+                    let stdout = io::stdout();
+                    let mut handle = Progress::new(stdout.lock(), "validate", None);
+                    let r = st.get(&id, start, end, Some(&mut handle));
+                    let _ = handle.flush();
+                    r
+                }
+            };
+            if let Err(e) = result {
+                invalid(&e);
+            }
+            output::done("");
+        }
+        StoreCommand::Ls { store } => {
+            let entries = store.open().list().unwrap_or_else(|e| fail(&e));
+            if output::json() {
+                let objects: Vec<_> = entries
+                    .iter()
+                    .map(|e| {
+                        serde_json::json!({
+                            "identifier": e.identifier,
+                            "length": e.length,
+                            "refs": e.refs,
+                        })
+                    })
+                    .collect();
+                output::set("objects", objects);
+            }
+            for entry in entries.iter().filter(|_| !output::json()) {
+                let refs = if entry.refs.is_empty() {
+                    "-".to_string()
+                } else {
+                    entry.refs.join(",")
+                };
+                println!("{} {} {}", entry.identifier, entry.length, refs);
+            }
+            output::done("");
+        }
+        StoreCommand::Rm { name, store } => {
+            store.open().remove_ref(&name).unwrap_or_else(|e| fail(&e));
+            output::set("ref", name);
+            output::done("");
+        }
+        StoreCommand::Gc { store } => {
+            let removed = store.open().gc().unwrap_or_else(|e| fail(&e));
+            if !output::json() {
+                for id in &removed {
+                    println!("removed {}", id);
+                }
+                eprintln!("Removed {} unreferenced objects.", removed.len());
+            }
+            output::set("removed", removed);
+            output::done("");
+        }
+        StoreCommand::Stats { store } => {
+            let st = store.open();
+            let stats = st.stats().unwrap_or_else(|e| fail(&e));
+            if output::json() {
+                output::set("mode", st.mode().as_str());
+                output::set("objects", stats.objects);
+                output::set("logical_bytes", stats.logical_bytes);
+                output::set("stored_bytes", stats.stored_bytes);
+                output::set("blocks", stats.blocks);
+                output::set("unique_blocks", stats.unique_blocks);
+                output::set("dedup_ratio", stats.dedup_ratio());
+            } else {
+                println!("mode: {}", st.mode().as_str());
+                println!("objects: {}", stats.objects);
+                println!("logical_bytes: {}", stats.logical_bytes);
+                println!("stored_bytes: {}", stats.stored_bytes);
+                println!("blocks: {}", stats.blocks);
+                println!("unique_blocks: {}", stats.unique_blocks);
+                println!("dedup_ratio: {:.2}", stats.dedup_ratio());
+            }
+            output::done("");
+        }
+    }
+}
+
+/// The statement in the signature file at `sig`, once it verifies with the
+/// public key at `pubkey`; exits otherwise.
+fn verified_statement(sig: &Path, pubkey: &Path) -> Statement {
+    let key = PublicKey::read(pubkey).unwrap_or_else(|e| fail(&e));
+    let signed = SignedStatement::read(sig).unwrap_or_else(|e| fail(&e));
+    output::set("key_id", key.key_id());
+    match signed.verify(&key) {
+        Ok(st) => st.clone(),
+        Err(e) => output::failure(Some("Signature verification failed"), &e),
+    }
+}
+
+/// Hash `input` with the named profile, reporting progress.
+async fn hash(input: &Path, profile: &str) -> BuiltTree {
+    let file = open(input);
+    let total = file.metadata().ok().map(|m| m.len());
+    // Regular files are read by offset, skipping holes; pipes are streamed.
+    build_from_file_with(lookup_profile(profile), Progress::new(file, "hash", total))
+        .await
+        .unwrap_or_else(|e| fail(&Error::io(format!("hashing failed: {}", e))))
+}
+
+/// Record a freshly built tree in the JSON output.
+fn built(tree: &BuiltTree, profile: &str) {
+    let counts: Vec<u64> = tree.layers.iter().map(|l| l.len() as u64 / 32).collect();
+    output::tree_parts(&tree.identifier(), tree.length, &tree.tree_hex(), &counts);
+    output::set("profile", profile);
+}
+
+/// The in-toto subject name of `input`: its file name.
+fn subject_name(input: &Path) -> String {
+    input
+        .file_name()
+        .unwrap_or(input.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn lookup_profile(name: &str) -> &'static dyn Profile {
+    profile_by_name(name).unwrap_or_else(|| usage(&format!("unknown profile {}", name)))
+}
+
+/// Directory mode lists terrapin-sha256 member identifiers only.
+fn sha256_only(profile: &str) {
+    if profile != "terrapin-sha256" {
+        usage("--dir supports only the terrapin-sha256 profile");
     }
 }
 
 fn open(path: &Path) -> File {
-    File::open(path).unwrap_or_else(|e| fail(&format!("cannot open {}: {}", path.display(), e)))
+    File::open(path)
+        .unwrap_or_else(|e| fail(&Error::io(format!("cannot open {}: {}", path.display(), e))))
 }
 
 fn with_terra(input: &Path) -> PathBuf {
-    let mut s = input.as_os_str().to_os_string();
-    s.push(".terra");
+    with_ext(input, "terra")
+}
+
+fn with_ext(name: &Path, ext: &str) -> PathBuf {
+    let mut s = name.as_os_str().to_os_string();
+    s.push(".");
+    s.push(ext);
     PathBuf::from(s)
 }
 
-fn fail(msg: &str) -> ! {
-    eprintln!("{}", msg);
-    exit(1);
+fn fail(e: &Error) -> ! {
+    output::failure(None, e)
+}
+
+fn usage(msg: &str) -> ! {
+    output::usage(msg)
+}
+
+fn invalid(e: &Error) -> ! {
+    output::failure(Some("Validation failed"), e)
 }
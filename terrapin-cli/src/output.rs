@@ -0,0 +1,264 @@
+//! `--format json`: one JSON object per command, plus JSON-lines events for
+//! long operations.
+//!
+//! In text mode commands print exactly what they always have. In JSON mode a
+//! command prints a single object when it finishes, whether it succeeded or
+//! not:
+//!
+//! ```text
+//! {"command":"validate","outcome":"ok","identifier":"terrapin-sha256:…",
+//!  "length":5,"tree":"…","layer_counts":[1],"range":{"start":0,"end":5},
+//!  "elapsed_ms":0}
+//! ```
+//!
+//! `command`, `outcome` (`ok` or `failed`) and `elapsed_ms` are always present;
+//! the rest is whatever the command had established when it finished. A
+//! failure carries `error: {kind, message}`, plus `block` and `layer` when the
+//! error names the failing block. The kind is the [`terrapin::ErrorKind`] the
+//! library raised (see [`terrapin::ErrorKind::name`]), or `usage` for a bad
+//! command line. Before
+//! that, long operations print `{"event":…}` lines: hashing and validation
+//! every 64 MiB, `fetch` per block, SBOMs per entry.
+//!
+//! Objects and events go to stdout, or to stderr when stdout carries data
+//! (`cat`, and `sign` / `store get` / `sbom create` without `-o`).
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::process::exit;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use serde_json::{json, Map, Value};
+use terrapin::{Error, PathLayer, PersistedTree, SparseSource};
+
+/// Bytes hashed or validated between two progress events.
+const PROGRESS_EVERY: u64 = 64 << 20;
+
+static JSON: AtomicBool = AtomicBool::new(false);
+static STATE: Mutex<Option<State>> = Mutex::new(None);
+
+struct State {
+    command: &'static str,
+    started: Instant,
+    fields: Map<String, Value>,
+    stderr: bool,
+}
+
+/// Select the output format for `command` and start its clock.
+pub fn init(json: bool, command: &'static str) {
+    JSON.store(json, Ordering::Relaxed);
+    *STATE.lock().unwrap() = Some(State {
+        command,
+        started: Instant::now(),
+        fields: Map::new(),
+        stderr: false,
+    });
+}
+
+/// Whether `--format json` is in effect.
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Stdout carries the command's data: print JSON to stderr instead.
+pub fn data_on_stdout() {
+    with_state(|s| s.stderr = true);
+}
+
+/// Record a field of the command's result object.
+pub fn set(key: &str, value: impl Into<Value>) {
+    let value = value.into();
+    with_state(|s| {
+        s.fields.insert(key.to_string(), value);
+    });
+}
+
+/// Record a tree's identifier, length, root and layer counts.
+pub fn tree(pt: &PersistedTree) {
+    tree_parts(&pt.identifier, pt.length, &pt.tree_hex, &pt.counts);
+}
+
+/// [`tree`] from its parts, e.g. of a freshly built tree.
+pub fn tree_parts(identifier: &str, length: u64, tree_hex: &str, counts: &[u64]) {
+    set("identifier", identifier);
+    set("length", length);
+    set("tree", tree_hex);
+    set("layer_counts", counts);
+}
+
+/// Record the byte range `[start, end)` a command covers, defaults resolved
+/// against `length`.
+pub fn range(start: Option<u64>, end: Option<u64>, length: u64) {
+    set(
+        "range",
+        json!({"start": start.unwrap_or(0), "end": end.unwrap_or(length)}),
+    );
+}
+
+/// Finish successfully: print `text` (if any) in text mode, the result object
+/// in JSON mode.
+pub fn done(text: &str) {
+    if json() {
+        emit(result("ok", None));
+    } else if !text.is_empty() {
+        println!("{}", text);
+    }
+}
+
+/// Finish with `e` and exit 1. Text mode prints `prefix: message` (or just
+/// the message) to stderr.
+pub fn failure(prefix: Option<&str>, e: &Error) -> ! {
+    finish(prefix, e.kind().name(), e)
+}
+
+/// Finish with a command-line error, kind `usage`, and exit 1.
+pub fn usage(msg: &str) -> ! {
+    finish(None, "usage", &Error::other(msg))
+}
+
+fn finish(prefix: Option<&str>, kind: &str, e: &Error) -> ! {
+    if json() {
+        let mut error = json!({
+            "kind": kind,
+            "message": e.message(),
+        });
+        if let Some(block) = e.block() {
+            error["block"] = block.into();
+            match e.layer() {
+                Some(PathLayer::Hashes(layer)) => error["layer"] = layer.into(),
+                Some(PathLayer::Root) => error["layer"] = "root".into(),
+                None => {}
+            }
+        }
+        emit(result("failed", Some(error)));
+    } else {
+        match prefix {
+            Some(p) => eprintln!("{}: {}", p, e),
+            None => eprintln!("{}", e),
+        }
+    }
+    exit(1);
+}
+
+/// Print a JSON-lines event (JSON mode only).
+pub fn event(name: &str, fields: Value) {
+    if !json() {
+        return;
+    }
+    let mut ev = json!({"event": name});
+    with_state(|s| {
+        ev["command"] = s.command.into();
+        ev["elapsed_ms"] = elapsed_ms(s).into();
+    });
+    if let Value::Object(fields) = fields {
+        ev.as_object_mut().unwrap().extend(fields);
+    }
+    emit(ev);
+}
+
+/// Counts bytes passing through a reader or writer and reports them as
+/// `progress` events of `phase` every 64 MiB.
+pub struct Progress<T> {
+    inner: T,
+    phase: &'static str,
+    total: Option<u64>,
+    bytes: u64,
+    next: u64,
+}
+
+impl<T> Progress<T> {
+    pub fn new(inner: T, phase: &'static str, total: Option<u64>) -> Self {
+        Progress {
+            inner,
+            phase,
+            total,
+            bytes: 0,
+            next: PROGRESS_EVERY,
+        }
+    }
+
+    fn advance(&mut self, n: usize) {
+        self.bytes += n as u64;
+        if self.bytes >= self.next {
+            self.next = (self.bytes / PROGRESS_EVERY + 1) * PROGRESS_EVERY;
+            event(
+                "progress",
+                json!({"phase": self.phase, "bytes": self.bytes, "total": self.total}),
+            );
+        }
+    }
+}
+
+impl<R: Read> Read for Progress<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.advance(n);
+        Ok(n)
+    }
+}
+
+impl<W: Write> Write for Progress<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.advance(n);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<S: Seek> Seek for Progress<S> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// Holes are skipped unread, so progress counts only the data read.
+impl<S: SparseSource> SparseSource for Progress<S> {
+    fn data_region(&mut self, offset: u64) -> io::Result<Option<(u64, u64)>> {
+        self.inner.data_region(offset)
+    }
+
+    fn length(&mut self) -> Option<u64> {
+        self.inner.length()
+    }
+}
+
+fn result(outcome: &str, error: Option<Value>) -> Value {
+    let mut obj = Map::new();
+    with_state(|s| {
+        obj.insert("command".into(), s.command.into());
+        obj.insert("outcome".into(), outcome.into());
+        obj.extend(s.fields.clone());
+        if let Some(error) = error {
+            obj.insert("error".into(), error);
+        }
+        obj.insert("elapsed_ms".into(), elapsed_ms(s).into());
+    });
+    Value::Object(obj)
+}
+
+fn emit(value: Value) {
+    let to_stderr = STATE.lock().unwrap().as_ref().is_some_and(|s| s.stderr);
+    if to_stderr {
+        eprintln!("{}", value);
+    } else {
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        let _ = writeln!(handle, "{}", value);
+        let _ = handle.flush();
+    }
+}
+
+fn elapsed_ms(s: &State) -> u64 {
+    s.started.elapsed().as_millis() as u64
+}
+
+fn with_state(f: impl FnOnce(&mut State)) {
+    if let Some(s) = STATE.lock().unwrap().as_mut() {
+        f(s);
+    }
+}
@@ -0,0 +1,266 @@
+//! `terrapin serve`: an HTTP server that never returns an unverified data byte.
+//!
+//! Each dataset, named by its file name `<name>`, is exposed at:
+//!
+//! * `/<name>` — the data, with single `Range` support. Every byte is checked
+//!   against the published tree before it is sent.
+//! * `/<name>.terra.head`, `/<name>.terra.blocks` — the tree artifact, also
+//!   range-fetchable so a client can pull only the hash-file groups it needs.
+//!   A single-file tree (`attest --single`) is served as `/<name>.terra`
+//!   instead, range-fetchable the same way.
+//! * `/<name>.terra.id` — the identifier, one line.
+//!
+//! `/.well-known/terrapin` lists `<identifier> <name>` for every dataset.
+//!
+//! The first block of a data request is validated before the status line is
+//! written, so a corrupt start yields `500` with the validation error instead
+//! of bytes. The range is then streamed through [`PersistedTree::validate`],
+//! which emits a block only after verifying it: a later block that fails
+//! aborts the connection short of `Content-Length` rather than leaking. A
+//! `HEAD` reads no data at all.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+use terrapin::{Error, PersistedTree, BLOCK};
+
+use crate::http::{self, Request};
+use crate::{output, with_ext};
+
+/// One served dataset: its data file and opened tree.
+pub struct Dataset {
+    name: String,
+    data: PathBuf,
+    tree_base: PathBuf,
+    tree: PersistedTree,
+}
+
+impl Dataset {
+    /// Open the tree at `tree_base` for the data file `data`.
+    pub fn open(data: &Path, tree_base: &Path) -> Result<Dataset, Error> {
+        let name = data
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| Error::other(format!("{}: not a servable file name", data.display())))?
+            .to_string();
+        let tree = PersistedTree::read(tree_base)?;
+        Ok(Dataset {
+            name,
+            data: data.to_path_buf(),
+            tree_base: tree_base.to_path_buf(),
+            tree,
+        })
+    }
+}
+
+/// What a request path resolves to.
+enum Route<'a> {
+    Index,
+    Data(&'a Dataset),
+    Artifact(PathBuf),
+    Identifier(&'a Dataset),
+}
+
+/// Bind `addr`, announce the bound address on stdout, and serve forever.
+pub fn run(addr: &str, datasets: Vec<Dataset>) -> Result<(), Error> {
+    for (i, d) in datasets.iter().enumerate() {
+        if datasets[..i].iter().any(|o| o.name == d.name) {
+            return Err(Error::other(format!(
+                "serve: duplicate dataset name {}",
+                d.name
+            )));
+        }
+    }
+    let listener =
+        TcpListener::bind(addr).map_err(|e| Error::io(format!("cannot bind {}: {}", addr, e)))?;
+    let local = listener
+        .local_addr()
+        .map_err(|e| Error::io(format!("cannot bind {}: {}", addr, e)))?;
+    if output::json() {
+        output::event(
+            "listening",
+            serde_json::json!({"address": local.to_string()}),
+        );
+    } else {
+        println!("listening on http://{}", local);
+    }
+    let _ = io::stdout().flush();
+
+    let datasets = Arc::new(datasets);
+    for conn in listener.incoming() {
+        let stream = match conn {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("accept: {}", e);
+                continue;
+            }
+        };
+        let datasets = Arc::clone(&datasets);
+        thread::spawn(move || {
+            if let Err(e) = handle(stream, &datasets) {
+                eprintln!("connection: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle(stream: TcpStream, datasets: &[Dataset]) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut w = stream;
+    let req = match http::read_request(&mut reader) {
+        Ok(r) => r,
+        Err(e) => return http::write_text(&mut w, 400, &format!("{}\n", e), false),
+    };
+    let head_only = match req.method.as_str() {
+        "GET" => false,
+        "HEAD" => true,
+        _ => return http::write_text(&mut w, 405, "only GET and HEAD are supported\n", false),
+    };
+    match route(&req.path, datasets) {
+        None => http::write_text(&mut w, 404, "not found\n", head_only),
+        Some(Route::Index) => {
+            let body: String = datasets
+                .iter()
+                .map(|d| format!("{} {}\n", d.tree.identifier, d.name))
+                .collect();
+            http::write_text(&mut w, 200, &body, head_only)
+        }
+        Some(Route::Identifier(d)) => {
+            http::write_text(&mut w, 200, &format!("{}\n", d.tree.identifier), head_only)
+        }
+        Some(Route::Artifact(path)) => send_file(&mut w, &path, &req, head_only),
+        Some(Route::Data(d)) => send_data(w, d, &req, head_only),
+    }
+}
+
+fn route<'a>(path: &str, datasets: &'a [Dataset]) -> Option<Route<'a>> {
+    let path = path.split('?').next().unwrap_or_default();
+    if path == "/.well-known/terrapin" {
+        return Some(Route::Index);
+    }
+    let rel = path.strip_prefix('/')?;
+    for d in datasets {
+        if rel == d.name {
+            return Some(Route::Data(d));
+        }
+        let single = d.tree.blocks_offset() > 0;
+        match rel.strip_prefix(d.name.as_str()) {
+            Some(".terra") if single => return Some(Route::Artifact(d.tree_base.clone())),
+            Some(".terra.head") if !single => {
+                return Some(Route::Artifact(with_ext(&d.tree_base, "head")))
+            }
+            Some(".terra.blocks") if !single => {
+                return Some(Route::Artifact(with_ext(&d.tree_base, "blocks")))
+            }
+            Some(".terra.id") => return Some(Route::Identifier(d)),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Serve a tree artifact file as-is (it is the evidence, not the data).
+fn send_file(w: &mut TcpStream, path: &Path, req: &Request, head_only: bool) -> io::Result<()> {
+    let mut f = match File::open(path) {
+        Ok(f) => f,
+        Err(e) => {
+            let msg = format!("cannot open {}: {}\n", path.display(), e);
+            return http::write_text(w, 500, &msg, head_only);
+        }
+    };
+    let len = f.metadata()?.len();
+    let (status, start, end) = match http::parse_range(req.header("Range"), len) {
+        Ok(Some((s, e))) => (206, s, e),
+        Ok(None) => (200, 0, len),
+        Err(e) => return unsatisfiable(w, len, &e, head_only),
+    };
+    let mut headers = vec![
+        ("Content-Type", "application/octet-stream".to_string()),
+        ("Accept-Ranges", "bytes".to_string()),
+        ("Content-Length", (end - start).to_string()),
+    ];
+    if status == 206 {
+        headers.push(("Content-Range", content_range(start, end, len)));
+    }
+    http::write_head(w, status, &headers)?;
+    if !head_only {
+        f.seek(SeekFrom::Start(start))?;
+        io::copy(&mut f.take(end - start), w)?;
+    }
+    w.flush()
+}
+
+/// Serve data bytes, verified against the dataset's tree.
+fn send_data(mut w: TcpStream, d: &Dataset, req: &Request, head_only: bool) -> io::Result<()> {
+    let len = d.tree.length;
+    let (status, start, end) = match http::parse_range(req.header("Range"), len) {
+        Ok(Some((s, e))) => (206, s, e),
+        Ok(None) => (200, 0, len),
+        Err(e) => return unsatisfiable(&mut w, len, &e, head_only),
+    };
+
+    // Prove the first block before committing to a status; the rest is
+    // checked while streaming, so large ranges start sending at once.
+    if !head_only && start < end {
+        let first_end = end.min((start / BLOCK as u64 + 1) * BLOCK as u64);
+        if let Err(e) = d.tree.validate(&d.data, Some(start), Some(first_end), None) {
+            eprintln!("{}: validation failed: {}", d.name, e);
+            let msg = format!("Validation failed: {}\n", e);
+            return http::write_text(&mut w, 500, &msg, false);
+        }
+    }
+
+    let mut headers = vec![
+        ("Content-Type", "application/octet-stream".to_string()),
+        ("Accept-Ranges", "bytes".to_string()),
+        ("Content-Length", (end - start).to_string()),
+        ("X-Terrapin-Identifier", d.tree.identifier.clone()),
+    ];
+    if status == 206 {
+        headers.push(("Content-Range", content_range(start, end, len)));
+    }
+    http::write_head(&mut w, status, &headers)?;
+    if head_only {
+        return w.flush();
+    }
+
+    // Stream, verifying each block before it is written.
+    let mut out = BufWriter::new(&mut w);
+    let streamed = d
+        .tree
+        .validate(&d.data, Some(start), Some(end), Some(&mut out));
+    let flushed = out.flush();
+    drop(out);
+    if let Err(e) = streamed {
+        eprintln!("{}: aborted mid-response: {}", d.name, e);
+        let _ = w.shutdown(Shutdown::Both);
+        return Ok(());
+    }
+    flushed
+}
+
+fn unsatisfiable(w: &mut TcpStream, len: u64, msg: &str, head_only: bool) -> io::Result<()> {
+    let body = format!("{}\n", msg);
+    http::write_head(
+        w,
+        416,
+        &[
+            ("Content-Type", "text/plain; charset=utf-8".into()),
+            ("Content-Length", body.len().to_string()),
+            ("Content-Range", format!("bytes */{}", len)),
+        ],
+    )?;
+    if !head_only {
+        w.write_all(body.as_bytes())?;
+    }
+    w.flush()
+}
+
+fn content_range(start: u64, end: u64, len: u64) -> String {
+    format!("bytes {}-{}/{}", start, end - 1, len)
+}
@@ -4,7 +4,7 @@
 //! external test crates) and use the `terrapin` library only to compute
 //! expected values. `cargo test` builds the binary dependency automatically.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Output};
 use std::sync::atomic::{AtomicU64, Ordering};
 
@@ -53,12 +53,12 @@ fn write_temp(label: &str, data: &[u8]) -> PathBuf {
     p
 }
 
-fn s(p: &PathBuf) -> &str {
+fn s(p: &Path) -> &str {
     p.to_str().expect("path is valid utf8")
 }
 
 /// Attest `data_path` to base `base` and assert it succeeds.
-fn attest_to(data_path: &PathBuf, base: &PathBuf) {
+fn attest_to(data_path: &Path, base: &Path) {
     let out = run(&["attest", s(data_path), "--out", s(base)]);
     assert!(
         out.status.success(),
@@ -75,7 +75,7 @@ fn stderr_str(out: &Output) -> String {
     String::from_utf8_lossy(&out.stderr).to_string()
 }
 
-fn cleanup_base(base: &PathBuf) {
+fn cleanup_base(base: &Path) {
     let mut head = base.as_os_str().to_os_string();
     head.push(".head");
     let mut blocks = base.as_os_str().to_os_string();
@@ -383,3 +383,691 @@ fn validate_enforces_trusted_identifier() {
     cleanup_base(&base);
     let _ = std::fs::remove_file(&f);
 }
+
+// Verifies: REQ-CLI-016
+#[test]
+fn inspect_shows_layout_hash_and_path() {
+    let data = xorshift_bytes(2 * BLOCK + 10, 67);
+    let f = write_temp("inspect", &data);
+    let base = unique_path("inspecttree");
+    assert!(run(&["attest", s(&f), "--out", s(&base)]).status.success());
+    let id = terrapin::identifier(&data);
+
+    let out = run(&["inspect", s(&base)]);
+    assert!(out.status.success(), "{}", stderr_str(&out));
+    let text = stdout_str(&out);
+    assert!(text.contains(&format!("identifier: {}\n", id)), "{}", text);
+    assert!(text.contains("layer 0: 3 hashes at .blocks offset 0\n"), "{}", text);
+    assert!(text.contains("blocks_size: expected 96, actual 96\n"), "{}", text);
+    assert!(text.contains("  block_size: 2097152\n"), "manifest shown: {}", text);
+    assert!(text.ends_with("identifier binds to manifest: yes\n"), "{}", text);
+
+    let tree = terrapin::PersistedTree::read(&base).unwrap();
+    let leaf = terrapin::to_hex(&tree.leaf_hashes(1, 1).unwrap()[0]);
+    let out = run(&["inspect", s(&base), "--layer", "0", "--index", "1"]);
+    assert_eq!(stdout_str(&out).trim(), leaf);
+
+    let offset = (BLOCK + 5).to_string();
+    let out = run(&["--format", "json", "inspect", s(&base), "--offset", &offset]);
+    let v: serde_json::Value = serde_json::from_str(stdout_str(&out).trim()).unwrap();
+    assert_eq!(v["block"], 1);
+    assert_eq!(v["path"][0]["position"], 1);
+    assert_eq!(v["path"][0]["hash"], leaf.as_str());
+    let past = data.len().to_string();
+    assert!(!run(&["inspect", s(&base), "--offset", &past]).status.success());
+
+    // Trailing garbage in .blocks shows up as a size mismatch.
+    let mut blocks = base.as_os_str().to_os_string();
+    blocks.push(".blocks");
+    let mut raw = std::fs::read(&blocks).unwrap();
+    raw.push(0);
+    std::fs::write(&blocks, raw).unwrap();
+    let out = run(&["--format", "json", "inspect", s(&base)]);
+    let v: serde_json::Value = serde_json::from_str(stdout_str(&out).trim()).unwrap();
+    assert_eq!(v["blocks_size"], serde_json::json!({"expected": 96, "actual": 97}));
+    assert_eq!(v["binds"], true);
+
+    cleanup_base(&base);
+    let _ = std::fs::remove_file(&f);
+}
+
+// Verifies: REQ-CLI-017
+#[test]
+fn verify_tree_checks_structure_without_data() {
+    let data = xorshift_bytes(3 * BLOCK, 71);
+    let f = write_temp("vtree", &data);
+    let base = unique_path("vtreetree");
+    assert!(run(&["attest", s(&f), "--out", s(&base)]).status.success());
+    let _ = std::fs::remove_file(&f);
+
+    let ok = run(&["verify-tree", s(&base)]);
+    assert!(ok.status.success(), "{}", stderr_str(&ok));
+    let id = terrapin::identifier(&data);
+    assert!(run(&["verify-tree", s(&base), "--identifier", &id]).status.success());
+    let wrong = format!("terrapin-sha256:{}", "0".repeat(64));
+    let bad = run(&["verify-tree", s(&base), "--identifier", &wrong]);
+    assert!(!bad.status.success());
+
+    let mut blocks = base.as_os_str().to_os_string();
+    blocks.push(".blocks");
+    let mut raw = std::fs::read(&blocks).unwrap();
+    raw.extend_from_slice(b"junk");
+    std::fs::write(&blocks, &raw).unwrap();
+    let bad = run(&["--format", "json", "verify-tree", s(&base)]);
+    assert!(!bad.status.success());
+    let v: serde_json::Value = serde_json::from_str(stdout_str(&bad).trim()).unwrap();
+    assert_eq!(v["error"]["kind"], "malformed");
+
+    raw.truncate(raw.len() - 4);
+    raw[40] ^= 1;
+    std::fs::write(&blocks, &raw).unwrap();
+    let bad = run(&["verify-tree", s(&base)]);
+    assert!(stderr_str(&bad).contains("Validation failed"), "{}", stderr_str(&bad));
+
+    cleanup_base(&base);
+}
+
+// Verifies: REQ-CLI-018
+#[test]
+fn attest_single_writes_one_file() {
+    let data = xorshift_bytes(BLOCK + 40, 73);
+    let f = write_temp("single", &data);
+    let tree = unique_path("singletree");
+    let out = run(&["attest", s(&f), "--out", s(&tree), "--single"]);
+    assert!(out.status.success(), "{}", stderr_str(&out));
+    assert_eq!(stdout_str(&out).trim(), terrapin::identifier(&data));
+    assert!(tree.is_file());
+    let mut head = tree.as_os_str().to_os_string();
+    head.push(".head");
+    assert!(!PathBuf::from(head).exists());
+
+    assert!(run(&["validate", s(&f), "--tree", s(&tree)]).status.success());
+    let cat = run(&["cat", s(&f), "--tree", s(&tree), "--start", "10", "--end", "20"]);
+    assert_eq!(cat.stdout, &data[10..20]);
+    assert!(run(&["verify-tree", s(&tree)]).status.success());
+    let inspect = stdout_str(&run(&["inspect", s(&tree)]));
+    assert!(inspect.contains("single file: .blocks at byte 4096\n"), "{}", inspect);
+    assert!(inspect.contains("blocks_size: expected 64, actual 64\n"), "{}", inspect);
+
+    let _ = std::fs::remove_file(&tree);
+    let _ = std::fs::remove_file(&f);
+}
+
+// Verifies: REQ-CLI-019
+#[test]
+fn attest_head_version_2_with_extensions() {
+    let data = xorshift_bytes(300, 74);
+    let f = write_temp("headv2", &data);
+    let tree = unique_path("headv2tree");
+    let out = run(&[
+        "attest",
+        s(&f),
+        "--out",
+        s(&tree),
+        "--head-version",
+        "2",
+        "--ext",
+        "terrapin.media_type=application/octet-stream",
+        "--ext",
+        "acme.build=7",
+    ]);
+    assert!(out.status.success(), "{}", stderr_str(&out));
+    assert!(run(&["validate", s(&f), "--tree", s(&tree)]).status.success());
+    let inspect = stdout_str(&run(&["inspect", s(&tree)]));
+    assert!(inspect.starts_with("terrapin-tree: 2\n"), "{}", inspect);
+    assert!(inspect.contains("ext.acme.build: 7\n"), "{}", inspect);
+    assert!(
+        inspect.contains("ext.terrapin.media_type: application/octet-stream\n"),
+        "{}",
+        inspect
+    );
+
+    let bad = run(&["attest", s(&f), "--out", s(&tree), "--ext", "a.b=1"]);
+    assert!(!bad.status.success());
+    assert!(stderr_str(&bad).contains("--head-version 2"), "{}", stderr_str(&bad));
+    let bad = run(&["attest", s(&f), "--out", s(&tree), "--head-version", "2", "--ext", "x=1"]);
+    assert!(!bad.status.success());
+
+    let _ = std::fs::remove_file(&f);
+}
+
+// Verifies: REQ-DIR-005
+#[test]
+fn dir_mode_id_attest_and_member_validate() {
+    let dir = unique_path("dirmode");
+    let shard = xorshift_bytes(BLOCK + 3, 21);
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("sub/shard.bin"), &shard).unwrap();
+    std::fs::write(dir.join("meta.txt"), b"m").unwrap();
+    let base = unique_path("dirmode_out");
+
+    let id = run(&["id", "--dir", s(&dir)]);
+    assert!(id.status.success(), "{}", stderr_str(&id));
+    let id = stdout_str(&id).trim().to_string();
+    assert!(id.starts_with("terrapin-dir-sha256:"), "{}", id);
+    let att = run(&["attest", "--dir", s(&dir), "--out", s(&base)]);
+    assert!(att.status.success(), "{}", stderr_str(&att));
+    assert_eq!(stdout_str(&att).trim(), id);
+
+    let member = dir.join("sub/shard.bin");
+    let validate = |identifier: &str| {
+        run(&[
+            "validate",
+            s(&member),
+            "--tree",
+            s(&base),
+            "--member",
+            "sub/shard.bin",
+            "--identifier",
+            identifier,
+        ])
+    };
+    let ok = validate(&id);
+    assert!(ok.status.success(), "{}", stderr_str(&ok));
+    let wrong = validate(&format!("terrapin-dir-sha256:{}", "0".repeat(64)));
+    assert!(!wrong.status.success());
+    assert!(stderr_str(&wrong).contains("identifier mismatch"));
+
+    let mut bad = shard.clone();
+    bad[7] ^= 1;
+    std::fs::write(&member, &bad).unwrap();
+    assert!(!validate(&id).status.success());
+
+    let _ = std::fs::remove_dir_all(&dir);
+    let _ = std::fs::remove_file(format!("{}.listing", base.display()));
+    let _ = std::fs::remove_dir_all(format!("{}.d", base.display()));
+}
+
+// Verifies: REQ-PRO-005
+#[test]
+fn profile_flag_selects_blake3_for_id_and_attest() {
+    let data = xorshift_bytes(BLOCK + 11, 31);
+    let input = write_temp("blake3", &data);
+    let base = unique_path("blake3_tree");
+
+    let id = run(&["id", "--profile", "terrapin-blake3", s(&input)]);
+    assert!(id.status.success(), "{}", stderr_str(&id));
+    let id = stdout_str(&id).trim().to_string();
+    assert_eq!(id, terrapin::identifier_with(terrapin::BLAKE3, &data));
+
+    let att = run(&["attest", "--profile", "terrapin-blake3", s(&input), "--out", s(&base)]);
+    assert!(att.status.success(), "{}", stderr_str(&att));
+    assert_eq!(stdout_str(&att).trim(), id);
+    let v = run(&["validate", s(&input), "--tree", s(&base), "--identifier", &id]);
+    assert!(v.status.success(), "{}", stderr_str(&v));
+
+    let bad = run(&["id", "--profile", "terrapin-md5", s(&input)]);
+    assert!(!bad.status.success());
+    assert!(stderr_str(&bad).contains("unknown profile"));
+
+    let _ = std::fs::remove_file(&input);
+    cleanup_base(&base);
+}
+
+// Verifies: REQ-SIG-004
+#[test]
+fn keygen_sign_and_verify_sig() {
+    let data = xorshift_bytes(BLOCK + 17, 31);
+    let f = write_temp("sign", &data);
+    let base = unique_path("signbase");
+    attest_to(&f, &base);
+    let id = terrapin::identifier(&data);
+
+    for algorithm in ["ed25519", "ecdsa-p256"] {
+        let keys = unique_path("signkeys");
+        let kg = run(&["keygen", "--out", s(&keys), "--algorithm", algorithm]);
+        assert!(kg.status.success(), "{}", stderr_str(&kg));
+        let key = keys.with_extension("key");
+        let public = keys.with_extension("pub");
+
+        let sig = unique_path("sig");
+        let signed = run(&[
+            "sign", "--key", s(&key), "--tree", s(&base), "--meta", "build=7", "-o", s(&sig),
+        ]);
+        assert!(signed.status.success(), "{}", stderr_str(&signed));
+        let text = std::fs::read_to_string(&sig).unwrap();
+        assert!(text.contains(&format!("identifier: {}\n", id)), "{}", text);
+        assert!(text.contains("meta.build: 7\n"), "{}", text);
+
+        let ok = run(&["verify-sig", s(&sig), "--pubkey", s(&public)]);
+        assert!(ok.status.success(), "{}", stderr_str(&ok));
+        assert_eq!(stdout_str(&ok).trim(), id);
+
+        std::fs::write(&sig, text.replace("meta.build: 7", "meta.build: 8")).unwrap();
+        let bad = run(&["verify-sig", s(&sig), "--pubkey", s(&public)]);
+        assert!(!bad.status.success(), "a forged statement must not verify");
+
+        for p in [&key, &public, &sig] {
+            let _ = std::fs::remove_file(p);
+        }
+    }
+
+    // --identifier/--length sign without a tree; a bad identifier is refused.
+    let keys = unique_path("signkeys2");
+    assert!(run(&["keygen", "--out", s(&keys)]).status.success());
+    let explicit = run(&[
+        "sign", "--key", s(&keys.with_extension("key")), "--identifier", &id, "--length", "9",
+    ]);
+    assert!(explicit.status.success(), "{}", stderr_str(&explicit));
+    assert!(stdout_str(&explicit).contains("length: 9\n"));
+    let refused = run(&[
+        "sign", "--key", s(&keys.with_extension("key")), "--identifier", "nope", "--length", "9",
+    ]);
+    assert!(!refused.status.success());
+
+    let _ = std::fs::remove_file(keys.with_extension("key"));
+    let _ = std::fs::remove_file(keys.with_extension("pub"));
+    cleanup_base(&base);
+    let _ = std::fs::remove_file(&f);
+}
+
+// Verifies: REQ-SIG-005
+#[test]
+fn validate_takes_identifier_from_verified_signature() {
+    let data = xorshift_bytes(BLOCK + 5, 37);
+    let f = write_temp("sigval", &data);
+    let base = unique_path("sigvalbase");
+    attest_to(&f, &base);
+    let keys = unique_path("sigvalkeys");
+    let other = unique_path("sigvalother");
+    assert!(run(&["keygen", "--out", s(&keys)]).status.success());
+    assert!(run(&["keygen", "--out", s(&other)]).status.success());
+    let key = keys.with_extension("key");
+
+    let sign = |identifier: &str, length: usize, out: &Path| {
+        let len = length.to_string();
+        let o = run(&[
+            "sign", "--key", s(&key), "--identifier", identifier, "--length", &len, "-o", s(out),
+        ]);
+        assert!(o.status.success(), "{}", stderr_str(&o));
+    };
+    let validate = |sig: &Path, public: &Path| {
+        run(&[
+            "validate", s(&f), "--tree", s(&base), "--signature", s(sig), "--pubkey", s(public),
+        ])
+    };
+
+    let good = unique_path("sigvalgood");
+    sign(&terrapin::identifier(&data), data.len(), &good);
+    let ok = validate(&good, &keys.with_extension("pub"));
+    assert!(ok.status.success(), "{}", stderr_str(&ok));
+
+    let wrong_key = validate(&good, &other.with_extension("pub"));
+    assert!(!wrong_key.status.success());
+    assert!(stderr_str(&wrong_key).contains("Signature verification failed"));
+
+    // A valid signature over some other dataset's identifier does not vouch
+    // for this tree.
+    let elsewhere = unique_path("sigvalelse");
+    sign(&terrapin::identifier(b"other"), data.len(), &elsewhere);
+    assert!(!validate(&elsewhere, &keys.with_extension("pub")).status.success());
+
+    let no_key = run(&["validate", s(&f), "--tree", s(&base), "--signature", s(&good)]);
+    assert!(!no_key.status.success(), "--signature requires --pubkey");
+
+    for p in [&good, &elsewhere] {
+        let _ = std::fs::remove_file(p);
+    }
+    for k in [&keys, &other] {
+        let _ = std::fs::remove_file(k.with_extension("key"));
+        let _ = std::fs::remove_file(k.with_extension("pub"));
+    }
+    cleanup_base(&base);
+    let _ = std::fs::remove_file(&f);
+}
+
+// Verifies: REQ-TOT-004
+#[test]
+fn attest_in_toto_writes_statement_or_envelope() {
+    let data = xorshift_bytes(BLOCK + 9, 41);
+    let f = write_temp("intoto", &data);
+    let base = unique_path("intotobase");
+    let id = terrapin::identifier(&data);
+    let name = f.file_name().unwrap().to_str().unwrap().to_string();
+
+    let plain = unique_path("intoto.json");
+    let out = run(&["attest", s(&f), "--out", s(&base), "--in-toto", s(&plain)]);
+    assert!(out.status.success(), "{}", stderr_str(&out));
+    let st = terrapin::InTotoStatement::parse(&std::fs::read(&plain).unwrap()).unwrap();
+    assert_eq!(st.identifier_for(&name).unwrap(), id);
+
+    let keys = unique_path("intotokeys");
+    assert!(run(&["keygen", "--out", s(&keys)]).status.success());
+    let signed = unique_path("intoto.dsse.json");
+    let out = run(&[
+        "attest", s(&f), "--out", s(&base), "--in-toto", s(&signed),
+        "--key", s(&keys.with_extension("key")),
+    ]);
+    assert!(out.status.success(), "{}", stderr_str(&out));
+    let env = terrapin::Envelope::parse(&std::fs::read(&signed).unwrap()).unwrap();
+    let public = terrapin::PublicKey::read(&keys.with_extension("pub")).unwrap();
+    assert_eq!(env.verify(&public).unwrap().identifier_for(&name).unwrap(), id);
+
+    let no_in_toto = run(&["attest", s(&f), "--key", s(&keys.with_extension("key"))]);
+    assert!(!no_in_toto.status.success(), "--key requires --in-toto");
+
+    for p in [&plain, &signed, &keys.with_extension("key"), &keys.with_extension("pub")] {
+        let _ = std::fs::remove_file(p);
+    }
+    cleanup_base(&base);
+    let _ = std::fs::remove_file(&f);
+}
+
+// Verifies: REQ-TOT-005
+#[test]
+fn verify_in_toto_checks_envelope_then_data() {
+    let data = xorshift_bytes(2 * BLOCK + 3, 43);
+    let f = write_temp("vintoto", &data);
+    let base = unique_path("vintotobase");
+    let keys = unique_path("vintotokeys");
+    let other = unique_path("vintotoother");
+    assert!(run(&["keygen", "--out", s(&keys)]).status.success());
+    assert!(run(&["keygen", "--out", s(&other), "--algorithm", "ecdsa-p256"]).status.success());
+    let env = unique_path("vintoto.dsse.json");
+    let out = run(&[
+        "attest", s(&f), "--out", s(&base), "--in-toto", s(&env),
+        "--key", s(&keys.with_extension("key")),
+    ]);
+    assert!(out.status.success(), "{}", stderr_str(&out));
+
+    let verify = |input: &Path, public: &Path| {
+        run(&[
+            "verify-in-toto", s(input), "--attestation", s(&env), "--pubkey", s(public),
+            "--tree", s(&base),
+        ])
+    };
+    let ok = verify(&f, &keys.with_extension("pub"));
+    assert!(ok.status.success(), "{}", stderr_str(&ok));
+    assert_eq!(stdout_str(&ok).trim(), terrapin::identifier(&data));
+
+    let wrong_key = verify(&f, &other.with_extension("pub"));
+    assert!(!wrong_key.status.success());
+    assert!(stderr_str(&wrong_key).contains("Attestation verification failed"));
+
+    // The data must still validate against the attested identifier.
+    let mut tampered = data.clone();
+    tampered[BLOCK + 1] ^= 1;
+    std::fs::write(&f, &tampered).unwrap();
+    let bad = verify(&f, &keys.with_extension("pub"));
+    assert!(!bad.status.success());
+    assert!(stderr_str(&bad).contains("Validation failed"), "{}", stderr_str(&bad));
+
+    // A re-attested tree for other data does not match the signed digest.
+    let out = run(&["attest", s(&f), "--out", s(&base)]);
+    assert!(out.status.success());
+    assert!(!verify(&f, &keys.with_extension("pub")).status.success());
+
+    let _ = std::fs::remove_file(&env);
+    for k in [&keys, &other] {
+        let _ = std::fs::remove_file(k.with_extension("key"));
+        let _ = std::fs::remove_file(k.with_extension("pub"));
+    }
+    cleanup_base(&base);
+    let _ = std::fs::remove_file(&f);
+}
+
+// Verifies: REQ-SBM-004
+#[test]
+fn sbom_create_then_verify() {
+    let dir = unique_path("sbomdir");
+    std::fs::create_dir_all(dir.join("weights")).unwrap();
+    let a = xorshift_bytes(BLOCK + 11, 47);
+    std::fs::write(dir.join("weights/a.bin"), &a).unwrap();
+    std::fs::write(dir.join("b.txt"), b"abc").unwrap();
+
+    for format in ["spdx", "cyclonedx"] {
+        let doc = dir.join(format!("sbom.{}.json", format));
+        let out = Command::new(env!("CARGO_BIN_EXE_terrapin-cli"))
+            .current_dir(&dir)
+            .args(["sbom", "create", "weights/a.bin", "b.txt", "--sha256", "--format", format])
+            .args(["-o", s(&doc)])
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "{}", stderr_str(&out));
+        let text = std::fs::read_to_string(&doc).unwrap();
+        assert!(text.contains(&terrapin::identifier(&a)), "{}", text);
+        assert!(text.contains(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        ));
+
+        let ok = run(&["sbom", "verify", s(&doc), "--root", s(&dir)]);
+        assert!(ok.status.success(), "{}", stderr_str(&ok));
+        assert_eq!(stdout_str(&ok), "ok weights/a.bin\nok b.txt\n");
+    }
+
+    // Tampering is caught via the tree; with the tree gone, by re-hashing.
+    let doc = dir.join("sbom.spdx.json");
+    std::fs::write(dir.join("b.txt"), b"abd").unwrap();
+    let bad = run(&["sbom", "verify", s(&doc), "--root", s(&dir)]);
+    assert!(!bad.status.success());
+    assert!(stdout_str(&bad).contains("FAILED b.txt"), "{}", stdout_str(&bad));
+    cleanup_base(&dir.join("b.txt.terra"));
+    let bad = run(&["sbom", "verify", s(&doc), "--root", s(&dir)]);
+    assert!(!bad.status.success());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// Verifies: REQ-OCI-005
+#[test]
+fn oci_export_then_verify() {
+    let data = xorshift_bytes(2 * BLOCK + 21, 53);
+    let f = write_temp("oci", &data);
+    let layout = unique_path("ocilayout");
+
+    let out = run(&["oci", "export", s(&f), s(&layout), "--ref", "v1"]);
+    assert!(out.status.success(), "{}", stderr_str(&out));
+    let id = terrapin::identifier(&data);
+    assert_eq!(stdout_str(&out).trim(), id);
+    assert!(layout.join("oci-layout").exists());
+
+    let ok = run(&["oci", "verify", s(&layout), "--identifier", &id]);
+    assert!(ok.status.success(), "{}", stderr_str(&ok));
+    let range = run(&[
+        "oci", "verify", s(&layout), "--ref", "v1", "--start", "5", "--end", "2097200",
+    ]);
+    assert!(range.status.success(), "{}", stderr_str(&range));
+
+    let wrong = format!("terrapin-sha256:{}", "0".repeat(64));
+    let bad = run(&["oci", "verify", s(&layout), "--identifier", &wrong]);
+    assert!(!bad.status.success());
+    assert!(!run(&["oci", "verify", s(&layout), "--ref", "v2"]).status.success());
+
+    let ds = terrapin::OciDataset::open(&layout, None).unwrap();
+    let mut tampered = data.clone();
+    tampered[2 * BLOCK + 1] ^= 1;
+    std::fs::write(ds.data_path(), &tampered).unwrap();
+    let bad = run(&["oci", "verify", s(&layout)]);
+    assert!(!bad.status.success());
+    assert!(stderr_str(&bad).contains("Validation failed"));
+    let head = run(&["oci", "verify", s(&layout), "--end", "1000"]);
+    assert!(head.status.success(), "untouched blocks still verify");
+
+    let _ = std::fs::remove_dir_all(&layout);
+    let _ = std::fs::remove_file(&f);
+}
+
+/// The JSON lines on `text`; the last is the command's result object.
+fn json_lines(text: &str) -> Vec<serde_json::Value> {
+    text.lines()
+        .map(|l| serde_json::from_str(l).unwrap_or_else(|e| panic!("{}: {:?}", e, l)))
+        .collect()
+}
+
+// Verifies: REQ-JSON-001
+#[test]
+fn json_objects_describe_the_result() {
+    let data = xorshift_bytes(BLOCK + 100, 59);
+    let f = write_temp("json", &data);
+    let base = unique_path("jsontree");
+
+    let out = run(&["--format", "json", "attest", s(&f), "--out", s(&base)]);
+    assert!(out.status.success(), "{}", stderr_str(&out));
+    let v = json_lines(&stdout_str(&out)).pop().unwrap();
+    let id = terrapin::identifier(&data);
+    assert_eq!(v["command"], "attest");
+    assert_eq!(v["outcome"], "ok");
+    assert_eq!(v["identifier"], id.as_str());
+    assert_eq!(v["length"], data.len() as u64);
+    assert_eq!(v["layer_counts"], serde_json::json!([2]));
+    assert_eq!(v["tree"].as_str().unwrap().len(), 64);
+    assert!(v["elapsed_ms"].is_u64());
+
+    let out = run(&[
+        "--format", "json", "validate", s(&f), "--tree", s(&base), "--start", "3", "--end", "9",
+    ]);
+    assert!(out.status.success(), "{}", stderr_str(&out));
+    let v = json_lines(&stdout_str(&out)).pop().unwrap();
+    assert_eq!(v["command"], "validate");
+    assert_eq!(v["identifier"], id.as_str());
+    assert_eq!(v["range"], serde_json::json!({"start": 3, "end": 9}));
+
+    let out = run(&["--format", "json", "id", s(&f)]);
+    let v = json_lines(&stdout_str(&out)).pop().unwrap();
+    assert_eq!((v["command"].as_str(), v["identifier"].as_str()), (Some("id"), Some(&*id)));
+
+    cleanup_base(&base);
+    let _ = std::fs::remove_file(&f);
+}
+
+// Verifies: REQ-JSON-002
+#[test]
+fn json_failures_carry_kind_and_failing_block() {
+    let mut data = xorshift_bytes(2 * BLOCK + 5, 61);
+    let f = write_temp("jsonbad", &data);
+    let base = unique_path("jsonbadtree");
+    assert!(run(&["attest", s(&f), "--out", s(&base)]).status.success());
+    data[BLOCK + 1] ^= 1;
+    std::fs::write(&f, &data).unwrap();
+
+    let out = run(&["--format", "json", "validate", s(&f), "--tree", s(&base)]);
+    assert!(!out.status.success());
+    let v = json_lines(&stdout_str(&out)).pop().unwrap();
+    assert_eq!(v["outcome"], "failed");
+    assert_eq!(v["error"]["kind"], "validation");
+    assert_eq!(v["error"]["block"], 1);
+    assert_eq!(v["error"]["layer"], 0);
+    assert!(v["identifier"].is_string(), "tree fields known before the failure");
+
+    let wrong = format!("terrapin-sha256:{}", "0".repeat(64));
+    let out = run(&[
+        "--format", "json", "validate", s(&f), "--tree", s(&base), "--identifier", &wrong,
+    ]);
+    let v = json_lines(&stdout_str(&out)).pop().unwrap();
+    assert_eq!(v["error"]["kind"], "identifier_mismatch");
+
+    let out = run(&[
+        "--format", "json", "validate", s(&f), "--tree", s(&base), "--end", "99999999",
+    ]);
+    let v = json_lines(&stdout_str(&out)).pop().unwrap();
+    assert_eq!(v["error"]["kind"], "out_of_range");
+
+    let missing = unique_path("jsonmissing");
+    let out = run(&["--format", "json", "id", s(&missing)]);
+    assert!(!out.status.success());
+    let v = json_lines(&stdout_str(&out)).pop().unwrap();
+    assert_eq!(v["error"]["kind"], "io");
+    assert!(stderr_str(&out).is_empty(), "{}", stderr_str(&out));
+
+    cleanup_base(&base);
+    let _ = std::fs::remove_file(&f);
+}
+
+// Verifies: REQ-JSON-003
+#[test]
+fn json_events_precede_the_object() {
+    let dir = unique_path("jsonsbom");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.bin"), b"abc").unwrap();
+    std::fs::write(dir.join("b.bin"), b"abd").unwrap();
+    let doc = dir.join("sbom.json");
+    let out = Command::new(env!("CARGO_BIN_EXE_terrapin-cli"))
+        .current_dir(&dir)
+        .args(["--format", "json", "sbom", "create", "a.bin", "b.bin", "-o", s(&doc)])
+        .args(["--format", "cyclonedx"])
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{}", stderr_str(&out));
+    assert!(std::fs::read_to_string(&doc).unwrap().contains("CycloneDX"));
+    let lines = json_lines(&stdout_str(&out));
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0]["event"], "entry");
+    assert_eq!(lines[1]["name"], "b.bin");
+    assert_eq!(lines[2]["files"], 2);
+
+    std::fs::write(dir.join("b.bin"), b"abe").unwrap();
+    let out = run(&["--format", "json", "sbom", "verify", s(&doc), "--root", s(&dir)]);
+    assert!(!out.status.success());
+    let lines = json_lines(&stdout_str(&out));
+    let outcomes: Vec<_> = lines.iter().map(|v| v["outcome"].as_str().unwrap()).collect();
+    assert_eq!(outcomes, ["ok", "failed", "failed"]);
+    assert_eq!(lines[2]["failed"], 1);
+
+    // `cat` keeps stdout for the data and reports on stderr.
+    let a = dir.join("a.bin");
+    let out = run(&["--format", "json", "cat", s(&a), "--tree", s(&dir.join("a.bin.terra"))]);
+    assert!(out.status.success(), "{}", stderr_str(&out));
+    assert_eq!(out.stdout, b"abc");
+    let v = json_lines(&stderr_str(&out)).pop().unwrap();
+    assert_eq!(v["command"], "cat");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// Verifies: REQ-CLI-020
+#[test]
+fn id_and_attest_reuse_and_verify_the_cache() {
+    let data = xorshift_bytes(BLOCK + 5, 75);
+    let f = write_temp("cached", &data);
+    let db = unique_path("cache_db");
+    let base = unique_path("cachedtree");
+    let expected = terrapin::identifier(&data);
+    let cache_of = |args: &[&str]| {
+        let out = run(&[&["--format", "json"], args].concat());
+        assert!(out.status.success(), "{}", stdout_str(&out));
+        let result = json_lines(&stdout_str(&out)).pop().unwrap();
+        assert_eq!(result["identifier"], expected.as_str());
+        result["cache"].as_str().unwrap().to_string()
+    };
+
+    let id = ["id", s(&f), "--cache-db", s(&db)];
+    assert_eq!(cache_of(&id), "miss");
+    assert_eq!(cache_of(&id), "hit");
+    let plain = run(&["id", s(&f), "--cache-db", s(&db)]);
+    assert_eq!(stdout_str(&plain).trim(), expected);
+
+    // A wrong cached identifier is served until --verify-cache catches and
+    // corrects it.
+    let wrong = terrapin::identifier(b"something else");
+    let text = std::fs::read_to_string(&db).unwrap();
+    std::fs::write(&db, text.replace(&expected, &wrong)).unwrap();
+    assert_eq!(stdout_str(&run(&id)).trim(), wrong);
+    let verify = run(&["id", s(&f), "--cache-db", s(&db), "--verify-cache"]);
+    assert!(!verify.status.success());
+    assert!(stderr_str(&verify).contains("identifier mismatch"), "{}", stderr_str(&verify));
+    assert_eq!(stdout_str(&run(&id)).trim(), expected);
+    assert_eq!(cache_of(&[&id[..], &["--verify-cache"]].concat()), "verified");
+
+    // attest reuses its own tree while the file and the tree are unchanged.
+    let attest = ["attest", s(&f), "--out", s(&base), "--cache-db", s(&db)];
+    assert_eq!(cache_of(&attest), "miss");
+    assert_eq!(cache_of(&attest), "hit");
+    assert_eq!(cache_of(&[&attest[..], &["--single"]].concat()), "miss");
+    let _ = std::fs::remove_file(&base);
+    cleanup_base(&base);
+    assert_eq!(cache_of(&attest), "miss");
+    assert!(run(&["validate", s(&f), "--tree", s(&base)]).status.success());
+
+    // Any metadata change is a miss.
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&f)
+        .unwrap()
+        .set_modified(std::time::SystemTime::UNIX_EPOCH)
+        .unwrap();
+    assert_eq!(cache_of(&id), "miss");
+
+    cleanup_base(&base);
+    let _ = std::fs::remove_file(&db);
+    let _ = std::fs::remove_file(&f);
+}
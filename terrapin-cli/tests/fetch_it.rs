@@ -0,0 +1,314 @@
+//! Black-box tests for `terrapin-cli fetch` against a local stand-in server.
+//!
+//! The stand-in is a few dozen lines of `std::net`: it serves fixed bodies by
+//! path with single-range support and records every request, so tests can
+//! assert which data blocks and hash-file groups a fetch actually pulled, and
+//! can serve bytes that do not match the published tree.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+const BLOCK: usize = 2 * 1024 * 1024;
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A unique, process-scoped temp path (not created on disk).
+fn unique_path(label: &str) -> PathBuf {
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let mut p = std::env::temp_dir();
+    p.push(format!(
+        "terrapin_fetch_it_{}_{}_{}",
+        std::process::id(),
+        n,
+        label
+    ));
+    p
+}
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_terrapin-cli"))
+        .args(args)
+        .output()
+        .expect("failed to spawn terrapin-cli")
+}
+
+/// Deterministic pseudo-random bytes via a small xorshift64.
+fn xorshift_bytes(n: usize, seed: u64) -> Vec<u8> {
+    let mut x = seed | 1;
+    let mut v = Vec::with_capacity(n);
+    for _ in 0..n {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        v.push((x & 0xff) as u8);
+    }
+    v
+}
+
+/// Attest `data` with the CLI and return `(identifier, head, blocks)`.
+fn attest(data: &[u8]) -> (String, Vec<u8>, Vec<u8>) {
+    let f = unique_path("src");
+    let base = unique_path("tree");
+    std::fs::write(&f, data).unwrap();
+    let out = run(&[
+        "attest",
+        f.to_str().unwrap(),
+        "--out",
+        base.to_str().unwrap(),
+    ]);
+    assert!(out.status.success());
+    let id = String::from_utf8(out.stdout).unwrap().trim().to_string();
+    let head = std::fs::read(format!("{}.head", base.display())).unwrap();
+    let blocks = std::fs::read(format!("{}.blocks", base.display())).unwrap();
+    let _ = std::fs::remove_file(&f);
+    let _ = std::fs::remove_file(format!("{}.head", base.display()));
+    let _ = std::fs::remove_file(format!("{}.blocks", base.display()));
+    (id, head, blocks)
+}
+
+/// Every request the stand-in saw: `(path, Range value after "bytes=")`.
+type RequestLog = Arc<Mutex<Vec<(String, Option<String>)>>>;
+
+/// Stand-in HTTP server: fixed bodies by path, single `bytes=a-b` ranges.
+struct StandIn {
+    base: String,
+    log: RequestLog,
+}
+
+impl StandIn {
+    fn start(files: HashMap<String, Vec<u8>>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let log: RequestLog = Arc::new(Mutex::new(Vec::new()));
+        let files = Arc::new(files);
+        let log2 = Arc::clone(&log);
+        thread::spawn(move || {
+            for conn in listener.incoming() {
+                let mut stream = conn.unwrap();
+                let mut r = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                r.read_line(&mut line).unwrap();
+                let path = line.split(' ').nth(1).unwrap().to_string();
+                let mut range = None;
+                loop {
+                    let mut h = String::new();
+                    r.read_line(&mut h).unwrap();
+                    if h.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(v) = h.trim().strip_prefix("Range: bytes=") {
+                        range = Some(v.to_string());
+                    }
+                }
+                log2.lock().unwrap().push((path.clone(), range.clone()));
+                let (status, body) = match files.get(&path) {
+                    None => ("404 Not Found", b"not found\n".to_vec()),
+                    Some(b) => match &range {
+                        None => ("200 OK", b.clone()),
+                        Some(r) => {
+                            let (s, e) = r.split_once('-').unwrap();
+                            let s: usize = s.parse().unwrap();
+                            let e: usize = e.parse::<usize>().unwrap().min(b.len() - 1);
+                            ("206 Partial Content", b[s..=e].to_vec())
+                        }
+                    },
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                );
+                let _ = stream.write_all(&body);
+            }
+        });
+        StandIn { base, log }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base, path)
+    }
+
+    /// Data-block indices requested from `path` (by range start).
+    fn blocks_requested(&self, path: &str) -> Vec<usize> {
+        self.log
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(p, _)| p == path)
+            .map(|(_, r)| {
+                let s: usize = r
+                    .as_ref()
+                    .unwrap()
+                    .split('-')
+                    .next()
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                s / BLOCK
+            })
+            .collect()
+    }
+
+    fn requests_for(&self, path: &str) -> usize {
+        self.log
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(p, _)| p == path)
+            .count()
+    }
+}
+
+/// Serve `served` as `/d.bin` with the tree attested over `data`.
+fn serve_dataset(data: &[u8], served: Vec<u8>) -> (StandIn, String) {
+    let (id, head, blocks) = attest(data);
+    let mut files = HashMap::new();
+    files.insert("/d.bin".to_string(), served);
+    files.insert("/d.bin.terra.head".to_string(), head);
+    files.insert("/d.bin.terra.blocks".to_string(), blocks);
+    (StandIn::start(files), id)
+}
+
+// Verifies: REQ-FET-001
+#[test]
+fn fetch_whole_dataset_matches_source() {
+    let data = xorshift_bytes(3 * BLOCK + 321, 1);
+    let (srv, id) = serve_dataset(&data, data.clone());
+    let out = unique_path("whole");
+
+    let r = run(&[
+        "fetch",
+        &srv.url("/d.bin"),
+        "--identifier",
+        &id,
+        "-o",
+        out.to_str().unwrap(),
+    ]);
+    assert!(
+        r.status.success(),
+        "fetch failed: {}",
+        String::from_utf8_lossy(&r.stderr)
+    );
+    assert_eq!(std::fs::read(&out).unwrap(), data);
+    // Single-layer tree: the one leaf group is fetched once and cached.
+    assert_eq!(srv.requests_for("/d.bin.terra.blocks"), 1);
+    let _ = std::fs::remove_file(&out);
+}
+
+// Verifies: REQ-FET-002
+#[test]
+fn fetch_rejects_untrusted_identifier_before_data() {
+    let data = xorshift_bytes(BLOCK + 5, 2);
+    let (srv, _) = serve_dataset(&data, data.clone());
+    let out = unique_path("wrongid");
+    let wrong = format!("terrapin-sha256:{}", "0".repeat(64));
+
+    let r = run(&[
+        "fetch",
+        &srv.url("/d.bin"),
+        "--identifier",
+        &wrong,
+        "-o",
+        out.to_str().unwrap(),
+    ]);
+    assert!(!r.status.success(), "wrong identifier must fail");
+    assert!(String::from_utf8_lossy(&r.stderr).contains("identifier mismatch"));
+    assert_eq!(
+        srv.requests_for("/d.bin"),
+        0,
+        "no data fetched before trust"
+    );
+    assert!(!out.exists(), "no output for an untrusted tree");
+}
+
+// Verifies: REQ-FET-003
+#[test]
+fn fetch_range_pulls_only_covering_blocks() {
+    let data = xorshift_bytes(4 * BLOCK + 10, 3);
+    let (srv, id) = serve_dataset(&data, data.clone());
+    let out = unique_path("range");
+    let (s, e) = (BLOCK + 100, 2 * BLOCK + 50);
+
+    let r = run(&[
+        "fetch",
+        &srv.url("/d.bin"),
+        "--identifier",
+        &id,
+        "--start",
+        &s.to_string(),
+        "--end",
+        &e.to_string(),
+        "-o",
+        out.to_str().unwrap(),
+    ]);
+    assert!(
+        r.status.success(),
+        "fetch failed: {}",
+        String::from_utf8_lossy(&r.stderr)
+    );
+    assert_eq!(std::fs::read(&out).unwrap(), &data[s..e]);
+    assert_eq!(srv.blocks_requested("/d.bin"), vec![1, 2]);
+    let _ = std::fs::remove_file(&out);
+}
+
+// Verifies: REQ-FET-004
+#[test]
+fn fetch_writes_only_verified_bytes_on_tampered_server() {
+    let data = xorshift_bytes(3 * BLOCK, 4);
+    let mut served = data.clone();
+    served[2 * BLOCK + 7] ^= 0xff;
+    let (srv, id) = serve_dataset(&data, served);
+    let out = unique_path("tampered");
+
+    let r = run(&[
+        "fetch",
+        &srv.url("/d.bin"),
+        "--identifier",
+        &id,
+        "-o",
+        out.to_str().unwrap(),
+    ]);
+    assert!(!r.status.success(), "tampered data must fail");
+    assert!(String::from_utf8_lossy(&r.stderr).contains("validation failed at block 2"));
+    // Blocks 0 and 1 verified and were kept; nothing of block 2 was written.
+    assert_eq!(std::fs::read(&out).unwrap(), &data[..2 * BLOCK]);
+    let _ = std::fs::remove_file(&out);
+}
+
+// Verifies: REQ-FET-005
+#[test]
+fn fetch_resumes_by_reverifying_existing_blocks() {
+    let data = xorshift_bytes(3 * BLOCK + 99, 5);
+    let (srv, id) = serve_dataset(&data, data.clone());
+    let out = unique_path("resume");
+
+    // An interrupted earlier run: block 0 complete, block 1 corrupt and short.
+    let mut partial = data[..BLOCK + 1000].to_vec();
+    partial[BLOCK + 3] ^= 0x55;
+    std::fs::write(&out, &partial).unwrap();
+
+    let r = run(&[
+        "fetch",
+        &srv.url("/d.bin"),
+        "--identifier",
+        &id,
+        "-o",
+        out.to_str().unwrap(),
+    ]);
+    assert!(
+        r.status.success(),
+        "resume failed: {}",
+        String::from_utf8_lossy(&r.stderr)
+    );
+    assert_eq!(std::fs::read(&out).unwrap(), data);
+    assert_eq!(srv.blocks_requested("/d.bin"), vec![1, 2, 3]);
+    assert!(String::from_utf8_lossy(&r.stdout).contains("1 reused"));
+    let _ = std::fs::remove_file(&out);
+}
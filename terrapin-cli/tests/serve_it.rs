@@ -0,0 +1,315 @@
+//! Black-box tests for `terrapin-cli serve` over loopback.
+//!
+//! Each test attests a file with the CLI, starts the server on an ephemeral
+//! port (`--bind 127.0.0.1:0`, address read from the first stdout line) and
+//! talks raw HTTP/1.1 over `std::net::TcpStream` — no external crates.
+#![cfg(feature = "serve")]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const BLOCK: usize = 2 * 1024 * 1024;
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A temp data file plus its `<file>.terra.{head,blocks}` (or single-file
+/// `<file>.terra`), removed on drop.
+struct Attested(PathBuf);
+
+impl Attested {
+    fn new(label: &str, data: &[u8]) -> Self {
+        Self::with_args(label, data, &[])
+    }
+    fn single(label: &str, data: &[u8]) -> Self {
+        Self::with_args(label, data, &["--single"])
+    }
+    fn with_args(label: &str, data: &[u8], args: &[&str]) -> Self {
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let mut p = std::env::temp_dir();
+        p.push(format!(
+            "terrapin_serve_it_{}_{}_{}",
+            std::process::id(),
+            n,
+            label
+        ));
+        std::fs::write(&p, data).unwrap();
+        let out = Command::new(env!("CARGO_BIN_EXE_terrapin-cli"))
+            .arg("attest")
+            .arg(&p)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "attest failed");
+        Attested(p)
+    }
+    fn name(&self) -> String {
+        self.0.file_name().unwrap().to_str().unwrap().to_string()
+    }
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let mut s = self.0.as_os_str().to_os_string();
+        s.push(suffix);
+        PathBuf::from(s)
+    }
+}
+
+impl Drop for Attested {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+        let _ = std::fs::remove_file(self.sibling(".terra.head"));
+        let _ = std::fs::remove_file(self.sibling(".terra.blocks"));
+        let _ = std::fs::remove_file(self.sibling(".terra"));
+    }
+}
+
+/// A running server, killed on drop.
+struct Server {
+    child: Child,
+    addr: String,
+}
+
+impl Server {
+    fn start(files: &[&Path]) -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_terrapin-cli"))
+            .arg("serve")
+            .args(files)
+            .args(["--bind", "127.0.0.1:0"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("spawn serve");
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let addr = line
+            .trim()
+            .strip_prefix("listening on http://")
+            .unwrap_or_else(|| panic!("unexpected banner {:?}", line))
+            .to_string();
+        Server { child, addr }
+    }
+
+    /// Issue one request; returns (status, lowercase headers, body).
+    fn request(&self, method: &str, path: &str, range: Option<&str>) -> Response {
+        let mut s = TcpStream::connect(&self.addr).unwrap();
+        let mut req = format!("{} {} HTTP/1.1\r\nHost: {}\r\n", method, path, self.addr);
+        if let Some(r) = range {
+            req.push_str(&format!("Range: {}\r\n", r));
+        }
+        req.push_str("\r\n");
+        s.write_all(req.as_bytes()).unwrap();
+        let mut raw = Vec::new();
+        s.read_to_end(&mut raw).unwrap();
+        let split = raw.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8(raw[..split].to_vec()).unwrap();
+        let mut lines = head.split("\r\n");
+        let status: u16 = lines
+            .next()
+            .unwrap()
+            .split(' ')
+            .nth(1)
+            .unwrap()
+            .parse()
+            .unwrap();
+        let headers = lines
+            .map(|l| {
+                let (k, v) = l.split_once(':').unwrap();
+                (k.trim().to_ascii_lowercase(), v.trim().to_string())
+            })
+            .collect();
+        Response {
+            status,
+            headers,
+            body: raw[split + 4..].to_vec(),
+        }
+    }
+
+    fn get(&self, path: &str, range: Option<&str>) -> Response {
+        self.request("GET", path, range)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Deterministic pseudo-random bytes via a small xorshift64.
+fn xorshift_bytes(n: usize, seed: u64) -> Vec<u8> {
+    let mut x = seed | 1;
+    let mut v = Vec::with_capacity(n);
+    for _ in 0..n {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        v.push((x & 0xff) as u8);
+    }
+    v
+}
+
+// Verifies: REQ-SRV-001
+#[test]
+fn serve_whole_and_ranges_return_verified_bytes() {
+    let data = xorshift_bytes(2 * BLOCK + 777, 1);
+    let f = Attested::new("ranges", &data);
+    let srv = Server::start(&[&f.0]);
+    let path = format!("/{}", f.name());
+
+    let whole = srv.get(&path, None);
+    assert_eq!(whole.status, 200);
+    assert_eq!(whole.body, data);
+    assert_eq!(whole.header("accept-ranges"), Some("bytes"));
+
+    // Block-straddling range (inclusive end, per RFC 9110).
+    let (s, e) = (BLOCK - 10, BLOCK + 20);
+    let part = srv.get(&path, Some(&format!("bytes={}-{}", s, e)));
+    assert_eq!(part.status, 206);
+    assert_eq!(part.body, &data[s..=e]);
+    let want_cr = format!("bytes {}-{}/{}", s, e, data.len());
+    assert_eq!(part.header("content-range"), Some(want_cr.as_str()));
+
+    // Open-ended and suffix ranges.
+    let open = srv.get(&path, Some(&format!("bytes={}-", 2 * BLOCK)));
+    assert_eq!(open.body, &data[2 * BLOCK..]);
+    let suffix = srv.get(&path, Some("bytes=-5"));
+    assert_eq!(suffix.body, &data[data.len() - 5..]);
+
+    // HEAD carries the length but no body.
+    let head = srv.request("HEAD", &path, None);
+    assert_eq!(head.status, 200);
+    let want_len = data.len().to_string();
+    assert_eq!(head.header("content-length"), Some(want_len.as_str()));
+    assert!(head.body.is_empty());
+}
+
+// Verifies: REQ-SRV-002
+#[test]
+fn serve_exposes_tree_and_identifier_paths() {
+    let data = xorshift_bytes(BLOCK + 99, 2);
+    let f = Attested::new("paths", &data);
+    let srv = Server::start(&[&f.0]);
+    let name = f.name();
+    let id = terrapin::identifier(&data);
+
+    let head = srv.get(&format!("/{}.terra.head", name), None);
+    assert_eq!(head.status, 200);
+    assert_eq!(head.body, std::fs::read(f.sibling(".terra.head")).unwrap());
+
+    // The hash file is range-fetchable: second leaf hash only.
+    let blocks = std::fs::read(f.sibling(".terra.blocks")).unwrap();
+    let leaf1 = srv.get(&format!("/{}.terra.blocks", name), Some("bytes=32-63"));
+    assert_eq!(leaf1.status, 206);
+    assert_eq!(leaf1.body, &blocks[32..64]);
+
+    let idr = srv.get(&format!("/{}.terra.id", name), None);
+    assert_eq!(String::from_utf8(idr.body).unwrap().trim(), id);
+
+    let index = srv.get("/.well-known/terrapin", None);
+    assert_eq!(
+        String::from_utf8(index.body).unwrap(),
+        format!("{} {}\n", id, name)
+    );
+}
+
+// Verifies: REQ-SRV-005
+#[test]
+fn serve_single_file_tree_is_range_fetchable_end_to_end() {
+    let data = xorshift_bytes(3 * BLOCK + 5, 6);
+    let f = Attested::single("single", &data);
+    let srv = Server::start(&[&f.0]);
+    let name = f.name();
+    let id = terrapin::identifier(&data);
+
+    let file = std::fs::read(f.sibling(".terra")).unwrap();
+    let header = srv.get(&format!("/{}.terra", name), Some("bytes=0-4095"));
+    assert_eq!(header.status, 206);
+    assert_eq!(header.body, &file[..4096]);
+    assert_eq!(srv.get(&format!("/{}.terra.head", name), None).status, 404);
+
+    let mut out = f.0.as_os_str().to_os_string();
+    out.push(".fetched");
+    let out = PathBuf::from(out);
+    let (s, e) = (BLOCK + 7, 2 * BLOCK + 3);
+    let r = Command::new(env!("CARGO_BIN_EXE_terrapin-cli"))
+        .arg("fetch")
+        .arg(format!("http://{}/{}", srv.addr, name))
+        .args(["--identifier", &id])
+        .args(["--start", &s.to_string(), "--end", &e.to_string()])
+        .arg("-o")
+        .arg(&out)
+        .output()
+        .unwrap();
+    assert!(
+        r.status.success(),
+        "fetch failed: {}",
+        String::from_utf8_lossy(&r.stderr)
+    );
+    assert_eq!(std::fs::read(&out).unwrap(), &data[s..e]);
+    let _ = std::fs::remove_file(&out);
+}
+
+// Verifies: REQ-SRV-003
+#[test]
+fn serve_tampered_data_returns_5xx_not_bytes() {
+    let mut data = xorshift_bytes(2 * BLOCK + 10, 3);
+    let f = Attested::new("tamper", &data);
+    data[BLOCK + 5] ^= 0xff;
+    std::fs::write(&f.0, &data).unwrap();
+    let srv = Server::start(&[&f.0]);
+    let path = format!("/{}", f.name());
+
+    let r = srv.get(&path, Some(&format!("bytes={}-{}", BLOCK, BLOCK + 100)));
+    assert_eq!(r.status, 500);
+    let body = String::from_utf8(r.body).unwrap();
+    assert!(body.contains("Validation failed"), "body: {}", body);
+
+    // A range entirely inside an untouched block is still served.
+    let ok = srv.get(&path, Some("bytes=0-99"));
+    assert_eq!(ok.status, 206);
+    assert_eq!(ok.body, &data[..100]);
+
+    // A whole GET starts at once and stops short before the bad block.
+    let whole = srv.get(&path, None);
+    assert_eq!(whole.status, 200);
+    let len = (2 * BLOCK + 10).to_string();
+    assert_eq!(whole.header("content-length"), Some(len.as_str()));
+    assert_eq!(whole.body, &data[..BLOCK]);
+
+    // HEAD does not read the data.
+    assert_eq!(srv.request("HEAD", &path, None).status, 200);
+}
+
+// Verifies: REQ-SRV-004
+#[test]
+fn serve_unsatisfiable_range_and_unknown_path() {
+    let data = xorshift_bytes(1000, 4);
+    let f = Attested::new("errors", &data);
+    let srv = Server::start(&[&f.0]);
+
+    let r = srv.get(&format!("/{}", f.name()), Some("bytes=1000-2000"));
+    assert_eq!(r.status, 416);
+    assert_eq!(r.header("content-range"), Some("bytes */1000"));
+
+    assert_eq!(srv.get("/no-such-dataset", None).status, 404);
+    assert_eq!(srv.request("POST", "/", None).status, 405);
+}
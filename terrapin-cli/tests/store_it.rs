@@ -0,0 +1,148 @@
+//! Black-box tests for `terrapin-cli store add|get|ls|rm|gc`.
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const BLOCK: usize = 2 * 1024 * 1024;
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A unique, process-scoped temp path (not created on disk).
+fn unique_path(label: &str) -> PathBuf {
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let mut p = std::env::temp_dir();
+    p.push(format!(
+        "terrapin_store_it_{}_{}_{}",
+        std::process::id(),
+        n,
+        label
+    ));
+    p
+}
+
+/// Run `terrapin-cli store <args> --store <dir>`.
+fn store(dir: &PathBuf, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_terrapin-cli"))
+        .arg("store")
+        .args(args)
+        .arg("--store")
+        .arg(dir)
+        .output()
+        .expect("failed to spawn terrapin-cli")
+}
+
+fn ok(out: &Output) -> String {
+    assert!(
+        out.status.success(),
+        "command failed: {}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    String::from_utf8(out.stdout.clone()).unwrap()
+}
+
+/// Deterministic pseudo-random bytes via a small xorshift64.
+fn xorshift_bytes(n: usize, seed: u64) -> Vec<u8> {
+    let mut x = seed | 1;
+    let mut v = Vec::with_capacity(n);
+    for _ in 0..n {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        v.push((x & 0xff) as u8);
+    }
+    v
+}
+
+// Verifies: REQ-STO-005
+#[test]
+fn store_cli_add_get_ls_gc_round_trip() {
+    let dir = unique_path("store");
+    let v1 = xorshift_bytes(2 * BLOCK + 17, 1);
+    let v2 = xorshift_bytes(1000, 2);
+    let (f1, f2) = (unique_path("v1"), unique_path("v2"));
+    std::fs::write(&f1, &v1).unwrap();
+    std::fs::write(&f2, &v2).unwrap();
+
+    let id1 = ok(&store(&dir, &["add", f1.to_str().unwrap(), "--name", "ds"]));
+    let id1 = id1.trim();
+    assert_eq!(id1, terrapin::identifier(&v1));
+
+    // Same content again: deduplicated, reported on stderr.
+    let again = store(&dir, &["add", f1.to_str().unwrap(), "--name", "copy"]);
+    assert_eq!(ok(&again).trim(), id1);
+    assert!(String::from_utf8_lossy(&again.stderr).contains("already stored"));
+
+    // Verified range by identifier, whole dataset by ref name.
+    let (s, e) = (BLOCK - 3, BLOCK + 3);
+    let range = store(
+        &dir,
+        &[
+            "get",
+            id1,
+            "--start",
+            &s.to_string(),
+            "--end",
+            &e.to_string(),
+        ],
+    );
+    assert!(range.status.success());
+    assert_eq!(range.stdout, &v1[s..e]);
+    let out = unique_path("out");
+    ok(&store(&dir, &["get", "ds", "-o", out.to_str().unwrap()]));
+    assert_eq!(std::fs::read(&out).unwrap(), v1);
+
+    let ls = ok(&store(&dir, &["ls"]));
+    assert_eq!(ls, format!("{} {} copy,ds\n", id1, v1.len()));
+
+    // Re-point both refs; gc then drops the orphaned first version.
+    let id2 = ok(&store(&dir, &["add", f2.to_str().unwrap(), "--name", "ds"]));
+    ok(&store(&dir, &["rm", "copy"]));
+    let gc = ok(&store(&dir, &["gc"]));
+    assert_eq!(gc, format!("removed {}\n", id1));
+    let ls = ok(&store(&dir, &["ls"]));
+    assert_eq!(ls, format!("{} {} ds\n", id2.trim(), v2.len()));
+    assert!(!store(&dir, &["get", id1]).status.success());
+
+    let _ = std::fs::remove_dir_all(&dir);
+    for f in [f1, f2, out] {
+        let _ = std::fs::remove_file(f);
+    }
+}
+
+// Verifies: REQ-STO-009
+#[test]
+fn store_cli_block_mode_reports_dedup_stats() {
+    let dir = unique_path("blocks");
+    ok(&store(&dir, &["init", "--blocks"]));
+    let shared = xorshift_bytes(2 * BLOCK, 3);
+    let mut v1 = shared.clone();
+    v1.extend(xorshift_bytes(BLOCK, 7));
+    let mut v2 = shared;
+    v2.extend(xorshift_bytes(BLOCK, 9));
+    let (f1, f2) = (unique_path("b1"), unique_path("b2"));
+    std::fs::write(&f1, &v1).unwrap();
+    std::fs::write(&f2, &v2).unwrap();
+
+    ok(&store(&dir, &["add", f1.to_str().unwrap(), "--name", "v1"]));
+    ok(&store(&dir, &["add", f2.to_str().unwrap(), "--name", "v2"]));
+    let got = store(&dir, &["get", "v2"]);
+    assert!(got.status.success());
+    assert_eq!(got.stdout, v2);
+
+    let stats = ok(&store(&dir, &["stats"]));
+    let want = format!(
+        "mode: blocks\nobjects: 2\nlogical_bytes: {}\nstored_bytes: {}\n\
+         blocks: 6\nunique_blocks: 4\ndedup_ratio: 1.50\n",
+        6 * BLOCK,
+        4 * BLOCK
+    );
+    assert_eq!(stats, want);
+
+    // A block store cannot be re-initialised as a whole-file store.
+    assert!(!store(&dir, &["init"]).status.success());
+
+    let _ = std::fs::remove_dir_all(&dir);
+    let _ = std::fs::remove_file(f1);
+    let _ = std::fs::remove_file(f2);
+}
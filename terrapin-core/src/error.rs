@@ -0,0 +1,150 @@
+//! The error type of this crate and of `terrapin`.
+//!
+//! Every error carries an [`ErrorKind`] set where it is raised, so callers —
+//! the CLI's JSON output, the C and Python bindings — branch on the kind
+//! instead of reading it off the message. The message is for people and may
+//! change; the kind is stable.
+
+use alloc::string::String;
+use core::fmt;
+
+/// What went wrong, independent of the message wording.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// Reading, writing or transferring bytes failed.
+    Io,
+    /// An input is not well formed: a head, `.blocks`, manifest, listing, key,
+    /// statement, envelope, store entry or document.
+    Malformed,
+    /// A block index or byte range lies outside the dataset.
+    OutOfRange,
+    /// An identifier is not the one trusted.
+    IdentifierMismatch,
+    /// Data does not hash to its tree.
+    Validation,
+    /// A signature is missing, unsupported or does not verify.
+    Signature,
+    /// Anything else: unsupported inputs, invalid arguments.
+    Other,
+}
+
+impl ErrorKind {
+    /// Stable snake_case name: `io`, `malformed`, `out_of_range`,
+    /// `identifier_mismatch`, `validation`, `signature`, or `error` for
+    /// [`ErrorKind::Other`].
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Io => "io",
+            ErrorKind::Malformed => "malformed",
+            ErrorKind::OutOfRange => "out_of_range",
+            ErrorKind::IdentifierMismatch => "identifier_mismatch",
+            ErrorKind::Validation => "validation",
+            ErrorKind::Signature => "signature",
+            ErrorKind::Other => "error",
+        }
+    }
+}
+
+/// Where on a block's path validation failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathLayer {
+    /// The hash-file group at this layer (0 holds the leaves).
+    Hashes(usize),
+    /// The tree root.
+    Root,
+}
+
+/// An error with its [`ErrorKind`] and, for validation failures, the block
+/// and path layer that failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    kind: ErrorKind,
+    message: String,
+    block: Option<u64>,
+    layer: Option<PathLayer>,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Error {
+        Error {
+            kind,
+            message: message.into(),
+            block: None,
+            layer: None,
+        }
+    }
+
+    /// An [`ErrorKind::Io`] error.
+    pub fn io(message: impl Into<String>) -> Error {
+        Error::new(ErrorKind::Io, message)
+    }
+
+    /// An [`ErrorKind::Malformed`] error.
+    pub fn malformed(message: impl Into<String>) -> Error {
+        Error::new(ErrorKind::Malformed, message)
+    }
+
+    /// An [`ErrorKind::OutOfRange`] error.
+    pub fn out_of_range(message: impl Into<String>) -> Error {
+        Error::new(ErrorKind::OutOfRange, message)
+    }
+
+    /// An [`ErrorKind::IdentifierMismatch`] error.
+    pub fn mismatch(message: impl Into<String>) -> Error {
+        Error::new(ErrorKind::IdentifierMismatch, message)
+    }
+
+    /// An [`ErrorKind::Validation`] error.
+    pub fn validation(message: impl Into<String>) -> Error {
+        Error::new(ErrorKind::Validation, message)
+    }
+
+    /// An [`ErrorKind::Signature`] error.
+    pub fn signature(message: impl Into<String>) -> Error {
+        Error::new(ErrorKind::Signature, message)
+    }
+
+    /// An [`ErrorKind::Other`] error.
+    pub fn other(message: impl Into<String>) -> Error {
+        Error::new(ErrorKind::Other, message)
+    }
+
+    /// Record the data block, and the layer of its path, that failed.
+    pub fn at_block(mut self, block: u64, layer: Option<PathLayer>) -> Error {
+        self.block = Some(block);
+        self.layer = layer;
+        self
+    }
+
+    /// The same error with `context: ` before its message.
+    pub fn context(mut self, context: &str) -> Error {
+        self.message = alloc::format!("{}: {}", context, self.message);
+        self
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The data block that failed validation, if this is about one.
+    pub fn block(&self) -> Option<u64> {
+        self.block
+    }
+
+    /// Where on [`Error::block`]'s path validation failed, if known.
+    pub fn layer(&self) -> Option<PathLayer> {
+        self.layer
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl core::error::Error for Error {}
@@ -0,0 +1,45 @@
+//! Terrapin core: the spec primitives and single-block path verification,
+//! usable without `std`.
+//!
+//! This crate is `#![no_std]` and needs only `alloc`, so a Terrapin slice can
+//! be checked on embedded verifiers and in enclaves. Everything that touches
+//! files, threads or a runtime — persisted trees, streaming construction, the
+//! store — lives in the `terrapin` crate, which re-exports these items.
+//!
+//! * [`g`], [`manifest_bytes`], [`parse_manifest`], [`identifier_from_parts`],
+//!   [`tree_root`] — the spec primitives (sections 3-5), with `*_with` forms
+//!   taking any [`Profile`]. [`zero_identifier`] / [`zero_tree_root`] give them
+//!   for `n` zero bytes without the bytes.
+//! * [`derive_counts`] / [`offsets_from_counts`] — the tree shape and `.blocks`
+//!   layout that follow from `length` alone (section 6 step 3).
+//! * [`PathVerifier`] — checks one data block at a time against a trusted
+//!   identifier, given the hash-file groups on its path (section 6 steps 4-5).
+//! * [`Error`] — what every fallible call returns: an [`ErrorKind`] set where
+//!   the error arose, plus the failing block and [`PathLayer`] for validation.
+//!
+//! The SHA-256 implementation is a cargo feature: `sha2` (default, pure Rust),
+//! `sha2-asm`, `ring` or `boringssl` (which needs `std`); [`sha256_backend`]
+//! names the one in use, and [`Sha256Context`] gives plain SHA-256 through it. Feature `std` only lets the backends use it.
+
+#![no_std]
+
+extern crate alloc;
+
+mod error;
+mod manifest;
+mod profile;
+mod sha256;
+mod verify;
+
+pub use error::{Error, ErrorKind, PathLayer};
+pub use manifest::{
+    g, g_many, hex_to_32, identifier, identifier_from_parts, identifier_from_parts_with,
+    identifier_with, manifest_bytes, manifest_bytes_with, parse_manifest, parse_manifest_with,
+    to_hex, tree_root, tree_root_with, zero_identifier, zero_identifier_with, zero_tree_root,
+    zero_tree_root_with, BLOCK, FANOUT,
+};
+pub use profile::{profile_by_name, profile_of, profiles, Profile, TerrapinSha256, SHA256};
+#[cfg(feature = "blake3")]
+pub use profile::{TerrapinBlake3, BLAKE3};
+pub use sha256::{backend as sha256_backend, Sha256Context};
+pub use verify::{derive_counts, offsets_from_counts, PathVerifier};
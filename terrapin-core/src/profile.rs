@@ -0,0 +1,133 @@
+//! Hash profiles (spec section 12).
+//!
+//! A profile fixes the node hash `G` — used for data blocks, hash-file blocks
+//! and the manifest alike — together with the strings that make an identifier
+//! self-describing: the identifier prefix and the manifest `terrapin:` value.
+//! Block size, fan-out, tree shape and the manifest layout are common to every
+//! profile.
+//!
+//! [`TerrapinSha256`] (GitOID SHA-256; the backend is a cargo feature, see
+//! [`sha256_backend`](crate::sha256_backend)) is the default everywhere a profile is
+//! not given. [`TerrapinBlake3`] (feature `blake3`) keeps the same
+//! `blob <len>\0` framing over BLAKE3.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::sha256;
+
+/// A Terrapin hash profile.
+pub trait Profile: Send + Sync {
+    /// Identifier prefix and tree-head `algorithm` value, e.g. `terrapin-sha256`.
+    fn name(&self) -> &'static str;
+
+    /// Value of the manifest's `terrapin:` field, e.g. `sha256`.
+    fn manifest_algorithm(&self) -> &'static str;
+
+    /// The node hash `G` over one data block, hash-file block or manifest.
+    fn g(&self, data: &[u8]) -> [u8; 32];
+
+    /// `G` over several independent buffers, in order: the entry point for a
+    /// multi-buffer backend hashing them together in SIMD lanes. No profile
+    /// here has one; all use this default, scalar [`Profile::g`] per buffer.
+    fn g_many(&self, inputs: &[&[u8]]) -> Vec<[u8; 32]> {
+        inputs.iter().map(|d| self.g(d)).collect()
+    }
+
+    /// How many buffers [`Profile::g_many`] hashes at once to full effect;
+    /// callers batch this many blocks per call. `1` (no batching) for the
+    /// scalar default.
+    fn lanes(&self) -> usize {
+        1
+    }
+}
+
+/// `terrapin-sha256`: `G(x) = sha256("blob " + decimal(len(x)) + "\0" + x)`.
+pub struct TerrapinSha256;
+
+impl Profile for TerrapinSha256 {
+    fn name(&self) -> &'static str {
+        "terrapin-sha256"
+    }
+
+    fn manifest_algorithm(&self) -> &'static str {
+        "sha256"
+    }
+
+    fn g(&self, data: &[u8]) -> [u8; 32] {
+        sha256::blob_digest(data)
+    }
+}
+
+/// `terrapin-blake3`: `G(x) = blake3("blob " + decimal(len(x)) + "\0" + x)`.
+#[cfg(feature = "blake3")]
+pub struct TerrapinBlake3;
+
+#[cfg(feature = "blake3")]
+impl Profile for TerrapinBlake3 {
+    fn name(&self) -> &'static str {
+        "terrapin-blake3"
+    }
+
+    fn manifest_algorithm(&self) -> &'static str {
+        "blake3"
+    }
+
+    fn g(&self, data: &[u8]) -> [u8; 32] {
+        let mut buf = [0u8; BLOB_HEADER_MAX];
+        let mut h = blake3::Hasher::new();
+        h.update(blob_header(data.len(), &mut buf));
+        h.update(data);
+        *h.finalize().as_bytes()
+    }
+}
+
+/// Longest `blob <len>\0` header: `"blob "` + 20 digits + NUL.
+pub(crate) const BLOB_HEADER_MAX: usize = 26;
+
+/// `blob <decimal len>\0`, written into `buf` without allocating.
+pub(crate) fn blob_header(len: usize, buf: &mut [u8; BLOB_HEADER_MAX]) -> &[u8] {
+    let mut digits = [0u8; 20];
+    let mut n = len;
+    let mut i = digits.len();
+    loop {
+        i -= 1;
+        digits[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+    let d = &digits[i..];
+    buf[..5].copy_from_slice(b"blob ");
+    buf[5..5 + d.len()].copy_from_slice(d);
+    buf[5 + d.len()] = 0;
+    &buf[..6 + d.len()]
+}
+
+/// The default profile, `terrapin-sha256`.
+pub const SHA256: &dyn Profile = &TerrapinSha256;
+
+/// The `terrapin-blake3` profile.
+#[cfg(feature = "blake3")]
+pub const BLAKE3: &dyn Profile = &TerrapinBlake3;
+
+/// Every profile compiled in, default first.
+pub fn profiles() -> Vec<&'static dyn Profile> {
+    vec![
+        SHA256,
+        #[cfg(feature = "blake3")]
+        BLAKE3,
+    ]
+}
+
+/// Look a profile up by name (`terrapin-sha256`, `terrapin-blake3`).
+pub fn profile_by_name(name: &str) -> Option<&'static dyn Profile> {
+    profiles().into_iter().find(|p| p.name() == name)
+}
+
+/// The profile an identifier (`<name>:<hex>`) was made with.
+pub fn profile_of(identifier: &str) -> Option<&'static dyn Profile> {
+    let (name, _) = identifier.split_once(':')?;
+    profile_by_name(name)
+}
@@ -0,0 +1,206 @@
+//! SHA-256 backends for the `terrapin-sha256` profile, and for the plain
+//! SHA-256 digests ([`Sha256Context`]) the `terrapin` crate writes elsewhere.
+//!
+//! Exactly one implementation is compiled in, chosen by cargo feature:
+//!
+//! | feature     | implementation                                      |
+//! |-------------|-----------------------------------------------------|
+//! | `sha2`      | pure Rust (`sha2`), SHA-NI / ARMv8 picked at runtime (default) |
+//! | `sha2-asm`  | `sha2` with its assembly compression function       |
+//! | `ring`      | `ring::digest`                                      |
+//! | `boringssl` | BoringSSL via `boring` (needs a C toolchain and cmake) |
+//!
+//! When several are enabled the first of `boringssl`, `ring`, `sha2` wins, so
+//! a downstream crate can add a faster backend without disabling defaults.
+//! Every backend produces byte-identical output.
+
+use crate::profile::{blob_header, BLOB_HEADER_MAX};
+
+#[cfg(not(any(feature = "sha2", feature = "ring", feature = "boringssl")))]
+compile_error!("terrapin: enable one SHA-256 backend feature: `sha2`, `ring` or `boringssl`");
+
+/// Name of the compiled-in backend: `boringssl`, `ring` or `sha2`.
+pub fn backend() -> &'static str {
+    imp::NAME
+}
+
+/// `sha256("blob " + decimal(len(data)) + "\0" + data)` — the GitOID blob hash.
+pub fn blob_digest(data: &[u8]) -> [u8; 32] {
+    let mut buf = [0u8; BLOB_HEADER_MAX];
+    let mut h = Sha256Context::new();
+    h.update(blob_header(data.len(), &mut buf));
+    h.update(data);
+    h.finish()
+}
+
+/// Plain, incremental SHA-256 through the compiled-in backend, for digests
+/// outside the hash profiles such as SBOM checksums and OCI blob digests.
+pub struct Sha256Context(imp::State);
+
+impl Sha256Context {
+    pub fn new() -> Sha256Context {
+        Sha256Context(imp::State::new())
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    pub fn finish(self) -> [u8; 32] {
+        self.0.finish()
+    }
+
+    /// SHA-256 of `data` in one call.
+    pub fn digest(data: &[u8]) -> [u8; 32] {
+        let mut h = Sha256Context::new();
+        h.update(data);
+        h.finish()
+    }
+}
+
+impl Default for Sha256Context {
+    fn default() -> Sha256Context {
+        Sha256Context::new()
+    }
+}
+
+#[cfg(feature = "boringssl")]
+mod imp {
+    pub const NAME: &str = "boringssl";
+
+    pub struct State(boring::sha::Sha256);
+
+    impl State {
+        pub fn new() -> State {
+            State(boring::sha::Sha256::new())
+        }
+        pub fn update(&mut self, data: &[u8]) {
+            self.0.update(data);
+        }
+        pub fn finish(self) -> [u8; 32] {
+            self.0.finish()
+        }
+    }
+}
+
+#[cfg(all(feature = "ring", not(feature = "boringssl")))]
+mod imp {
+    pub const NAME: &str = "ring";
+
+    pub struct State(ring::digest::Context);
+
+    impl State {
+        pub fn new() -> State {
+            State(ring::digest::Context::new(&ring::digest::SHA256))
+        }
+        pub fn update(&mut self, data: &[u8]) {
+            self.0.update(data);
+        }
+        pub fn finish(self) -> [u8; 32] {
+            let mut out = [0u8; 32];
+            out.copy_from_slice(self.0.finish().as_ref());
+            out
+        }
+    }
+}
+
+#[cfg(all(feature = "sha2", not(any(feature = "ring", feature = "boringssl"))))]
+mod imp {
+    use sha2::{Digest, Sha256};
+
+    pub const NAME: &str = "sha2";
+
+    pub struct State(Sha256);
+
+    impl State {
+        pub fn new() -> State {
+            State(Sha256::new())
+        }
+        pub fn update(&mut self, data: &[u8]) {
+            self.0.update(data);
+        }
+        pub fn finish(self) -> [u8; 32] {
+            self.0.finalize().into()
+        }
+    }
+}
+
+#[cfg(not(any(feature = "sha2", feature = "ring", feature = "boringssl")))]
+mod imp {
+    pub const NAME: &str = "none";
+
+    pub struct State;
+
+    impl State {
+        pub fn new() -> State {
+            unreachable!()
+        }
+        pub fn update(&mut self, _data: &[u8]) {
+            unreachable!()
+        }
+        pub fn finish(self) -> [u8; 32] {
+            unreachable!()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::format;
+    use alloc::string::String;
+    use alloc::vec;
+
+    fn hex(b: &[u8; 32]) -> String {
+        b.iter().map(|x| format!("{:02x}", x)).collect()
+    }
+
+    // Verifies: REQ-G-007
+    #[test]
+    fn backend_matches_git_blob_vectors() {
+        // `git hash-object --object-format=sha256` values.
+        assert_eq!(
+            hex(&blob_digest(b"")),
+            "473a0f4c3be8a93681a267e3b1e9a7dcda1185436fe141f7749120a303721813"
+        );
+        assert_eq!(
+            hex(&blob_digest(b"hello world")),
+            "fee53a18d32820613c0527aa79be5cb30173c823a9b448fa4817767cc84c6f03"
+        );
+        assert_eq!(
+            hex(&blob_digest(&vec![0u8; 2 * 1024 * 1024])),
+            "67cbed9b97ddabde2863f4daefa4f57176567a7c3ccfa1560c1065f9c8af74d6"
+        );
+    }
+
+    // Verifies: REQ-G-010
+    #[test]
+    fn plain_sha256_matches_fips_vectors() {
+        assert_eq!(
+            hex(&Sha256Context::digest(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let mut h = Sha256Context::new();
+        for chunk in b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq".chunks(7) {
+            h.update(chunk);
+        }
+        assert_eq!(
+            hex(&h.finish()),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    // Verifies: REQ-G-008
+    #[test]
+    fn backend_precedence() {
+        let expected = if cfg!(feature = "boringssl") {
+            "boringssl"
+        } else if cfg!(feature = "ring") {
+            "ring"
+        } else {
+            "sha2"
+        };
+        assert_eq!(backend(), expected);
+    }
+}
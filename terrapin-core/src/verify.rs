@@ -0,0 +1,220 @@
+//! Tree shape and single-block path verification (spec section 6).
+//!
+//! The shape of a tree — hashes per layer and where each layer starts in the
+//! concatenated `.blocks` byte space — follows from `length` alone. A
+//! [`PathVerifier`] anchors on a trusted identifier and checks one data block
+//! at a time by recomputing `G` upward through the hash-file groups on its
+//! path, however those groups are obtained.
+
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::error::{Error, PathLayer};
+use crate::manifest::{hex_to_32, identifier_from_parts_with, BLOCK, FANOUT};
+use crate::profile::Profile;
+
+/// A cached hash-file group along the validation path:
+/// `(group_start_index, group_bytes, node = g(group))`.
+type GroupCache = Option<(u64, Vec<u8>, [u8; 32])>;
+
+/// Number of hashes at each layer, derived solely from `length` and the fixed
+/// block size (spec section 6 step 3). `layers[0]` is the leaf count.
+pub fn derive_counts(length: u64) -> Vec<u64> {
+    let nblocks = if length == 0 {
+        1 // empty dataset is one empty leaf
+    } else {
+        length.div_ceil(BLOCK as u64)
+    };
+    let mut counts = vec![nblocks];
+    while *counts.last().unwrap() > FANOUT as u64 {
+        let prev = *counts.last().unwrap();
+        counts.push(prev.div_ceil(FANOUT as u64));
+    }
+    counts
+}
+
+/// Byte offset of each layer within the concatenated hash files (`.blocks`).
+pub fn offsets_from_counts(counts: &[u64]) -> Vec<u64> {
+    let mut offs = Vec::with_capacity(counts.len());
+    let mut acc = 0u64;
+    for &c in counts {
+        offs.push(acc);
+        acc += c * 32;
+    }
+    offs
+}
+
+/// Verifies data blocks one at a time against a trusted identifier.
+///
+/// The hash-file groups on each block's path come from a caller-supplied
+/// `fetch(offset, len)` over the `.blocks` byte space, or directly as a slice
+/// per layer; one group per layer is cached, so a run of neighbouring blocks
+/// fetches each group once.
+pub struct PathVerifier<'p> {
+    profile: &'p dyn Profile,
+    length: u64,
+    counts: Vec<u64>,
+    offsets: Vec<u64>,
+    root: [u8; 32],
+    cache: Vec<GroupCache>,
+}
+
+impl<'p> PathVerifier<'p> {
+    /// Bind `length` and tree root `tree_hex` to `identifier`: `G(manifest)`
+    /// must equal it (spec section 6 steps 1-2).
+    pub fn new(
+        profile: &'p dyn Profile,
+        identifier: &str,
+        length: u64,
+        tree_hex: &str,
+    ) -> Result<Self, Error> {
+        let root = hex_to_32(tree_hex).ok_or_else(|| Error::malformed("head: tree not 64 hex"))?;
+        if identifier_from_parts_with(profile, length, &root) != identifier {
+            return Err(Error::malformed("tree: identifier does not match manifest"));
+        }
+        let counts = derive_counts(length);
+        Ok(PathVerifier {
+            profile,
+            length,
+            offsets: offsets_from_counts(&counts),
+            cache: vec![None; counts.len()],
+            counts,
+            root,
+        })
+    }
+
+    /// Hashes per layer; `counts()[0]` is the number of data blocks.
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// The verified tree root.
+    pub fn root(&self) -> [u8; 32] {
+        self.root
+    }
+
+    /// Byte range `(offset, len)` within `.blocks` of the hash-file group at
+    /// `layer` starting at hash index `group_start`.
+    pub fn group_range(&self, layer: usize, group_start: u64) -> (u64, usize) {
+        let remaining = self.counts[layer] - group_start;
+        let len_hashes = remaining.min(FANOUT as u64) as usize;
+        (self.offsets[layer] + group_start * 32, len_hashes * 32)
+    }
+
+    /// Exact length of data block `index` (the final block may be short).
+    pub fn block_len(&self, index: u64) -> usize {
+        let block_off = index * BLOCK as u64;
+        (self.length.saturating_sub(block_off)).min(BLOCK as u64) as usize
+    }
+
+    /// Verify `block` as data block `index`, recomputing `G` upward to the tree
+    /// root (spec section 6 steps 4-5), pulling groups through `fetch`.
+    pub fn verify_block<F>(&mut self, index: u64, block: &[u8], mut fetch: F) -> Result<(), Error>
+    where
+        F: FnMut(u64, usize) -> Result<Vec<u8>, Error>,
+    {
+        self.verify(index, block, &mut |_, off, len| fetch(off, len))
+    }
+
+    /// [`Self::verify_block`] with the path supplied up front: `groups[l]` is
+    /// the whole layer-`l` group holding the block's path hash.
+    pub fn verify_block_with_groups(
+        &mut self,
+        index: u64,
+        block: &[u8],
+        groups: &[&[u8]],
+    ) -> Result<(), Error> {
+        self.verify(index, block, &mut |layer, _, _| {
+            groups.get(layer).map(|g| g.to_vec()).ok_or_else(|| {
+                Error::malformed(format!("missing hash-file group for layer {}", layer))
+            })
+        })
+    }
+
+    fn verify(
+        &mut self,
+        index: u64,
+        block: &[u8],
+        fetch: &mut dyn FnMut(usize, u64, usize) -> Result<Vec<u8>, Error>,
+    ) -> Result<(), Error> {
+        if self.length == 0 {
+            if index != 0 || !block.is_empty() || self.profile.g(b"") != self.root {
+                return Err(Error::validation(
+                    "validation failed: empty dataset root mismatch",
+                ));
+            }
+            return Ok(());
+        }
+        if index >= self.counts[0] {
+            return Err(Error::out_of_range(format!(
+                "block {} out of range for {} blocks",
+                index, self.counts[0]
+            )));
+        }
+        if block.len() != self.block_len(index) {
+            return Err(Error::validation(format!(
+                "validation failed at block {} (length {} != {})",
+                index,
+                block.len(),
+                self.block_len(index)
+            ))
+            .at_block(index, None));
+        }
+
+        let mut h = self.profile.g(block);
+
+        if self.counts[0] == 1 {
+            if h != self.root {
+                return Err(
+                    Error::validation(format!("validation failed at block {}", index))
+                        .at_block(index, Some(PathLayer::Root)),
+                );
+            }
+            return Ok(());
+        }
+
+        let mut idx = index;
+        for l in 0..self.cache.len() {
+            let gstart = (idx / FANOUT as u64) * FANOUT as u64;
+            let posn = (idx - gstart) as usize;
+
+            let need_reload = match &self.cache[l] {
+                Some((gs, _, _)) => *gs != gstart,
+                None => true,
+            };
+            if need_reload {
+                let (off, len) = self.group_range(l, gstart);
+                let bytes = fetch(l, off, len)?;
+                if bytes.len() != len {
+                    return Err(Error::malformed(format!(
+                        "blocks truncated: layer {} group {} has {} of {} bytes",
+                        l,
+                        gstart / FANOUT as u64,
+                        bytes.len(),
+                        len
+                    )));
+                }
+                let node = self.profile.g(&bytes);
+                self.cache[l] = Some((gstart, bytes, node));
+            }
+            let (_, bytes, node) = self.cache[l].as_ref().unwrap();
+            if bytes[posn * 32..posn * 32 + 32] != h[..] {
+                return Err(Error::validation(format!(
+                    "validation failed at block {} (layer {})",
+                    index, l
+                ))
+                .at_block(index, Some(PathLayer::Hashes(l))));
+            }
+            h = *node;
+            idx /= FANOUT as u64;
+        }
+        if h != self.root {
+            return Err(
+                Error::validation(format!("validation failed at block {} (root)", index))
+                    .at_block(index, Some(PathLayer::Root)),
+            );
+        }
+        Ok(())
+    }
+}
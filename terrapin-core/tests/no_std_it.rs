@@ -0,0 +1,85 @@
+//! Build check: `terrapin-core` must compile into a `#![no_std]` crate.
+//!
+//! A fixture crate with its own `#[panic_handler]` is built for the host
+//! target against this crate with default features off. If anything in the
+//! dependency graph links `std`, the build fails with a duplicate
+//! `panic_impl` lang item (E0152), so this catches a stray `std` dependency
+//! on Linux without an embedded target installed. With a bare-metal target
+//! installed (`TERRAPIN_NO_STD_TARGET`, e.g. `thumbv7em-none-eabihf`), it
+//! builds for that target instead.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const FIXTURE_LIB: &str = r#"#![no_std]
+
+extern crate alloc;
+
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
+
+pub fn check(identifier: &str, length: u64, tree: &str, block: &[u8], leaves: &[u8]) -> Result<(), terrapin_core::Error> {
+    let mut v = terrapin_core::PathVerifier::new(terrapin_core::SHA256, identifier, length, tree)?;
+    v.verify_block_with_groups(0, block, &[leaves])
+}
+"#;
+
+fn build_fixture(features: &str) -> (bool, String) {
+    let core = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"))
+        .join(format!("no-std-fixture-{}", features.replace(',', "-")));
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(
+        dir.join("Cargo.toml"),
+        format!(
+            "[package]\nname = \"no-std-fixture\"\nversion = \"0.0.0\"\nedition = \"2021\"\n\n\
+             [dependencies]\nterrapin-core = {{ path = {:?}, default-features = false, features = [{}] }}\n\n\
+             [workspace]\n",
+            core,
+            features
+                .split(',')
+                .map(|f| format!("{:?}", f))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    )
+    .unwrap();
+    fs::write(dir.join("src/lib.rs"), FIXTURE_LIB).unwrap();
+    // Reuse the workspace's resolved versions so the fixture builds offline.
+    let lock = core.join("../Cargo.lock");
+    if lock.exists() {
+        fs::copy(&lock, dir.join("Cargo.lock")).unwrap();
+    }
+
+    let mut cmd = Command::new(env!("CARGO"));
+    cmd.current_dir(&dir).args(["build", "--lib", "--quiet"]);
+    if let Ok(target) = std::env::var("TERRAPIN_NO_STD_TARGET") {
+        cmd.args(["--target", &target]);
+    }
+    let out = cmd.output().unwrap();
+    (
+        out.status.success(),
+        String::from_utf8_lossy(&out.stderr).into_owned(),
+    )
+}
+
+// Verifies: REQ-CORE-004
+#[test]
+fn builds_without_std() {
+    for features in ["sha2", "sha2,blake3", "ring"] {
+        let (ok, stderr) = build_fixture(features);
+        assert!(
+            ok,
+            "no_std build with features {} failed:\n{}",
+            features, stderr
+        );
+    }
+    // Control: pulling in std through the backends must trip the check.
+    if std::env::var("TERRAPIN_NO_STD_TARGET").is_err() {
+        let (ok, stderr) = build_fixture("sha2,std");
+        assert!(!ok && stderr.contains("E0152"), "{}", stderr);
+    }
+}
@@ -0,0 +1,211 @@
+//! Integration tests for `PathVerifier`: single-block verification against a
+//! trusted identifier from caller-supplied hash-file groups (spec section 6).
+//!
+//! Trees here are assembled by hand from `g` and the leaf layer, so every
+//! expectation is independent of the std `TreeBuilder`. A multi-layer tree
+//! only needs the verified block's own leaf to be real; the other leaf hashes
+//! are arbitrary, which keeps a two-layer path to a few MiB.
+
+use std::cell::Cell;
+
+use terrapin_core::{
+    derive_counts, g, identifier, identifier_from_parts, offsets_from_counts, parse_manifest,
+    to_hex, tree_root, Error, ErrorKind, PathLayer, PathVerifier, BLOCK, FANOUT, SHA256,
+};
+
+/// Deterministic pseudo-random bytes (xorshift64*).
+fn fill(len: usize, seed: u64) -> Vec<u8> {
+    let mut x = seed | 1;
+    (0..len)
+        .map(|_| {
+            x ^= x >> 12;
+            x ^= x << 25;
+            x ^= x >> 27;
+            (x.wrapping_mul(0x2545F4914F6CDD1D) >> 33) as u8
+        })
+        .collect()
+}
+
+/// The leaf hash file of `data`.
+fn leaf_layer(data: &[u8]) -> Vec<u8> {
+    data.chunks(BLOCK).flat_map(g).collect()
+}
+
+fn verifier<'a>(data: &[u8]) -> PathVerifier<'a> {
+    let root = tree_root(data);
+    PathVerifier::new(SHA256, &identifier(data), data.len() as u64, &to_hex(&root)).unwrap()
+}
+
+// Verifies: REQ-CORE-001
+#[test]
+fn verifies_every_block_from_supplied_groups() {
+    let data = fill(3 * BLOCK + 77, 3);
+    let leaves = leaf_layer(&data);
+    let mut v = verifier(&data);
+    assert_eq!(v.counts(), &[4]);
+    assert_eq!(v.root(), tree_root(&data));
+    for (i, block) in data.chunks(BLOCK).enumerate() {
+        assert_eq!(v.block_len(i as u64), block.len());
+        v.verify_block_with_groups(i as u64, block, &[&leaves])
+            .unwrap();
+    }
+
+    // The same path through `fetch` over the `.blocks` byte space; the one
+    // leaf group is fetched once for the whole run.
+    let fetches = Cell::new(0);
+    let mut v = verifier(&data);
+    for (i, block) in data.chunks(BLOCK).enumerate() {
+        v.verify_block(i as u64, block, |off, len| {
+            fetches.set(fetches.get() + 1);
+            Ok(leaves[off as usize..off as usize + len].to_vec())
+        })
+        .unwrap();
+    }
+    assert_eq!(fetches.get(), 1);
+}
+
+// Verifies: REQ-CORE-001
+#[test]
+fn verifies_single_block_and_empty_datasets() {
+    let one = fill(BLOCK, 5);
+    verifier(&one)
+        .verify_block_with_groups(0, &one, &[])
+        .unwrap();
+    verifier(&[]).verify_block_with_groups(0, &[], &[]).unwrap();
+    assert!(verifier(&[])
+        .verify_block_with_groups(0, b"x", &[])
+        .is_err());
+}
+
+// Verifies: REQ-CORE-002
+#[test]
+fn verifies_a_two_layer_path_from_its_groups() {
+    let length = (FANOUT as u64 + 1) * BLOCK as u64;
+    let counts = derive_counts(length);
+    assert_eq!(counts, vec![FANOUT as u64 + 1, 2]);
+    assert_eq!(
+        offsets_from_counts(&counts),
+        vec![0, (FANOUT as u64 + 1) * 32]
+    );
+
+    let block = fill(BLOCK, 7);
+    let mut leaves = fill((FANOUT + 1) * 32, 9);
+    let index = FANOUT - 2;
+    leaves[index * 32..index * 32 + 32].copy_from_slice(&g(&block));
+    let mut upper = g(&leaves[..BLOCK]).to_vec();
+    upper.extend_from_slice(&g(&leaves[BLOCK..]));
+    let root = g(&upper);
+    let id = identifier_from_parts(length, &root);
+
+    let mut v = PathVerifier::new(SHA256, &id, length, &to_hex(&root)).unwrap();
+    assert_eq!(v.group_range(0, 0), (0, BLOCK));
+    assert_eq!(v.group_range(0, FANOUT as u64), (BLOCK as u64, 32));
+    assert_eq!(v.group_range(1, 0), (BLOCK as u64 + 32, 64));
+    v.verify_block_with_groups(index as u64, &block, &[&leaves[..BLOCK], &upper])
+        .unwrap();
+
+    // A fresh verifier: a group already on the cached path is not re-read.
+    let mut bad = upper.clone();
+    bad[5] ^= 1;
+    let mut v = PathVerifier::new(SHA256, &id, length, &to_hex(&root)).unwrap();
+    let err = v
+        .verify_block_with_groups(index as u64, &block, &[&leaves[..BLOCK], &bad])
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Validation);
+    assert_eq!(err.layer(), Some(PathLayer::Hashes(1)));
+}
+
+// Verifies: REQ-CORE-003
+#[test]
+fn rejects_tampering_and_malformed_input() {
+    let data = fill(2 * BLOCK + 5, 11);
+    let leaves = leaf_layer(&data);
+    let root_hex = to_hex(&tree_root(&data));
+    let id = identifier(&data);
+
+    // The head must bind to the identifier.
+    let err = PathVerifier::new(SHA256, &id, data.len() as u64 + 1, &root_hex)
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), ErrorKind::Malformed);
+    let err = PathVerifier::new(SHA256, &id, data.len() as u64, "abc")
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), ErrorKind::Malformed);
+
+    let mut v = verifier(&data);
+    let mut block = data[..BLOCK].to_vec();
+    block[123] ^= 1;
+    assert!(v.verify_block_with_groups(0, &block, &[&leaves]).is_err());
+
+    let mut forged = leaves.clone();
+    forged[0] ^= 1;
+    let mut v = verifier(&data);
+    assert!(v
+        .verify_block_with_groups(0, &data[..BLOCK], &[&forged])
+        .is_err());
+
+    let mut v = verifier(&data);
+    let err = v
+        .verify_block_with_groups(2, &data[2 * BLOCK..2 * BLOCK + 4], &[&leaves])
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Validation);
+    assert_eq!((err.block(), err.layer()), (Some(2), None));
+    let err = v.verify_block_with_groups(3, &[], &[&leaves]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::OutOfRange);
+    let err = v
+        .verify_block_with_groups(0, &data[..BLOCK], &[&leaves[..64]])
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Malformed);
+    let err = v
+        .verify_block_with_groups(0, &data[..BLOCK], &[])
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Malformed);
+}
+
+// Verifies: REQ-CORE-005
+#[test]
+fn errors_carry_their_kind_and_failing_block() {
+    let data = fill(2 * BLOCK + 5, 13);
+    let leaves = leaf_layer(&data);
+    let id = identifier(&data);
+
+    let err = PathVerifier::new(SHA256, &id, data.len() as u64, "abc")
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), ErrorKind::Malformed);
+    assert_eq!(err.block(), None);
+    let err = parse_manifest(b"terrapin: nope\n").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Malformed);
+
+    let mut v = verifier(&data);
+    let err = v.verify_block_with_groups(3, &[], &[&leaves]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::OutOfRange);
+
+    let mut block = data[BLOCK..2 * BLOCK].to_vec();
+    block[9] ^= 1;
+    let err = v
+        .verify_block_with_groups(1, &block, &[&leaves])
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Validation);
+    assert_eq!(err.block(), Some(1));
+    assert_eq!(err.layer(), Some(PathLayer::Hashes(0)));
+
+    let mut forged = leaves.clone();
+    forged[40] ^= 1;
+    let mut v = verifier(&data);
+    let err = v
+        .verify_block_with_groups(0, &data[..BLOCK], &[&forged])
+        .unwrap_err();
+    assert_eq!((err.block(), err.layer()), (Some(0), Some(PathLayer::Root)));
+
+    // A fetch error reaches the caller as raised, kind and all.
+    let mut v = verifier(&data);
+    let err = v
+        .verify_block(0, &data[..BLOCK], |_, _| Err(Error::io("GET: refused")))
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Io);
+    assert_eq!(err.message(), "GET: refused");
+    assert_eq!(ErrorKind::OutOfRange.name(), "out_of_range");
+    assert_eq!(ErrorKind::Other.name(), "error");
+}
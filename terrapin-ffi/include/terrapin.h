@@ -0,0 +1,149 @@
+#ifndef TERRAPIN_H
+#define TERRAPIN_H
+
+/* Generated by cbindgen from terrapin-ffi/src/lib.rs; do not edit. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+// Version of this ABI; bumped on any incompatible change.
+#define TERRAPIN_ABI_VERSION 1
+
+// Buffer size that holds any identifier this library produces, with its NUL.
+#define TERRAPIN_IDENTIFIER_BUF_LEN 96
+
+// Result of every fallible call.
+typedef enum TerrapinStatus {
+  TERRAPIN_STATUS_OK = 0,
+  // A required pointer argument was NULL.
+  TERRAPIN_STATUS_NULL_ARGUMENT = 1,
+  // A string argument was not valid UTF-8.
+  TERRAPIN_STATUS_INVALID_UTF8 = 2,
+  // The named hash profile is not supported.
+  TERRAPIN_STATUS_UNKNOWN_PROFILE = 3,
+  // An output buffer is too small; nothing was consumed.
+  TERRAPIN_STATUS_BUFFER_TOO_SMALL = 4,
+  // A file could not be opened, read or written.
+  TERRAPIN_STATUS_IO = 5,
+  // A tree head, hash file or manifest is malformed, truncated or not
+  // canonical.
+  TERRAPIN_STATUS_MALFORMED = 6,
+  // The requested byte range lies outside the dataset.
+  TERRAPIN_STATUS_OUT_OF_RANGE = 7,
+  // Data, hash files or head do not verify against the identifier.
+  TERRAPIN_STATUS_VALIDATION = 8,
+  // A panic was caught inside the library.
+  TERRAPIN_STATUS_PANIC = 9,
+  // A signature is missing, unsupported or does not verify.
+  TERRAPIN_STATUS_SIGNATURE = 10,
+  // Any other failure, such as an unsupported input.
+  TERRAPIN_STATUS_OTHER = 11,
+} TerrapinStatus;
+
+// Incremental hasher handle.
+typedef struct TerrapinHasher TerrapinHasher;
+
+// Persisted tree handle.
+typedef struct TerrapinTree TerrapinTree;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The ABI version this library implements (`TERRAPIN_ABI_VERSION`).
+uint32_t terrapin_abi_version(void);
+
+// Description of the last failure on this thread, or an empty string. Valid
+// until the next failing call on the same thread.
+const char *terrapin_last_error(void);
+
+// A static, NUL-terminated name for `status`.
+const char *terrapin_status_str(enum TerrapinStatus status);
+
+// Create a hasher for `profile` (NULL for `terrapin-sha256`) in `*out`.
+//
+// # Safety
+// `profile` is NULL or a NUL-terminated string; `out` is valid for a write.
+enum TerrapinStatus terrapin_hasher_new(const char *profile, struct TerrapinHasher **out);
+
+// Append `len` bytes at `data` to the dataset. `data` may be NULL when `len`
+// is 0.
+//
+// # Safety
+// `hasher` is a live handle; `data` is valid for `len` bytes of reads.
+enum TerrapinStatus terrapin_hasher_update(struct TerrapinHasher *hasher,
+                                           const uint8_t *data,
+                                           size_t len);
+
+// Finish hashing and write the NUL-terminated identifier into `out` (`cap`
+// bytes; `TERRAPIN_IDENTIFIER_BUF_LEN` always suffices). The hasher is
+// consumed on success; on `TERRAPIN_STATUS_BUFFER_TOO_SMALL` it is left
+// untouched and may be finalized again.
+//
+// # Safety
+// `hasher` is a live handle; `out` is valid for `cap` bytes of writes.
+enum TerrapinStatus terrapin_hasher_finalize(struct TerrapinHasher *hasher, char *out, size_t cap);
+
+// Discard a hasher without finalizing. NULL is ignored.
+//
+// # Safety
+// `hasher` is NULL or a live handle, not used afterwards.
+void terrapin_hasher_free(struct TerrapinHasher *hasher);
+
+// Open the persisted tree `<base>.head` / `<base>.blocks` into `*out`.
+//
+// # Safety
+// `base` is a NUL-terminated path; `out` is valid for a write.
+enum TerrapinStatus terrapin_tree_open(const char *base, struct TerrapinTree **out);
+
+// Validate bytes `[offset, offset + len)` of the data file `data_path`
+// against the tree, recomputing `G` from each covering block up to the
+// identifier. Compare `terrapin_tree_identifier` with a trusted identifier
+// to bind the tree itself.
+//
+// # Safety
+// `tree` is a live handle; `data_path` is a NUL-terminated path.
+enum TerrapinStatus terrapin_tree_validate_range(const struct TerrapinTree *tree,
+                                                 const char *data_path,
+                                                 uint64_t offset,
+                                                 uint64_t len);
+
+// Write the tree's NUL-terminated identifier into `out` (`cap` bytes).
+//
+// # Safety
+// `tree` is a live handle; `out` is valid for `cap` bytes of writes.
+enum TerrapinStatus terrapin_tree_identifier(const struct TerrapinTree *tree,
+                                             char *out,
+                                             size_t cap);
+
+// The dataset length the tree commits to; 0 for a NULL handle.
+//
+// # Safety
+// `tree` is NULL or a live handle.
+uint64_t terrapin_tree_length(const struct TerrapinTree *tree);
+
+// Close a tree handle. NULL is ignored.
+//
+// # Safety
+// `tree` is NULL or a live handle, not used afterwards.
+void terrapin_tree_close(struct TerrapinTree *tree);
+
+// Parse a canonical root manifest under `profile` (NULL for
+// `terrapin-sha256`): the dataset length into `*length` and the 64-hex tree
+// root, NUL-terminated, into `tree_hex` (`cap` >= 65 bytes).
+//
+// # Safety
+// `manifest` is valid for `len` bytes of reads; `length` for a write;
+// `tree_hex` for `cap` bytes of writes; `profile` is NULL or NUL-terminated.
+enum TerrapinStatus terrapin_manifest_parse(const char *profile,
+                                            const uint8_t *manifest,
+                                            size_t len,
+                                            uint64_t *length,
+                                            char *tree_hex,
+                                            size_t cap);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* TERRAPIN_H */
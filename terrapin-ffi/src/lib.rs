@@ -0,0 +1,388 @@
+//! C ABI for Terrapin.
+//!
+//! A stable `extern "C"` surface over the `terrapin` crate for services
+//! written in other languages; the header is `include/terrapin.h`, generated
+//! from this file by cbindgen (`cbindgen.toml`).
+//!
+//! * `terrapin_hasher_*` — incremental hashing to an identifier.
+//! * `terrapin_tree_*` — open a persisted tree, validate byte ranges of a data
+//!   file against it, close it.
+//! * [`terrapin_manifest_parse`] — strict canonical manifest parsing.
+//!
+//! Every fallible call returns a [`TerrapinStatus`]; on failure
+//! [`terrapin_last_error`] describes it. Handles are opaque and owned by the
+//! caller until passed to their `*_free` / `*_close` / `*_finalize`. Strings
+//! are NUL-terminated UTF-8; output strings are written into caller buffers.
+//! A panic never crosses the boundary: it is reported as
+//! [`TerrapinStatus::Panic`].
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+use std::slice;
+
+use terrapin::{
+    parse_manifest_with, profile_by_name, Error, ErrorKind, Hasher, PersistedTree, Profile, SHA256,
+};
+
+/// Version of this ABI; bumped on any incompatible change.
+pub const TERRAPIN_ABI_VERSION: u32 = 1;
+
+/// Buffer size that holds any identifier this library produces, with its NUL.
+pub const TERRAPIN_IDENTIFIER_BUF_LEN: usize = 96;
+
+/// Result of every fallible call.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TerrapinStatus {
+    Ok = 0,
+    /// A required pointer argument was NULL.
+    NullArgument = 1,
+    /// A string argument was not valid UTF-8.
+    InvalidUtf8 = 2,
+    /// The named hash profile is not supported.
+    UnknownProfile = 3,
+    /// An output buffer is too small; nothing was consumed.
+    BufferTooSmall = 4,
+    /// A file could not be opened, read or written.
+    Io = 5,
+    /// A tree head, hash file or manifest is malformed, truncated or not
+    /// canonical.
+    Malformed = 6,
+    /// The requested byte range lies outside the dataset.
+    OutOfRange = 7,
+    /// Data, hash files or head do not verify against the identifier.
+    Validation = 8,
+    /// A panic was caught inside the library.
+    Panic = 9,
+    /// A signature is missing, unsupported or does not verify.
+    Signature = 10,
+    /// Any other failure, such as an unsupported input.
+    Other = 11,
+}
+
+/// Incremental hasher handle.
+pub struct TerrapinHasher {
+    profile: &'static dyn Profile,
+    inner: Hasher,
+}
+
+/// Persisted tree handle.
+pub struct TerrapinTree {
+    inner: PersistedTree,
+}
+
+type Failure = (TerrapinStatus, String);
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// Run `f`, record any failure for [`terrapin_last_error`], and turn panics
+/// into [`TerrapinStatus::Panic`].
+fn guard<F: FnOnce() -> Result<(), Failure>>(f: F) -> TerrapinStatus {
+    let (status, msg) = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return TerrapinStatus::Ok,
+        Ok(Err(failure)) => failure,
+        Err(_) => (TerrapinStatus::Panic, "panic inside terrapin".to_string()),
+    };
+    let msg = CString::new(msg.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = msg);
+    status
+}
+
+fn non_null<T>(p: *const T, what: &str) -> Result<(), Failure> {
+    if p.is_null() {
+        return Err((TerrapinStatus::NullArgument, format!("{} is NULL", what)));
+    }
+    Ok(())
+}
+
+/// # Safety
+/// `p` is NULL or a NUL-terminated string that outlives the call.
+unsafe fn str_arg<'a>(p: *const c_char, what: &str) -> Result<&'a str, Failure> {
+    non_null(p, what)?;
+    CStr::from_ptr(p).to_str().map_err(|_| {
+        (
+            TerrapinStatus::InvalidUtf8,
+            format!("{} is not UTF-8", what),
+        )
+    })
+}
+
+/// # Safety
+/// `p` is NULL or a NUL-terminated string.
+unsafe fn profile_arg(p: *const c_char) -> Result<&'static dyn Profile, Failure> {
+    if p.is_null() {
+        return Ok(SHA256);
+    }
+    let name = str_arg(p, "profile")?;
+    profile_by_name(name).ok_or_else(|| {
+        (
+            TerrapinStatus::UnknownProfile,
+            format!("unknown profile {}", name),
+        )
+    })
+}
+
+/// # Safety
+/// `out` is NULL or valid for `cap` bytes of writes.
+unsafe fn write_str(s: &str, out: *mut c_char, cap: usize) -> Result<(), Failure> {
+    non_null(out, "output buffer")?;
+    if cap <= s.len() {
+        return Err((
+            TerrapinStatus::BufferTooSmall,
+            format!("need {} bytes, buffer has {}", s.len() + 1, cap),
+        ));
+    }
+    ptr::copy_nonoverlapping(s.as_ptr(), out as *mut u8, s.len());
+    *out.add(s.len()) = 0;
+    Ok(())
+}
+
+/// The status for a library error's kind. An identifier that is not the one
+/// trusted is a [`TerrapinStatus::Validation`] failure like any other data
+/// that does not verify against it.
+fn failure_of(e: Error) -> Failure {
+    let status = match e.kind() {
+        ErrorKind::Io => TerrapinStatus::Io,
+        ErrorKind::Malformed => TerrapinStatus::Malformed,
+        ErrorKind::OutOfRange => TerrapinStatus::OutOfRange,
+        ErrorKind::IdentifierMismatch | ErrorKind::Validation => TerrapinStatus::Validation,
+        ErrorKind::Signature => TerrapinStatus::Signature,
+        ErrorKind::Other => TerrapinStatus::Other,
+    };
+    (status, e.to_string())
+}
+
+/// The ABI version this library implements (`TERRAPIN_ABI_VERSION`).
+#[no_mangle]
+pub extern "C" fn terrapin_abi_version() -> u32 {
+    TERRAPIN_ABI_VERSION
+}
+
+/// Description of the last failure on this thread, or an empty string. Valid
+/// until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn terrapin_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ptr())
+}
+
+/// A static, NUL-terminated name for `status`.
+#[no_mangle]
+pub extern "C" fn terrapin_status_str(status: TerrapinStatus) -> *const c_char {
+    let s: &'static [u8] = match status {
+        TerrapinStatus::Ok => b"ok\0",
+        TerrapinStatus::NullArgument => b"null argument\0",
+        TerrapinStatus::InvalidUtf8 => b"invalid utf-8\0",
+        TerrapinStatus::UnknownProfile => b"unknown profile\0",
+        TerrapinStatus::BufferTooSmall => b"buffer too small\0",
+        TerrapinStatus::Io => b"i/o error\0",
+        TerrapinStatus::Malformed => b"malformed\0",
+        TerrapinStatus::OutOfRange => b"out of range\0",
+        TerrapinStatus::Validation => b"validation failed\0",
+        TerrapinStatus::Panic => b"panic\0",
+        TerrapinStatus::Signature => b"signature failed\0",
+        TerrapinStatus::Other => b"error\0",
+    };
+    s.as_ptr() as *const c_char
+}
+
+/// Create a hasher for `profile` (NULL for `terrapin-sha256`) in `*out`.
+///
+/// # Safety
+/// `profile` is NULL or a NUL-terminated string; `out` is valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn terrapin_hasher_new(
+    profile: *const c_char,
+    out: *mut *mut TerrapinHasher,
+) -> TerrapinStatus {
+    guard(|| {
+        non_null(out, "out")?;
+        let profile = profile_arg(profile)?;
+        let h = Box::new(TerrapinHasher {
+            profile,
+            inner: Hasher::with_profile(profile),
+        });
+        *out = Box::into_raw(h);
+        Ok(())
+    })
+}
+
+/// Append `len` bytes at `data` to the dataset. `data` may be NULL when `len`
+/// is 0.
+///
+/// # Safety
+/// `hasher` is a live handle; `data` is valid for `len` bytes of reads.
+#[no_mangle]
+pub unsafe extern "C" fn terrapin_hasher_update(
+    hasher: *mut TerrapinHasher,
+    data: *const u8,
+    len: usize,
+) -> TerrapinStatus {
+    guard(|| {
+        non_null(hasher, "hasher")?;
+        if len == 0 {
+            return Ok(());
+        }
+        non_null(data, "data")?;
+        (*hasher).inner.update(slice::from_raw_parts(data, len));
+        Ok(())
+    })
+}
+
+/// Finish hashing and write the NUL-terminated identifier into `out` (`cap`
+/// bytes; `TERRAPIN_IDENTIFIER_BUF_LEN` always suffices). The hasher is
+/// consumed on success; on `TERRAPIN_STATUS_BUFFER_TOO_SMALL` it is left
+/// untouched and may be finalized again.
+///
+/// # Safety
+/// `hasher` is a live handle; `out` is valid for `cap` bytes of writes.
+#[no_mangle]
+pub unsafe extern "C" fn terrapin_hasher_finalize(
+    hasher: *mut TerrapinHasher,
+    out: *mut c_char,
+    cap: usize,
+) -> TerrapinStatus {
+    guard(|| {
+        non_null(hasher, "hasher")?;
+        non_null(out, "output buffer")?;
+        let need = (*hasher).profile.name().len() + 1 + 64;
+        if cap <= need {
+            return Err((
+                TerrapinStatus::BufferTooSmall,
+                format!("need {} bytes, buffer has {}", need + 1, cap),
+            ));
+        }
+        let h = Box::from_raw(hasher);
+        write_str(&h.inner.identifier(), out, cap)
+    })
+}
+
+/// Discard a hasher without finalizing. NULL is ignored.
+///
+/// # Safety
+/// `hasher` is NULL or a live handle, not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn terrapin_hasher_free(hasher: *mut TerrapinHasher) {
+    if !hasher.is_null() {
+        drop(Box::from_raw(hasher));
+    }
+}
+
+/// Open the persisted tree `<base>.head` / `<base>.blocks` into `*out`.
+///
+/// # Safety
+/// `base` is a NUL-terminated path; `out` is valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn terrapin_tree_open(
+    base: *const c_char,
+    out: *mut *mut TerrapinTree,
+) -> TerrapinStatus {
+    guard(|| {
+        non_null(out, "out")?;
+        let base = str_arg(base, "base")?;
+        let inner = PersistedTree::read(Path::new(base)).map_err(failure_of)?;
+        *out = Box::into_raw(Box::new(TerrapinTree { inner }));
+        Ok(())
+    })
+}
+
+/// Validate bytes `[offset, offset + len)` of the data file `data_path`
+/// against the tree, recomputing `G` from each covering block up to the
+/// identifier. Compare `terrapin_tree_identifier` with a trusted identifier
+/// to bind the tree itself.
+///
+/// # Safety
+/// `tree` is a live handle; `data_path` is a NUL-terminated path.
+#[no_mangle]
+pub unsafe extern "C" fn terrapin_tree_validate_range(
+    tree: *const TerrapinTree,
+    data_path: *const c_char,
+    offset: u64,
+    len: u64,
+) -> TerrapinStatus {
+    guard(|| {
+        non_null(tree, "tree")?;
+        let data_path = str_arg(data_path, "data_path")?;
+        let end = offset.checked_add(len).ok_or_else(|| {
+            (
+                TerrapinStatus::OutOfRange,
+                format!("range {}+{} overflows", offset, len),
+            )
+        })?;
+        (*tree)
+            .inner
+            .validate(Path::new(data_path), Some(offset), Some(end), None)
+            .map_err(failure_of)
+    })
+}
+
+/// Write the tree's NUL-terminated identifier into `out` (`cap` bytes).
+///
+/// # Safety
+/// `tree` is a live handle; `out` is valid for `cap` bytes of writes.
+#[no_mangle]
+pub unsafe extern "C" fn terrapin_tree_identifier(
+    tree: *const TerrapinTree,
+    out: *mut c_char,
+    cap: usize,
+) -> TerrapinStatus {
+    guard(|| {
+        non_null(tree, "tree")?;
+        write_str(&(*tree).inner.identifier, out, cap)
+    })
+}
+
+/// The dataset length the tree commits to; 0 for a NULL handle.
+///
+/// # Safety
+/// `tree` is NULL or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn terrapin_tree_length(tree: *const TerrapinTree) -> u64 {
+    if tree.is_null() {
+        return 0;
+    }
+    (*tree).inner.length
+}
+
+/// Close a tree handle. NULL is ignored.
+///
+/// # Safety
+/// `tree` is NULL or a live handle, not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn terrapin_tree_close(tree: *mut TerrapinTree) {
+    if !tree.is_null() {
+        drop(Box::from_raw(tree));
+    }
+}
+
+/// Parse a canonical root manifest under `profile` (NULL for
+/// `terrapin-sha256`): the dataset length into `*length` and the 64-hex tree
+/// root, NUL-terminated, into `tree_hex` (`cap` >= 65 bytes).
+///
+/// # Safety
+/// `manifest` is valid for `len` bytes of reads; `length` for a write;
+/// `tree_hex` for `cap` bytes of writes; `profile` is NULL or NUL-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn terrapin_manifest_parse(
+    profile: *const c_char,
+    manifest: *const u8,
+    len: usize,
+    length: *mut u64,
+    tree_hex: *mut c_char,
+    cap: usize,
+) -> TerrapinStatus {
+    guard(|| {
+        non_null(manifest, "manifest")?;
+        non_null(length, "length")?;
+        let profile = profile_arg(profile)?;
+        let bytes = slice::from_raw_parts(manifest, len);
+        let (n, tree) = parse_manifest_with(profile, bytes).map_err(failure_of)?;
+        write_str(&tree, tree_hex, cap)?;
+        *length = n;
+        Ok(())
+    })
+}
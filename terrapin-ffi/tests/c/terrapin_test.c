@@ -0,0 +1,145 @@
+/*
+ * C-side test of the terrapin C ABI, driven by tests/c_abi_it.rs.
+ *
+ * usage: terrapin_test <data> <tampered> <tree base> <manifest> <identifier> <tree hex>
+ *
+ * Prints every failed check and exits non-zero if there was any.
+ */
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "terrapin.h"
+
+static int failures = 0;
+
+#define CHECK(cond)                                                          \
+    do {                                                                     \
+        if (!(cond)) {                                                       \
+            fprintf(stderr, "%s:%d: check failed: %s (last error: %s)\n",   \
+                    __FILE__, __LINE__, #cond, terrapin_last_error());       \
+            failures++;                                                      \
+        }                                                                    \
+    } while (0)
+
+static unsigned char *slurp(const char *path, size_t *len) {
+    FILE *f = fopen(path, "rb");
+    if (!f) {
+        perror(path);
+        exit(2);
+    }
+    fseek(f, 0, SEEK_END);
+    long n = ftell(f);
+    fseek(f, 0, SEEK_SET);
+    unsigned char *buf = malloc(n > 0 ? (size_t)n : 1);
+    if (n > 0 && fread(buf, 1, (size_t)n, f) != (size_t)n) {
+        perror(path);
+        exit(2);
+    }
+    fclose(f);
+    *len = (size_t)n;
+    return buf;
+}
+
+static void test_hasher(const char *data_path, const char *want) {
+    size_t len;
+    unsigned char *data = slurp(data_path, &len);
+    char id[TERRAPIN_IDENTIFIER_BUF_LEN];
+    char small[16];
+    TerrapinHasher *h = NULL;
+
+    CHECK(terrapin_hasher_new(NULL, &h) == TERRAPIN_STATUS_OK);
+    for (size_t off = 0; off < len; off += 1000003) {
+        size_t n = len - off < 1000003 ? len - off : 1000003;
+        CHECK(terrapin_hasher_update(h, data + off, n) == TERRAPIN_STATUS_OK);
+    }
+    CHECK(terrapin_hasher_update(h, NULL, 0) == TERRAPIN_STATUS_OK);
+    /* Too small: reported, and the hasher survives for a second attempt. */
+    CHECK(terrapin_hasher_finalize(h, small, sizeof small) == TERRAPIN_STATUS_BUFFER_TOO_SMALL);
+    CHECK(terrapin_hasher_finalize(h, id, sizeof id) == TERRAPIN_STATUS_OK);
+    CHECK(strcmp(id, want) == 0);
+
+    CHECK(terrapin_hasher_new("terrapin-nope", &h) == TERRAPIN_STATUS_UNKNOWN_PROFILE);
+    CHECK(strstr(terrapin_last_error(), "terrapin-nope") != NULL);
+    CHECK(terrapin_hasher_new(NULL, NULL) == TERRAPIN_STATUS_NULL_ARGUMENT);
+
+    CHECK(terrapin_hasher_new("terrapin-blake3", &h) == TERRAPIN_STATUS_OK);
+    CHECK(terrapin_hasher_update(h, data, len) == TERRAPIN_STATUS_OK);
+    CHECK(terrapin_hasher_finalize(h, id, sizeof id) == TERRAPIN_STATUS_OK);
+    CHECK(strncmp(id, "terrapin-blake3:", 16) == 0);
+
+    CHECK(terrapin_hasher_new(NULL, &h) == TERRAPIN_STATUS_OK);
+    terrapin_hasher_free(h);
+    terrapin_hasher_free(NULL);
+    free(data);
+}
+
+static void test_tree(const char *data_path, const char *tampered, const char *base,
+                      const char *want) {
+    char id[TERRAPIN_IDENTIFIER_BUF_LEN];
+    TerrapinTree *t = NULL;
+
+    CHECK(terrapin_tree_open("/nonexistent/terrapin", &t) == TERRAPIN_STATUS_IO);
+    CHECK(terrapin_tree_open(base, &t) == TERRAPIN_STATUS_OK);
+    CHECK(terrapin_tree_identifier(t, id, sizeof id) == TERRAPIN_STATUS_OK);
+    CHECK(strcmp(id, want) == 0);
+
+    uint64_t len = terrapin_tree_length(t);
+    CHECK(len > 2097152);
+    CHECK(terrapin_tree_validate_range(t, data_path, 0, len) == TERRAPIN_STATUS_OK);
+    CHECK(terrapin_tree_validate_range(t, data_path, 2097150, 10) == TERRAPIN_STATUS_OK);
+    CHECK(terrapin_tree_validate_range(t, data_path, len, 0) == TERRAPIN_STATUS_OK);
+    CHECK(terrapin_tree_validate_range(t, data_path, len - 1, 2) == TERRAPIN_STATUS_OUT_OF_RANGE);
+    CHECK(terrapin_tree_validate_range(t, data_path, 1, UINT64_MAX) == TERRAPIN_STATUS_OUT_OF_RANGE);
+    CHECK(terrapin_tree_validate_range(t, "/nonexistent/data", 0, 1) == TERRAPIN_STATUS_IO);
+
+    /* The tampered copy differs only in its first block. */
+    CHECK(terrapin_tree_validate_range(t, tampered, 0, 1) == TERRAPIN_STATUS_VALIDATION);
+    CHECK(terrapin_tree_validate_range(t, tampered, len - 1, 1) == TERRAPIN_STATUS_OK);
+    CHECK(strlen(terrapin_last_error()) > 0);
+
+    terrapin_tree_close(t);
+    terrapin_tree_close(NULL);
+}
+
+static void test_manifest(const char *manifest_path, const char *want_tree) {
+    size_t len;
+    unsigned char *m = slurp(manifest_path, &len);
+    uint64_t length = 0;
+    char tree[65];
+
+    CHECK(terrapin_manifest_parse(NULL, m, len, &length, tree, sizeof tree) == TERRAPIN_STATUS_OK);
+    CHECK(strcmp(tree, want_tree) == 0);
+    CHECK(length > 2097152);
+    CHECK(terrapin_manifest_parse(NULL, m, len, &length, tree, 64) ==
+          TERRAPIN_STATUS_BUFFER_TOO_SMALL);
+    CHECK(terrapin_manifest_parse("terrapin-blake3", m, len, &length, tree, sizeof tree) ==
+          TERRAPIN_STATUS_MALFORMED);
+
+    m[len - 1] = ' '; /* drop the final LF */
+    CHECK(terrapin_manifest_parse(NULL, m, len, &length, tree, sizeof tree) ==
+          TERRAPIN_STATUS_MALFORMED);
+    CHECK(strstr(terrapin_last_error(), "manifest") != NULL);
+    free(m);
+}
+
+int main(int argc, char **argv) {
+    if (argc != 7) {
+        fprintf(stderr, "usage: %s data tampered base manifest identifier tree\n", argv[0]);
+        return 2;
+    }
+    CHECK(terrapin_abi_version() == TERRAPIN_ABI_VERSION);
+    CHECK(strcmp(terrapin_status_str(TERRAPIN_STATUS_VALIDATION), "validation failed") == 0);
+    CHECK(strcmp(terrapin_status_str(TERRAPIN_STATUS_OTHER), "error") == 0);
+
+    test_hasher(argv[1], argv[5]);
+    test_tree(argv[1], argv[2], argv[3], argv[5]);
+    test_manifest(argv[4], argv[6]);
+
+    if (failures) {
+        fprintf(stderr, "%d check(s) failed\n", failures);
+        return 1;
+    }
+    printf("ok\n");
+    return 0;
+}
@@ -0,0 +1,95 @@
+//! The C ABI as a C program sees it: `tests/c/terrapin_test.c` is compiled
+//! against `include/terrapin.h` and the static library, then run on a dataset
+//! and tree prepared here with the `terrapin` crate.
+//!
+//! The compiler is `$CC`, else `cc`. The C program checks return codes and
+//! outputs itself and exits non-zero if any check failed.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use terrapin::{build_from_reader_sync, manifest_bytes, PersistedTree, BLOCK};
+
+/// The static library cargo built alongside this test: in `deps/` next to the
+/// test binary, or uplifted to `target/<profile>`.
+fn static_lib() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    let deps = exe.parent().unwrap();
+    [deps, deps.parent().unwrap()]
+        .iter()
+        .map(|d| d.join("libterrapin_ffi.a"))
+        .find(|p| p.exists())
+        .expect("libterrapin_ffi.a not built")
+}
+
+fn compile(out: &Path) {
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let lib = static_lib();
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(cc)
+        .args(["-std=c99", "-Wall", "-Wextra", "-Werror", "-I"])
+        .arg(crate_dir.join("include"))
+        .arg(crate_dir.join("tests/c/terrapin_test.c"))
+        .arg(&lib)
+        .args(["-lpthread", "-ldl", "-lm", "-o"])
+        .arg(out)
+        .status()
+        .expect("failed to run the C compiler");
+    assert!(status.success(), "compiling terrapin_test.c failed");
+}
+
+fn data(len: usize) -> Vec<u8> {
+    let mut x = 0x9E3779B97F4A7C15u64;
+    (0..len)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x as u8
+        })
+        .collect()
+}
+
+// Verifies: REQ-FFI-001
+// Verifies: REQ-FFI-002
+// Verifies: REQ-FFI-003
+// Verifies: REQ-FFI-004
+#[test]
+fn c_program_drives_the_abi() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("terrapin-ffi-c");
+    fs::create_dir_all(&dir).unwrap();
+    let exe = dir.join("terrapin_test");
+    compile(&exe);
+
+    let bytes = data(2 * BLOCK + 4321);
+    let data_path = dir.join("data");
+    fs::write(&data_path, &bytes).unwrap();
+    let mut tampered = bytes.clone();
+    tampered[7] ^= 1;
+    let tampered_path = dir.join("tampered");
+    fs::write(&tampered_path, &tampered).unwrap();
+
+    let bt = build_from_reader_sync(&bytes[..]).unwrap();
+    let base = dir.join("tree");
+    PersistedTree::write(&base, &bt).unwrap();
+    let manifest_path = dir.join("manifest");
+    fs::write(&manifest_path, manifest_bytes(bt.length, &bt.tree_hex())).unwrap();
+
+    let out = Command::new(&exe)
+        .arg(&data_path)
+        .arg(&tampered_path)
+        .arg(&base)
+        .arg(&manifest_path)
+        .arg(bt.identifier())
+        .arg(bt.tree_hex())
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}{}",
+        String::from_utf8_lossy(&out.stdout),
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "ok\n");
+}
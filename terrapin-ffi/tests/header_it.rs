@@ -0,0 +1,31 @@
+//! `include/terrapin.h` must be exactly what cbindgen generates from
+//! `src/lib.rs` with `cbindgen.toml`. Set `TERRAPIN_FFI_BLESS=1` to rewrite it
+//! after changing the ABI.
+
+use std::fs;
+use std::path::Path;
+
+// Verifies: REQ-FFI-005
+#[test]
+fn header_matches_generated() {
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).unwrap();
+    let mut generated = Vec::new();
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(crate_dir.join("src/lib.rs"))
+        .generate()
+        .expect("cbindgen failed")
+        .write(&mut generated);
+
+    let path = crate_dir.join("include/terrapin.h");
+    if std::env::var_os("TERRAPIN_FFI_BLESS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &generated).unwrap();
+    }
+    let committed = fs::read(&path).unwrap_or_default();
+    assert!(
+        committed == generated,
+        "include/terrapin.h is stale; rerun with TERRAPIN_FFI_BLESS=1"
+    );
+}
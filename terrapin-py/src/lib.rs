@@ -0,0 +1,174 @@
+//! Python bindings for Terrapin (`import terrapin`).
+//!
+//! * `identifier(data, profile=...)` — identifier of a `bytes`-like object.
+//! * `identifier_from_path(path, profile=...)` — streams a file through the
+//!   parallel builder.
+//! * `attest(path, out, profile=...)` — writes `<out>.head` / `<out>.blocks`
+//!   and returns the identifier.
+//! * `PersistedTree(base)` — an attested tree: `validate(path, start, end)`
+//!   and `read_range(path, start, end)`, which returns only verified bytes.
+//!
+//! All file I/O, hashing and validation runs with the GIL released.
+//! Hashing a file raises `OSError` when it cannot be read; everything else
+//! raises `terrapin.TerrapinError` (a `ValueError`).
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyOSError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyInt};
+
+use terrapin::{
+    build_from_file_sync_with, build_from_reader_sync_with, profile_by_name, Error, PersistedTree,
+    Profile, BLOCK,
+};
+
+create_exception!(
+    terrapin,
+    TerrapinError,
+    PyValueError,
+    "A malformed tree or manifest, an out-of-range request, or data that does not verify."
+);
+
+fn profile(name: &str) -> PyResult<&'static dyn Profile> {
+    profile_by_name(name).ok_or_else(|| TerrapinError::new_err(format!("unknown profile {}", name)))
+}
+
+fn terrapin_error(e: Error) -> PyErr {
+    TerrapinError::new_err(e.to_string())
+}
+
+fn os_error(path: &Path, e: std::io::Error) -> PyErr {
+    PyOSError::new_err(format!("{}: {}", path.display(), e))
+}
+
+/// The identifier of `data`: `bytes` (hashed in place) or any object
+/// supporting the buffer protocol (copied first).
+#[pyfunction]
+#[pyo3(signature = (data, profile = "terrapin-sha256"))]
+fn identifier(py: Python<'_>, data: &Bound<'_, PyAny>, profile: &str) -> PyResult<String> {
+    let p = self::profile(profile)?;
+    let bytes = match data.downcast::<PyBytes>() {
+        Ok(b) => b.clone(),
+        Err(_) if data.is_instance_of::<PyInt>() => {
+            return Err(PyTypeError::new_err("expected a bytes-like object"))
+        }
+        Err(_) => py.get_type::<PyBytes>().call1((data,))?.downcast_into()?,
+    };
+    let data = bytes.as_bytes();
+    py.allow_threads(|| build_from_reader_sync_with(p, data))
+        .map(|t| t.identifier())
+        .map_err(|e| TerrapinError::new_err(e.to_string()))
+}
+
+/// The identifier of the file at `path`, hashed in parallel without holding
+/// it in memory.
+#[pyfunction]
+#[pyo3(signature = (path, profile = "terrapin-sha256"))]
+fn identifier_from_path(py: Python<'_>, path: PathBuf, profile: &str) -> PyResult<String> {
+    let p = self::profile(profile)?;
+    py.allow_threads(|| build_from_file_sync_with(p, File::open(&path)?))
+        .map(|t| t.identifier())
+        .map_err(|e| os_error(&path, e))
+}
+
+/// Build the tree of the file at `path`, write it as `<out>.head` /
+/// `<out>.blocks`, and return the identifier.
+#[pyfunction]
+#[pyo3(signature = (path, out, profile = "terrapin-sha256"))]
+fn attest(py: Python<'_>, path: PathBuf, out: PathBuf, profile: &str) -> PyResult<String> {
+    let p = self::profile(profile)?;
+    let tree = py
+        .allow_threads(|| build_from_file_sync_with(p, File::open(&path)?))
+        .map_err(|e| os_error(&path, e))?;
+    py.allow_threads(|| PersistedTree::write(&out, &tree))
+        .map_err(|e| os_error(&out, e))?;
+    Ok(tree.identifier())
+}
+
+/// A persisted tree, opened from `<base>.head` / `<base>.blocks`.
+#[pyclass(name = "PersistedTree", module = "terrapin", frozen)]
+struct PyPersistedTree {
+    inner: PersistedTree,
+}
+
+#[pymethods]
+impl PyPersistedTree {
+    #[new]
+    fn new(base: PathBuf) -> PyResult<Self> {
+        let inner = PersistedTree::read(&base).map_err(terrapin_error)?;
+        Ok(PyPersistedTree { inner })
+    }
+
+    #[getter]
+    fn identifier(&self) -> &str {
+        &self.inner.identifier
+    }
+
+    #[getter]
+    fn length(&self) -> u64 {
+        self.inner.length
+    }
+
+    #[getter]
+    fn tree(&self) -> &str {
+        &self.inner.tree_hex
+    }
+
+    /// Raise unless this tree's identifier is `trusted`.
+    fn check_against(&self, trusted: &str) -> PyResult<()> {
+        self.inner.check_against(trusted).map_err(terrapin_error)
+    }
+
+    /// Validate bytes `[start, end)` of the data file at `path` (the whole
+    /// file by default); raises `TerrapinError` if they do not verify.
+    #[pyo3(signature = (path, start = None, end = None))]
+    fn validate(
+        &self,
+        py: Python<'_>,
+        path: PathBuf,
+        start: Option<u64>,
+        end: Option<u64>,
+    ) -> PyResult<()> {
+        py.allow_threads(|| self.inner.validate(&path, start, end, None))
+            .map_err(terrapin_error)
+    }
+
+    /// Bytes `[start, end)` of the data file at `path`, returned only once
+    /// every block they touch has verified.
+    #[pyo3(signature = (path, start = None, end = None))]
+    fn read_range<'py>(
+        &self,
+        py: Python<'py>,
+        path: PathBuf,
+        start: Option<u64>,
+        end: Option<u64>,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let mut out = Vec::new();
+        py.allow_threads(|| self.inner.validate(&path, start, end, Some(&mut out)))
+            .map_err(terrapin_error)?;
+        Ok(PyBytes::new(py, &out))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "PersistedTree(identifier={:?}, length={})",
+            self.inner.identifier, self.inner.length
+        )
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "terrapin")]
+fn terrapin_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    m.add("BLOCK", BLOCK)?;
+    m.add("TerrapinError", m.py().get_type::<TerrapinError>())?;
+    m.add_function(wrap_pyfunction!(identifier, m)?)?;
+    m.add_function(wrap_pyfunction!(identifier_from_path, m)?)?;
+    m.add_function(wrap_pyfunction!(attest, m)?)?;
+    m.add_class::<PyPersistedTree>()?;
+    Ok(())
+}
@@ -0,0 +1,142 @@
+"""pytest suite for the terrapin Python bindings.
+
+Run from terrapin-py/ after `maturin develop`:  python -m pytest
+
+Identifiers are checked against the Rust golden vectors
+(terrapin/tests/vectors-terrapin.json); the 128 GiB zero vectors are skipped.
+"""
+
+import json
+import os
+import threading
+
+import pytest
+
+import terrapin
+
+VECTORS = os.path.join(
+    os.path.dirname(__file__), "..", "..", "terrapin", "tests", "vectors-terrapin.json"
+)
+BLOCK = terrapin.BLOCK
+PREFIX = "terrapin-sha256:"
+
+
+def vectors():
+    with open(VECTORS) as f:
+        return [v for v in json.load(f) if v.get("length", 0) <= 4 * BLOCK]
+
+
+def vector_data(v):
+    if v["kind"] == "input":
+        return v["input"].encode()
+    return bytes(v["length"])
+
+
+def pattern(n, seed=1):
+    """Deterministic non-trivial bytes (period 251, so blocks differ)."""
+    cycle = bytes((i * 31 + seed) % 256 for i in range(251))
+    return (cycle * (n // 251 + 1))[:n]
+
+
+@pytest.fixture
+def dataset(tmp_path):
+    data = pattern(2 * BLOCK + 4321)
+    path = tmp_path / "data"
+    path.write_bytes(data)
+    return path, data
+
+
+# Verifies: REQ-PY-001
+@pytest.mark.parametrize("v", vectors(), ids=lambda v: v["name"])
+def test_identifier_matches_golden_vectors(v):
+    assert terrapin.identifier(vector_data(v)) == PREFIX + v["id"]
+
+
+# Verifies: REQ-PY-001
+@pytest.mark.parametrize("v", vectors(), ids=lambda v: v["name"])
+def test_identifier_from_path_matches_golden_vectors(v, tmp_path):
+    path = tmp_path / "data"
+    path.write_bytes(vector_data(v))
+    assert terrapin.identifier_from_path(path) == PREFIX + v["id"]
+    assert terrapin.identifier_from_path(str(path)) == PREFIX + v["id"]
+
+
+# Verifies: REQ-PY-001
+def test_identifier_accepts_buffers_and_profiles():
+    data = pattern(BLOCK + 1)
+    want = terrapin.identifier(data)
+    assert terrapin.identifier(bytearray(data)) == want
+    assert terrapin.identifier(memoryview(data)) == want
+    assert terrapin.identifier(data, profile="terrapin-blake3").startswith("terrapin-blake3:")
+    with pytest.raises(terrapin.TerrapinError):
+        terrapin.identifier(data, profile="terrapin-nope")
+    with pytest.raises(OSError):
+        terrapin.identifier_from_path("/nonexistent/terrapin-data")
+
+
+# Verifies: REQ-PY-002
+@pytest.mark.skipif(not hasattr(os, "mkfifo"), reason="needs named pipes")
+def test_hashing_releases_the_gil(tmp_path):
+    # Only this thread feeds the pipe the hash reads from: if
+    # identifier_from_path held the GIL while reading, neither side could
+    # make progress and this test would hang.
+    fifo = tmp_path / "fifo"
+    os.mkfifo(fifo)
+    data = pattern(BLOCK + 99)
+    result = []
+    t = threading.Thread(target=lambda: result.append(terrapin.identifier_from_path(fifo)))
+    t.start()
+    with open(fifo, "wb") as f:
+        for i in range(0, len(data), 65536):
+            f.write(data[i : i + 65536])
+    t.join()
+    assert result == [terrapin.identifier(data)]
+
+
+# Verifies: REQ-PY-003
+def test_attest_then_validate_and_read_range(dataset, tmp_path):
+    path, data = dataset
+    base = tmp_path / "tree"
+    ident = terrapin.attest(path, base)
+    assert ident == terrapin.identifier(data)
+    assert (tmp_path / "tree.head").exists() and (tmp_path / "tree.blocks").exists()
+
+    tree = terrapin.PersistedTree(base)
+    assert tree.identifier == ident
+    assert tree.length == len(data)
+    assert len(tree.tree) == 64
+    tree.check_against(ident)
+    with pytest.raises(terrapin.TerrapinError):
+        tree.check_against(PREFIX + "00" * 32)
+
+    tree.validate(path)
+    tree.validate(path, BLOCK - 5, BLOCK + 5)
+    assert tree.read_range(path) == data
+    assert tree.read_range(path, BLOCK - 5, BLOCK + 5) == data[BLOCK - 5 : BLOCK + 5]
+    assert tree.read_range(path, 10, 10) == b""
+
+
+# Verifies: REQ-PY-004
+def test_tampered_data_and_bad_ranges_raise(dataset, tmp_path):
+    path, data = dataset
+    base = tmp_path / "tree"
+    terrapin.attest(path, base)
+    tree = terrapin.PersistedTree(base)
+
+    tampered = bytearray(data)
+    tampered[BLOCK + 7] ^= 1
+    bad = tmp_path / "tampered"
+    bad.write_bytes(bytes(tampered))
+
+    with pytest.raises(terrapin.TerrapinError, match="validation failed"):
+        tree.validate(bad)
+    with pytest.raises(terrapin.TerrapinError):
+        tree.read_range(bad, BLOCK, BLOCK + 10)
+    # Blocks the range does not touch are not read.
+    assert tree.read_range(bad, 0, 10) == data[:10]
+
+    with pytest.raises(terrapin.TerrapinError, match="out of bounds"):
+        tree.validate(path, 0, len(data) + 1)
+    with pytest.raises(terrapin.TerrapinError):
+        terrapin.PersistedTree(tmp_path / "missing")
+    assert issubclass(terrapin.TerrapinError, ValueError)
@@ -1,9 +1,9 @@
-// Throughput bench for Terrapin (gitoid boringssl backend).
-// Run: cargo run -p terrapin --example bench --release
+// Throughput bench for Terrapin's SHA-256 backend.
+// Run: cargo run -p terrapin --example bench --release [--features ring|boringssl]
 use std::hint::black_box;
 use std::io::Cursor;
 use std::time::Instant;
-use terrapin::{build_from_reader, g, tree_root, BLOCK};
+use terrapin::{build_from_reader_sync, g, sha256_backend, tree_root, BLOCK};
 
 fn bench<F: Fn()>(name: &str, bytes: usize, iters: usize, f: F) {
     f(); // warmup
@@ -23,6 +23,7 @@ fn bench<F: Fn()>(name: &str, bytes: usize, iters: usize, f: F) {
 }
 
 fn main() {
+    println!("sha256 backend: {}", sha256_backend());
     let block = vec![0x5au8; BLOCK]; // 2 MiB
     bench("G 2MiB", block.len(), 3000, || {
         black_box(g(black_box(&block)));
@@ -34,11 +35,19 @@ fn main() {
     });
 
     // Streaming + parallel construction over the same 32 MiB object.
-    let rt = tokio::runtime::Runtime::new().unwrap();
-    bench("build_from_reader 32MiB (par)", obj.len(), 200, || {
-        let t = rt
-            .block_on(build_from_reader(Cursor::new(obj.clone())))
-            .unwrap();
+    bench("build_from_reader_sync 32MiB (par)", obj.len(), 200, || {
+        let t = build_from_reader_sync(Cursor::new(obj.clone())).unwrap();
         black_box(t.root);
     });
+
+    #[cfg(feature = "tokio")]
+    {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        bench("build_from_reader 32MiB (par)", obj.len(), 200, || {
+            let t = rt
+                .block_on(terrapin::build_from_reader(Cursor::new(obj.clone())))
+                .unwrap();
+            black_box(t.root);
+        });
+    }
 }
@@ -7,16 +7,26 @@
 //! reference [`crate::tree_root`] by construction — including the FANOUT-power
 //! boundaries (e.g. exactly 65536 leaves produce a single wrap, not two).
 //!
+//! Upper layers are reduced eagerly: each time a layer completes a full
+//! FANOUT-hash group, its `g` is computed on the spot and kept as the next
+//! layer's prefix. [`TreeBuilder::build`] then only hashes each layer's short
+//! trailing group and the final wrap, so it returns almost immediately after
+//! the last leaf instead of re-reading the whole leaf layer.
+//!
 //! Memory is `O(dataset_len / FANOUT)` (the size of the leaf hash file), never
 //! the dataset itself.
 
-use crate::manifest::{g, identifier_from_parts, to_hex, BLOCK};
+use terrapin_core::{identifier_from_parts_with, to_hex, Profile, BLOCK, SHA256};
 
 /// Accumulates leaf hashes and builds the recursive tree.
-#[derive(Default)]
 pub struct TreeBuilder {
     /// Concatenated 32-byte leaf hashes (the layer-0 hash file).
     leaves: Vec<u8>,
+    /// `upper[k]`: `g` of every full BLOCK-byte group of layer `k` so far,
+    /// i.e. the complete-group prefix of layer `k + 1`.
+    upper: Vec<Vec<u8>>,
+    /// Hash profile for the upper layers and the identifier.
+    profile: &'static dyn Profile,
 }
 
 /// A fully built tree: every layer's hash file plus the derived root.
@@ -28,16 +38,51 @@ pub struct BuiltTree {
     pub layers: Vec<Vec<u8>>,
     /// The recursive tree root `T(dataset)`.
     pub root: [u8; 32],
+    /// The profile the tree was built with.
+    pub profile: &'static dyn Profile,
+}
+
+impl Default for TreeBuilder {
+    fn default() -> Self {
+        TreeBuilder::new()
+    }
 }
 
 impl TreeBuilder {
     pub fn new() -> Self {
-        TreeBuilder { leaves: Vec::new() }
+        TreeBuilder::with_profile(SHA256)
+    }
+
+    /// A builder for `profile`; leaves pushed must be that profile's `G`.
+    pub fn with_profile(profile: &'static dyn Profile) -> Self {
+        TreeBuilder {
+            leaves: Vec::new(),
+            upper: Vec::new(),
+            profile,
+        }
     }
 
     /// Append one leaf hash (`g` of a data block), in block order.
     pub fn push_leaf(&mut self, h: &[u8; 32]) {
         self.leaves.extend_from_slice(h);
+        if self.leaves.len().is_multiple_of(BLOCK) {
+            let group = self.profile.g(&self.leaves[self.leaves.len() - BLOCK..]);
+            self.push_upper(0, group);
+        }
+    }
+
+    /// Record the hash of a just-completed group of layer `k`, cascading when
+    /// that completes a group of layer `k + 1`.
+    fn push_upper(&mut self, k: usize, h: [u8; 32]) {
+        if self.upper.len() == k {
+            self.upper.push(Vec::new());
+        }
+        let layer = &mut self.upper[k];
+        layer.extend_from_slice(&h);
+        if layer.len().is_multiple_of(BLOCK) {
+            let group = self.profile.g(&layer[layer.len() - BLOCK..]);
+            self.push_upper(k + 1, group);
+        }
     }
 
     /// Number of leaf hashes pushed so far.
@@ -48,7 +93,7 @@ impl TreeBuilder {
     /// Finish the tree for a dataset of `length` bytes.
     ///
     /// Requires at least one leaf (an empty dataset is one empty leaf, `g("")`).
-    pub fn build(self, length: u64) -> BuiltTree {
+    pub fn build(mut self, length: u64) -> BuiltTree {
         let mut layers: Vec<Vec<u8>> = vec![self.leaves];
         debug_assert!(!layers[0].is_empty(), "at least one leaf is required");
 
@@ -60,14 +105,28 @@ impl TreeBuilder {
         } else {
             let mut cur = 0;
             loop {
-                if layers[cur].len() <= BLOCK {
+                let len = layers[cur].len();
+                // g of the leading full groups of this layer, hashed as they
+                // completed; a group completed only by the previous layer's
+                // tail is not among them.
+                let mut next = self
+                    .upper
+                    .get_mut(cur)
+                    .map(std::mem::take)
+                    .unwrap_or_default();
+                if len <= BLOCK {
                     // <= FANOUT hashes left: one final wrap is the root.
-                    root = g(&layers[cur]);
+                    root = match next.get(..32) {
+                        Some(h) => h.try_into().unwrap(),
+                        None => self.profile.g(&layers[cur]),
+                    };
                     break;
                 }
                 // Split into FANOUT-hash (BLOCK-byte) groups, last may be short,
-                // and g each group to form the next layer.
-                let next: Vec<u8> = layers[cur].chunks(BLOCK).flat_map(g).collect();
+                // and g each group not yet hashed to form the next layer.
+                let done = next.len() / 32 * BLOCK;
+                let rest: Vec<&[u8]> = layers[cur][done..].chunks(BLOCK).collect();
+                next.extend(self.profile.g_many(&rest).concat());
                 layers.push(next);
                 cur += 1;
             }
@@ -77,14 +136,16 @@ impl TreeBuilder {
             length,
             layers,
             root,
+            profile: self.profile,
         }
     }
 }
 
 impl BuiltTree {
-    /// The `terrapin-sha256:<hex>` identifier (spec section 5.3).
+    /// The `<profile>:<hex>` identifier, e.g. `terrapin-sha256:<hex>` (spec
+    /// section 5.3).
     pub fn identifier(&self) -> String {
-        identifier_from_parts(self.length, &self.root)
+        identifier_from_parts_with(self.profile, self.length, &self.root)
     }
 
     /// The tree root as 64 lowercase hex.
@@ -96,7 +157,7 @@ impl BuiltTree {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::manifest::{tree_root, FANOUT};
+    use terrapin_core::{g, tree_root, FANOUT};
 
     /// Build a tree from raw bytes (in-memory), mirroring how the streaming
     /// path feeds leaves, and return the root.
@@ -0,0 +1,384 @@
+//! Opt-in identifier cache, so re-identifying an unchanged file skips hashing
+//! it.
+//!
+//! An entry records a file's identifier, and optionally where its tree was
+//! written, against the file's [`FileKey`]. A lookup stats the file again and
+//! returns the entry only if every recorded field still matches. Entries are
+//! kept per hash profile, in one of:
+//!
+//! * [`IdCache::Db`] — a local text database, one line per file and profile,
+//!   keyed on device, inode, size, mtime and ctime: any metadata change (a
+//!   write, truncation, replacement, `touch` or `chmod`) invalidates it. It
+//!   works on read-only files and filesystems without user attributes.
+//!   Writers hold an exclusive lock on `<db>.lock` while they read, update
+//!   and replace it, so concurrent runs keep each other's entries; readers
+//!   take no lock, since the database is only ever replaced by a rename.
+//! * [`IdCache::Xattr`] — the file's own `user.terrapin.<profile>` extended
+//!   attribute (Linux). Setting an attribute updates the file's ctime, so an
+//!   attribute entry cannot record the ctime it leaves behind and is keyed on
+//!   device, inode, size and mtime only: a `chmod`, or a same-size rewrite
+//!   that restores the mtime, still hits. Use the database where that
+//!   matters.
+//!
+//! An entry is one line: `<dev> <ino> <size> <mtime> <ctime> <identifier>`,
+//! then ` <tree>` if a tree path is recorded. Times are `<secs>.<nanos>`; the
+//! attribute form writes `-` for ctime. The cache is a hint: callers that need
+//! certainty rehash and compare (`terrapin id --verify-cache`). File keys need
+//! Unix metadata; elsewhere every operation fails.
+
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::Error;
+
+static STAGING: AtomicU64 = AtomicU64::new(0);
+
+/// The file metadata a cache entry is valid for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileKey {
+    pub dev: u64,
+    pub ino: u64,
+    pub size: u64,
+    /// Modification time, `(seconds, nanoseconds)`.
+    pub mtime: (i64, i64),
+    /// Status-change time, `(seconds, nanoseconds)`; `None` in an attribute
+    /// entry, which does not check it.
+    pub ctime: Option<(i64, i64)>,
+}
+
+impl FileKey {
+    /// The current key of the file at `path` (symlinks followed).
+    #[cfg(unix)]
+    pub fn of(path: &Path) -> Result<FileKey, Error> {
+        let m =
+            fs::metadata(path).map_err(|e| Error::io(format!("stat {}: {}", path.display(), e)))?;
+        Ok(FileKey {
+            dev: m.dev(),
+            ino: m.ino(),
+            size: m.size(),
+            mtime: (m.mtime(), m.mtime_nsec()),
+            ctime: Some((m.ctime(), m.ctime_nsec())),
+        })
+    }
+
+    /// The current key of the file at `path` (symlinks followed).
+    #[cfg(not(unix))]
+    pub fn of(_path: &Path) -> Result<FileKey, Error> {
+        Err(Error::other("cache: supported on Unix only"))
+    }
+
+    /// Whether an entry recorded under `self` is valid for a file now at
+    /// `now`. A recorded ctime of `None` is not compared.
+    pub fn matches(&self, now: &FileKey) -> bool {
+        self.dev == now.dev
+            && self.ino == now.ino
+            && self.size == now.size
+            && self.mtime == now.mtime
+            && self.ctime.is_none_or(|c| now.ctime == Some(c))
+    }
+}
+
+/// What the cache knows about a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CacheEntry {
+    pub identifier: String,
+    /// Base name of a tree written for the file, e.g. by `attest`.
+    pub tree: Option<PathBuf>,
+}
+
+/// Where cache entries live (see the module docs).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IdCache {
+    /// The file's `user.terrapin.<profile>` extended attribute; does not
+    /// check ctime.
+    Xattr,
+    /// A cache database file.
+    Db(PathBuf),
+}
+
+impl IdCache {
+    /// The default database path: `$XDG_CACHE_HOME/terrapin/ids`, else
+    /// `$HOME/.cache/terrapin/ids`.
+    pub fn default_db() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CACHE_HOME")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))?;
+        Some(base.join("terrapin").join("ids"))
+    }
+
+    /// The `profile` entry for `path`, if there is one and the file has not
+    /// changed since it was recorded.
+    pub fn get(&self, path: &Path, profile: &str) -> Result<Option<CacheEntry>, Error> {
+        let now = FileKey::of(path)?;
+        let found = match self {
+            IdCache::Xattr => xattr_get(path, &attr_name(profile))?
+                .as_deref()
+                .and_then(parse_line),
+            IdCache::Db(db) => read_db(db)?.into_iter().find(|(key, entry)| {
+                key.dev == now.dev && key.ino == now.ino && profile_of(entry) == profile
+            }),
+        };
+        Ok(found
+            .filter(|(key, entry)| key.matches(&now) && profile_of(entry) == profile)
+            .map(|(_, entry)| entry))
+    }
+
+    /// Record `entry` for `path`, whose key was `before` when hashing started.
+    /// Nothing is recorded (and `false` returned) if the file has changed
+    /// since, so an entry never describes content it was not computed from.
+    pub fn put(&self, path: &Path, before: &FileKey, entry: &CacheEntry) -> Result<bool, Error> {
+        let now = FileKey::of(path)?;
+        if !before.matches(&now) {
+            return Ok(false);
+        }
+        if entry.identifier.contains([' ', '\n']) || !entry.identifier.contains(':') {
+            return Err(Error::other(format!(
+                "cache: bad identifier {:?}",
+                entry.identifier
+            )));
+        }
+        let profile = profile_of(entry);
+        match self {
+            IdCache::Xattr => {
+                let key = FileKey { ctime: None, ..now };
+                xattr_set(path, &attr_name(profile), &format_line(&key, entry))?;
+            }
+            IdCache::Db(db) => {
+                let _lock = lock_db(db)?;
+                let mut entries = read_db(db)?;
+                entries.retain(|(key, e)| {
+                    !(key.dev == now.dev && key.ino == now.ino && profile_of(e) == profile)
+                });
+                entries.push((now, entry.clone()));
+                write_db(db, &entries)?;
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// The profile an entry's identifier names.
+fn profile_of(entry: &CacheEntry) -> &str {
+    entry.identifier.split(':').next().unwrap_or("")
+}
+
+fn attr_name(profile: &str) -> String {
+    format!("user.terrapin.{}", profile)
+}
+
+fn format_line(key: &FileKey, entry: &CacheEntry) -> Vec<u8> {
+    let time = |(s, ns): (i64, i64)| format!("{}.{:09}", s, ns);
+    let mut line = format!(
+        "{} {} {} {} {} {}",
+        key.dev,
+        key.ino,
+        key.size,
+        time(key.mtime),
+        key.ctime.map_or("-".to_string(), time),
+        entry.identifier
+    )
+    .into_bytes();
+    // A tree path that would break the line is not recorded.
+    if let Some(tree) = entry.tree.as_ref().map(|t| path_bytes(t)) {
+        if !tree.contains(&b'\n') {
+            line.push(b' ');
+            line.extend_from_slice(&tree);
+        }
+    }
+    line
+}
+
+/// Parse one entry line; `None` if it is malformed (treated as a miss).
+fn parse_line(line: &[u8]) -> Option<(FileKey, CacheEntry)> {
+    let mut fields = line.splitn(7, |&b| b == b' ');
+    let mut next = || fields.next().and_then(|f| std::str::from_utf8(f).ok());
+    let time = |s: &str| {
+        let (secs, nanos) = s.split_once('.')?;
+        Some((secs.parse().ok()?, nanos.parse().ok()?))
+    };
+    let dev = next()?.parse().ok()?;
+    let ino = next()?.parse().ok()?;
+    let size = next()?.parse().ok()?;
+    let mtime = time(next()?)?;
+    let ctime = match next()? {
+        "-" => None,
+        c => Some(time(c)?),
+    };
+    let identifier = next()?.to_string();
+    if !identifier.contains(':') {
+        return None;
+    }
+    let tree = fields.next().map(path_from_bytes);
+    let key = FileKey {
+        dev,
+        ino,
+        size,
+        mtime,
+        ctime,
+    };
+    Some((key, CacheEntry { identifier, tree }))
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn read_db(db: &Path) -> Result<Vec<(FileKey, CacheEntry)>, Error> {
+    let raw = match fs::read(db) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(Error::io(format!("cannot read {}: {}", db.display(), e))),
+    };
+    Ok(raw
+        .split(|&b| b == b'\n')
+        .filter(|l| !l.is_empty())
+        .filter_map(parse_line)
+        .collect())
+}
+
+/// Take the exclusive writer lock `<db>.lock`, held until the file is
+/// dropped.
+fn lock_db(db: &Path) -> Result<fs::File, Error> {
+    if let Some(dir) = db.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .map_err(|e| Error::io(format!("cannot create {}: {}", dir.display(), e)))?;
+    }
+    let mut path = db.as_os_str().to_os_string();
+    path.push(".lock");
+    let path = PathBuf::from(path);
+    let f = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .map_err(|e| Error::io(format!("cannot open {}: {}", path.display(), e)))?;
+    f.lock()
+        .map_err(|e| Error::io(format!("cannot lock {}: {}", path.display(), e)))?;
+    Ok(f)
+}
+
+/// Replace the database with `entries`, via a rename so readers never see a
+/// partial file. The caller holds [`lock_db`], which created the directory.
+fn write_db(db: &Path, entries: &[(FileKey, CacheEntry)]) -> Result<(), Error> {
+    let mut text = Vec::new();
+    for (key, entry) in entries {
+        text.extend(format_line(key, entry));
+        text.push(b'\n');
+    }
+    let mut tmp = db.as_os_str().to_os_string();
+    tmp.push(format!(
+        ".tmp-{}-{}",
+        std::process::id(),
+        STAGING.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::write(&tmp, text)
+        .and_then(|_| fs::rename(&tmp, db))
+        .map_err(|e| Error::io(format!("write {}: {}", db.display(), e)))
+}
+
+#[cfg(target_os = "linux")]
+fn xattr_get(path: &Path, name: &str) -> Result<Option<Vec<u8>>, Error> {
+    use std::ffi::CString;
+    let err =
+        |e: std::io::Error| Error::io(format!("cannot read {} of {}: {}", name, path.display(), e));
+    let cpath = CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| Error::other("cache: NUL in path"))?;
+    let cname = CString::new(name).map_err(|_| Error::other("cache: NUL in name"))?;
+    loop {
+        // SAFETY: both strings are NUL-terminated; a null buffer of size 0
+        // asks for the value's size.
+        let size =
+            unsafe { libc::getxattr(cpath.as_ptr(), cname.as_ptr(), std::ptr::null_mut(), 0) };
+        if size < 0 {
+            let e = std::io::Error::last_os_error();
+            return match e.raw_os_error() {
+                Some(libc::ENODATA) | Some(libc::ENOTSUP) => Ok(None),
+                _ => Err(err(e)),
+            };
+        }
+        let mut buf = vec![0u8; size as usize];
+        // SAFETY: `buf` is valid for `buf.len()` bytes.
+        let n = unsafe {
+            libc::getxattr(
+                cpath.as_ptr(),
+                cname.as_ptr(),
+                buf.as_mut_ptr().cast(),
+                buf.len(),
+            )
+        };
+        if n >= 0 {
+            buf.truncate(n as usize);
+            return Ok(Some(buf));
+        }
+        let e = std::io::Error::last_os_error();
+        // The value grew between the two calls: ask again.
+        if e.raw_os_error() != Some(libc::ERANGE) {
+            return Err(err(e));
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn xattr_set(path: &Path, name: &str, value: &[u8]) -> Result<(), Error> {
+    use std::ffi::CString;
+    let cpath = CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| Error::other("cache: NUL in path"))?;
+    let cname = CString::new(name).map_err(|_| Error::other("cache: NUL in name"))?;
+    // SAFETY: both strings are NUL-terminated and `value` is valid for its
+    // length.
+    let r = unsafe {
+        libc::setxattr(
+            cpath.as_ptr(),
+            cname.as_ptr(),
+            value.as_ptr().cast(),
+            value.len(),
+            0,
+        )
+    };
+    if r < 0 {
+        let e = std::io::Error::last_os_error();
+        return Err(Error::io(format!(
+            "cannot write {} of {}: {}",
+            name,
+            path.display(),
+            e
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn xattr_get(_path: &Path, _name: &str) -> Result<Option<Vec<u8>>, Error> {
+    Err(Error::other(
+        "cache: extended attributes are supported on Linux only; use a cache database",
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn xattr_set(_path: &Path, _name: &str, _value: &[u8]) -> Result<(), Error> {
+    Err(Error::other(
+        "cache: extended attributes are supported on Linux only; use a cache database",
+    ))
+}
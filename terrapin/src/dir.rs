@@ -0,0 +1,335 @@
+//! Directory datasets (spec section 11).
+//!
+//! A directory is addressed by a canonical listing: one line per regular file,
+//! `<mode> <length> <identifier> <path>`, sorted by path, under a
+//! `terrapin-dir: sha256` header. Each file keeps its own Terrapin identifier
+//! and tree; the directory identifier is `terrapin-dir-sha256:` + hex of
+//! `G(listing)`. A single member is validated with its own tree, bound to the
+//! listing entry, with the listing bound to the trusted directory identifier.
+//!
+//! An attested directory `<base>` is written as `<base>.listing` plus one tree
+//! per member in its own directory, `<base>.d/<path>/tree.head` and
+//! `.../tree.blocks`. A member's directory can never be another member's tree
+//! (e.g. file `a` next to directory `a.head/`), since no path is both a file
+//! and a directory.
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use terrapin_core::{g, to_hex, Error};
+
+use crate::stream::build_from_file_sync;
+use crate::tree::{with_ext, PersistedTree};
+
+/// Prefix of a directory identifier.
+pub const DIR_PREFIX: &str = "terrapin-dir-sha256:";
+
+const HEADER: &str = "terrapin-dir: sha256\n";
+const MODE_FILE: u32 = 0o100644;
+const MODE_EXEC: u32 = 0o100755;
+/// Base name of each member's tree inside its directory under `<base>.d/`.
+const MEMBER_TREE: &str = "tree";
+
+/// One file of a directory listing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirEntry {
+    /// Relative `/`-separated path.
+    pub path: String,
+    /// `0o100644`, or `0o100755` for an executable file.
+    pub mode: u32,
+    pub length: u64,
+    /// The file's `terrapin-sha256:` identifier.
+    pub identifier: String,
+}
+
+/// A canonical directory listing: entries sorted by path, no duplicates.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirListing {
+    entries: Vec<DirEntry>,
+}
+
+impl DirListing {
+    /// Build a listing from entries in any order; rejects entries the
+    /// canonical form cannot represent.
+    pub fn new(mut entries: Vec<DirEntry>) -> Result<DirListing, Error> {
+        entries.sort_by(|a, b| a.path.as_bytes().cmp(b.path.as_bytes()));
+        for e in &entries {
+            check_entry(e)?;
+        }
+        check_paths(&entries)?;
+        Ok(DirListing { entries })
+    }
+
+    /// The entries, sorted by path.
+    pub fn entries(&self) -> &[DirEntry] {
+        &self.entries
+    }
+
+    /// The entry for `path`, if listed.
+    pub fn entry(&self, path: &str) -> Option<&DirEntry> {
+        self.entries
+            .binary_search_by(|e| e.path.as_bytes().cmp(path.as_bytes()))
+            .ok()
+            .map(|i| &self.entries[i])
+    }
+
+    /// Canonical listing bytes (the input to `G`).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut s = String::from(HEADER);
+        for e in &self.entries {
+            s.push_str(&format!(
+                "{:06o} {} {} {}\n",
+                e.mode, e.length, e.identifier, e.path
+            ));
+        }
+        s.into_bytes()
+    }
+
+    /// Parse listing bytes, accepting only the exact canonical form.
+    pub fn parse(bytes: &[u8]) -> Result<DirListing, Error> {
+        let text =
+            std::str::from_utf8(bytes).map_err(|_| Error::malformed("listing: not utf-8"))?;
+        let body = text
+            .strip_prefix(HEADER)
+            .ok_or_else(|| Error::malformed("listing: missing terrapin-dir header"))?;
+        if !body.is_empty() && !body.ends_with('\n') {
+            return Err(Error::malformed("listing: missing final newline"));
+        }
+        let mut entries = Vec::new();
+        for line in body.split_terminator('\n') {
+            let mut parts = line.splitn(4, ' ');
+            let (mode, length, identifier, path) =
+                match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                    (Some(m), Some(l), Some(i), Some(p)) => (m, l, i, p),
+                    _ => {
+                        return Err(Error::malformed(format!(
+                            "listing: malformed line {:?}",
+                            line
+                        )))
+                    }
+                };
+            let mode = match mode {
+                "100644" => MODE_FILE,
+                "100755" => MODE_EXEC,
+                _ => return Err(Error::malformed(format!("listing: bad mode {:?}", mode))),
+            };
+            let canonical_len = length.bytes().all(|c| c.is_ascii_digit())
+                && !length.is_empty()
+                && (length == "0" || !length.starts_with('0'));
+            let length: u64 = match length.parse() {
+                Ok(n) if canonical_len => n,
+                _ => {
+                    return Err(Error::malformed(format!(
+                        "listing: bad length {:?}",
+                        length
+                    )))
+                }
+            };
+            entries.push(DirEntry {
+                path: path.to_string(),
+                mode,
+                length,
+                identifier: identifier.to_string(),
+            });
+        }
+        for e in &entries {
+            check_entry(e)?;
+        }
+        for w in entries.windows(2) {
+            if w[0].path.as_bytes() >= w[1].path.as_bytes() {
+                return Err(Error::malformed(format!(
+                    "listing: {:?} out of order or repeated",
+                    w[1].path
+                )));
+            }
+        }
+        check_paths(&entries)?;
+        Ok(DirListing { entries })
+    }
+
+    /// `terrapin-dir-sha256:` + hex of `G(listing)`.
+    pub fn identifier(&self) -> String {
+        format!("{}{}", DIR_PREFIX, to_hex(&g(&self.to_bytes())))
+    }
+
+    /// Read and parse a listing file.
+    pub fn read(path: &Path) -> Result<DirListing, Error> {
+        let bytes = fs::read(path)
+            .map_err(|e| Error::io(format!("cannot read {}: {}", path.display(), e)))?;
+        DirListing::parse(&bytes)
+    }
+
+    /// Assert this listing's identifier equals a trusted directory identifier.
+    pub fn check_against(&self, trusted_identifier: &str) -> Result<(), Error> {
+        let id = self.identifier();
+        if id != trusted_identifier {
+            return Err(Error::mismatch(format!(
+                "identifier mismatch: listing is {}, expected {}",
+                id, trusted_identifier
+            )));
+        }
+        Ok(())
+    }
+
+    /// Open member `path`'s tree from the attestation at `base` and check it is
+    /// the tree the listing names. Validate data against the result.
+    pub fn member_tree(&self, base: &Path, path: &str) -> Result<PersistedTree, Error> {
+        let entry = self
+            .entry(path)
+            .ok_or_else(|| Error::malformed(format!("listing: no member {:?}", path)))?;
+        let tree = PersistedTree::read(&member_base(base, path))?;
+        tree.check_against(&entry.identifier)?;
+        Ok(tree)
+    }
+}
+
+/// Hash every regular file under `root` into a listing, writing nothing.
+pub fn dir_listing(root: &Path) -> Result<DirListing, Error> {
+    hash_dir(root, None)
+}
+
+/// Hash every regular file under `root`, writing `<base>.listing` and each
+/// member's tree under `<base>.d/`.
+pub fn attest_dir(root: &Path, base: &Path) -> Result<DirListing, Error> {
+    let listing = hash_dir(root, Some(base))?;
+    let path = with_ext(base, "listing");
+    fs::write(&path, listing.to_bytes())
+        .map_err(|e| Error::io(format!("cannot write {}: {}", path.display(), e)))?;
+    Ok(listing)
+}
+
+/// Tree base name of member `path` in the attestation at `base`.
+pub fn member_base(base: &Path, path: &str) -> PathBuf {
+    let mut p = with_ext(base, "d");
+    p.extend(path.split('/'));
+    p.push(MEMBER_TREE);
+    p
+}
+
+fn hash_dir(root: &Path, base: Option<&Path>) -> Result<DirListing, Error> {
+    let mut files = Vec::new();
+    walk(root, "", &mut files)?;
+    let mut entries = Vec::with_capacity(files.len());
+    for (rel, full, mode) in files {
+        let f = File::open(&full)
+            .map_err(|e| Error::io(format!("cannot open {}: {}", full.display(), e)))?;
+        let tree = build_from_file_sync(f)
+            .map_err(|e| Error::io(format!("hashing {} failed: {}", full.display(), e)))?;
+        if let Some(base) = base {
+            let out = member_base(base, &rel);
+            let parent = out.parent().expect("member base has a parent");
+            fs::create_dir_all(parent)
+                .and_then(|_| PersistedTree::write(&out, &tree))
+                .map_err(|e| Error::io(format!("writing tree for {} failed: {}", rel, e)))?;
+        }
+        entries.push(DirEntry {
+            path: rel,
+            mode,
+            length: tree.length,
+            identifier: tree.identifier(),
+        });
+    }
+    DirListing::new(entries)
+}
+
+/// Collect `(relative path, full path, mode)` for every regular file under `dir`.
+fn walk(dir: &Path, prefix: &str, out: &mut Vec<(String, PathBuf, u32)>) -> Result<(), Error> {
+    let rd = fs::read_dir(dir)
+        .map_err(|e| Error::io(format!("cannot read {}: {}", dir.display(), e)))?;
+    for entry in rd {
+        let entry =
+            entry.map_err(|e| Error::io(format!("cannot read {}: {}", dir.display(), e)))?;
+        let full = entry.path();
+        let name = entry
+            .file_name()
+            .into_string()
+            .map_err(|_| Error::other(format!("{}: non-utf-8 file name", full.display())))?;
+        let rel = format!("{}{}", prefix, name);
+        let meta = fs::symlink_metadata(&full)
+            .map_err(|e| Error::io(format!("stat {}: {}", full.display(), e)))?;
+        if meta.is_dir() {
+            walk(&full, &format!("{}/", rel), out)?;
+        } else if meta.is_file() {
+            out.push((rel, full, file_mode(&meta)));
+        } else {
+            return Err(Error::other(format!(
+                "{}: only regular files and directories are supported",
+                full.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn file_mode(meta: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    if meta.permissions().mode() & 0o111 != 0 {
+        MODE_EXEC
+    } else {
+        MODE_FILE
+    }
+}
+
+#[cfg(not(unix))]
+fn file_mode(_meta: &fs::Metadata) -> u32 {
+    MODE_FILE
+}
+
+fn check_entry(e: &DirEntry) -> Result<(), Error> {
+    if e.mode != MODE_FILE && e.mode != MODE_EXEC {
+        return Err(Error::malformed(format!(
+            "listing: bad mode {:o} for {:?}",
+            e.mode, e.path
+        )));
+    }
+    let hex_ok = e
+        .identifier
+        .strip_prefix("terrapin-sha256:")
+        .is_some_and(|h| {
+            h.len() == 64 && h.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f'))
+        });
+    if !hex_ok {
+        return Err(Error::malformed(format!(
+            "listing: bad identifier for {:?}",
+            e.path
+        )));
+    }
+    let path_ok = !e.path.is_empty()
+        && !e.path.bytes().any(|c| c < 0x20 || c == 0x7f)
+        && e.path
+            .split('/')
+            .all(|c| !c.is_empty() && c != "." && c != "..");
+    if !path_ok {
+        return Err(Error::malformed(format!("listing: bad path {:?}", e.path)));
+    }
+    Ok(())
+}
+
+/// No duplicate paths, and no path is also a directory of another.
+fn check_paths(entries: &[DirEntry]) -> Result<(), Error> {
+    let mut seen = HashSet::new();
+    for e in entries {
+        if !seen.insert(e.path.as_str()) {
+            return Err(Error::malformed(format!(
+                "listing: duplicate path {:?}",
+                e.path
+            )));
+        }
+    }
+    for e in entries {
+        let mut at = 0;
+        while let Some(i) = e.path[at..].find('/') {
+            let dir = &e.path[..at + i];
+            if seen.contains(dir) {
+                return Err(Error::malformed(format!(
+                    "listing: {:?} is both a file and a directory",
+                    dir
+                )));
+            }
+            at += i + 1;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,203 @@
+//! Incremental hashing of data produced piecewise in memory.
+//!
+//! [`Hasher`] takes bytes through [`Hasher::update`] (or [`std::io::Write`]) in
+//! chunks of any size, buffers them to exact `BLOCK` boundaries and feeds each
+//! completed block's leaf hash to a [`TreeBuilder`]. The result is the same
+//! tree [`crate::build_from_reader_sync`] produces for the concatenated bytes.
+//! Only the current partial block is buffered, plus up to `threads` blocks in
+//! flight when hashing in the background.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use terrapin_core::{Profile, BLOCK, SHA256};
+
+use crate::builder::{BuiltTree, TreeBuilder};
+
+/// Incremental Terrapin hasher: `update` in any chunking, then `finalize`.
+pub struct Hasher {
+    profile: &'static dyn Profile,
+    /// The current partial block; hashed once it reaches `BLOCK` bytes.
+    buf: Vec<u8>,
+    length: u64,
+    builder: TreeBuilder,
+    workers: Option<Workers>,
+}
+
+impl Default for Hasher {
+    fn default() -> Self {
+        Hasher::new()
+    }
+}
+
+impl Hasher {
+    pub fn new() -> Self {
+        Hasher::with_profile(SHA256)
+    }
+
+    /// A hasher for `profile`.
+    pub fn with_profile(profile: &'static dyn Profile) -> Self {
+        Hasher {
+            profile,
+            buf: Vec::new(),
+            length: 0,
+            builder: TreeBuilder::with_profile(profile),
+            workers: None,
+        }
+    }
+
+    /// Hash completed blocks on `threads` background threads instead of in
+    /// `update`; `update` then only blocks once `threads` blocks are in
+    /// flight. `0` or `1` keeps hashing inline. May be called at any point:
+    /// blocks already in flight are delivered first.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        if let Some(w) = self.workers.as_mut() {
+            w.drain(&mut self.builder);
+        }
+        self.workers = (threads > 1).then(|| Workers::start(self.profile, threads));
+        self
+    }
+
+    /// Append `data` to the dataset.
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.length += data.len() as u64;
+        while !data.is_empty() {
+            if self.buf.is_empty() && data.len() >= BLOCK {
+                self.push_block(&data[..BLOCK]);
+                data = &data[BLOCK..];
+                continue;
+            }
+            let take = (BLOCK - self.buf.len()).min(data.len());
+            self.buf.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.buf.len() == BLOCK {
+                let mut block = mem::take(&mut self.buf);
+                self.push_block(&block);
+                block.clear();
+                self.buf = block;
+            }
+        }
+    }
+
+    /// Bytes passed to `update` so far.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// Finish the tree. An empty dataset is one empty leaf, as for a reader.
+    pub fn finalize(mut self) -> BuiltTree {
+        if !self.buf.is_empty() || self.length == 0 {
+            let block = mem::take(&mut self.buf);
+            self.push_block(&block);
+        }
+        if let Some(w) = self.workers.as_mut() {
+            w.drain(&mut self.builder);
+        }
+        self.builder.build(self.length)
+    }
+
+    /// Finish and return the `<profile>:<hex>` identifier.
+    pub fn identifier(self) -> String {
+        self.finalize().identifier()
+    }
+
+    fn push_block(&mut self, block: &[u8]) {
+        match self.workers.as_mut() {
+            Some(w) => w.submit(block.to_vec(), &mut self.builder),
+            None => self.builder.push_leaf(&self.profile.g(block)),
+        }
+    }
+}
+
+impl Write for Hasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Background block hashing with bounded, order-preserving delivery.
+struct Workers {
+    jobs: Option<Sender<(u64, Vec<u8>)>>,
+    done: Receiver<(u64, thread::Result<[u8; 32]>)>,
+    handles: Vec<JoinHandle<()>>,
+    limit: u64,
+    sent: u64,
+    next: u64,
+    ready: BTreeMap<u64, [u8; 32]>,
+}
+
+impl Workers {
+    fn start(profile: &'static dyn Profile, threads: usize) -> Self {
+        let (job_tx, job_rx) = mpsc::channel::<(u64, Vec<u8>)>();
+        let (done_tx, done_rx) = mpsc::channel();
+        let job_rx = Arc::new(Mutex::new(job_rx));
+        let handles = (0..threads)
+            .map(|_| {
+                let job_rx = Arc::clone(&job_rx);
+                let done_tx = done_tx.clone();
+                thread::spawn(move || loop {
+                    let job = job_rx.lock().unwrap().recv();
+                    let Ok((seq, block)) = job else { break };
+                    let h = panic::catch_unwind(AssertUnwindSafe(|| profile.g(&block)));
+                    if done_tx.send((seq, h)).is_err() {
+                        break;
+                    }
+                })
+            })
+            .collect();
+        Workers {
+            jobs: Some(job_tx),
+            done: done_rx,
+            handles,
+            limit: threads as u64,
+            sent: 0,
+            next: 0,
+            ready: BTreeMap::new(),
+        }
+    }
+
+    fn submit(&mut self, block: Vec<u8>, builder: &mut TreeBuilder) {
+        while self.sent - self.next >= self.limit {
+            self.receive(builder);
+        }
+        let jobs = self.jobs.as_ref().expect("workers running");
+        jobs.send((self.sent, block)).expect("workers running");
+        self.sent += 1;
+    }
+
+    fn drain(&mut self, builder: &mut TreeBuilder) {
+        while self.next < self.sent {
+            self.receive(builder);
+        }
+    }
+
+    /// Wait for one result, then push every leaf now in order.
+    fn receive(&mut self, builder: &mut TreeBuilder) {
+        let (seq, h) = self.done.recv().expect("workers running");
+        let h = h.unwrap_or_else(|p| panic::resume_unwind(p));
+        self.ready.insert(seq, h);
+        while let Some(h) = self.ready.remove(&self.next) {
+            builder.push_leaf(&h);
+            self.next += 1;
+        }
+    }
+}
+
+impl Drop for Workers {
+    fn drop(&mut self) {
+        self.jobs = None;
+        for h in self.handles.drain(..) {
+            let _ = h.join();
+        }
+    }
+}
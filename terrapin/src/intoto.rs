@@ -0,0 +1,283 @@
+//! in-toto attestations with terrapin subject digests (feature `intoto`).
+//!
+//! [`InTotoStatement`] is an in-toto Statement v1 whose subjects carry the
+//! identifier as a digest, keyed by profile name:
+//! `{"name": "data.bin", "digest": {"terrapin-sha256": "<64 hex>"}}`.
+//! [`Envelope`] wraps a statement in a DSSE envelope signed with a
+//! [`SecretKey`]; [`Envelope::verify`] checks it against a [`PublicKey`] and
+//! returns the statement, whose [`InTotoStatement::identifier_for`] is then the
+//! trusted identifier for [`crate::PersistedTree::check_against`].
+
+use std::collections::BTreeMap;
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE};
+use base64::Engine;
+use serde_json::{json, Value};
+
+use crate::{profile_by_name, Error, PublicKey, SecretKey, BLOCK, DIR_PREFIX};
+
+/// `_type` of an in-toto Statement v1.
+pub const STATEMENT_TYPE: &str = "https://in-toto.io/Statement/v1";
+/// DSSE `payloadType` of an in-toto statement.
+pub const PAYLOAD_TYPE: &str = "application/vnd.in-toto+json";
+/// `predicateType` of the statements [`InTotoStatement::for_tree`] makes.
+pub const PREDICATE_TYPE: &str = "https://terrapin.io/attestation/tree/v1";
+
+/// An in-toto subject: a name and its digests by algorithm.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Subject {
+    pub name: String,
+    pub digest: BTreeMap<String, String>,
+}
+
+impl Subject {
+    /// A subject whose only digest is `identifier` (`<profile>:<hex>`).
+    pub fn new(name: &str, identifier: &str) -> Result<Subject, Error> {
+        let (alg, hex) = identifier
+            .split_once(':')
+            .ok_or_else(|| Error::malformed(format!("in-toto: bad identifier {:?}", identifier)))?;
+        let subject = Subject {
+            name: name.to_string(),
+            digest: BTreeMap::from([(alg.to_string(), hex.to_string())]),
+        };
+        subject.identifier()?;
+        Ok(subject)
+    }
+
+    /// The terrapin identifier among the digests: the one keyed by a known
+    /// profile name or `terrapin-dir-sha256`.
+    pub fn identifier(&self) -> Result<String, Error> {
+        let dir = DIR_PREFIX.trim_end_matches(':');
+        let (alg, hex) = self
+            .digest
+            .iter()
+            .find(|(alg, _)| profile_by_name(alg).is_some() || alg.as_str() == dir)
+            .ok_or_else(|| {
+                Error::malformed(format!(
+                    "in-toto: subject {:?} has no terrapin digest",
+                    self.name
+                ))
+            })?;
+        if hex.len() != 64 || !hex.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f')) {
+            return Err(Error::malformed(format!(
+                "in-toto: bad {} digest {:?}",
+                alg, hex
+            )));
+        }
+        Ok(format!("{}:{}", alg, hex))
+    }
+}
+
+/// An in-toto Statement v1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InTotoStatement {
+    pub subjects: Vec<Subject>,
+    pub predicate_type: String,
+    pub predicate: Value,
+}
+
+impl InTotoStatement {
+    /// A statement about one attested tree: `name` with its identifier as
+    /// the digest, and a [`PREDICATE_TYPE`] predicate recording the length,
+    /// block size and tree root.
+    pub fn for_tree(
+        name: &str,
+        identifier: &str,
+        length: u64,
+        tree_hex: &str,
+    ) -> Result<InTotoStatement, Error> {
+        Ok(InTotoStatement {
+            subjects: vec![Subject::new(name, identifier)?],
+            predicate_type: PREDICATE_TYPE.to_string(),
+            predicate: json!({ "length": length, "blockSize": BLOCK, "tree": tree_hex }),
+        })
+    }
+
+    /// The statement as JSON (object keys sorted).
+    pub fn to_json(&self) -> Vec<u8> {
+        let subjects: Vec<Value> = self
+            .subjects
+            .iter()
+            .map(|s| json!({ "name": s.name, "digest": s.digest }))
+            .collect();
+        let v = json!({
+            "_type": STATEMENT_TYPE,
+            "subject": subjects,
+            "predicateType": self.predicate_type,
+            "predicate": self.predicate,
+        });
+        serde_json::to_vec(&v).expect("statement serializes")
+    }
+
+    /// Parse a statement, requiring `_type` Statement v1 and at least one
+    /// subject.
+    pub fn parse(bytes: &[u8]) -> Result<InTotoStatement, Error> {
+        let v: Value = serde_json::from_slice(bytes)
+            .map_err(|e| Error::malformed(format!("in-toto: {}", e)))?;
+        if v["_type"] != STATEMENT_TYPE {
+            return Err(Error::malformed(format!(
+                "in-toto: _type is not {}",
+                STATEMENT_TYPE
+            )));
+        }
+        let subjects = v["subject"]
+            .as_array()
+            .filter(|a| !a.is_empty())
+            .ok_or_else(|| Error::malformed("in-toto: missing subject"))?
+            .iter()
+            .map(|s| {
+                let name = s["name"]
+                    .as_str()
+                    .ok_or_else(|| Error::malformed("in-toto: subject without name"))?;
+                let digest = s["digest"]
+                    .as_object()
+                    .ok_or_else(|| Error::malformed("in-toto: subject without digest"))?
+                    .iter()
+                    .map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+                    .collect::<Option<BTreeMap<_, _>>>()
+                    .ok_or_else(|| Error::malformed("in-toto: non-string digest"))?;
+                Ok(Subject {
+                    name: name.to_string(),
+                    digest,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let predicate_type = v["predicateType"]
+            .as_str()
+            .ok_or_else(|| Error::malformed("in-toto: missing predicateType"))?
+            .to_string();
+        Ok(InTotoStatement {
+            subjects,
+            predicate_type,
+            predicate: v["predicate"].clone(),
+        })
+    }
+
+    /// The identifier of the subject called `name`.
+    pub fn identifier_for(&self, name: &str) -> Result<String, Error> {
+        self.subjects
+            .iter()
+            .find(|s| s.name == name)
+            .ok_or_else(|| Error::malformed(format!("in-toto: no subject named {:?}", name)))?
+            .identifier()
+    }
+}
+
+/// One DSSE signature.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnvelopeSignature {
+    pub keyid: String,
+    pub sig: Vec<u8>,
+}
+
+/// A DSSE envelope.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Envelope {
+    pub payload_type: String,
+    pub payload: Vec<u8>,
+    pub signatures: Vec<EnvelopeSignature>,
+}
+
+impl Envelope {
+    /// Wrap `statement` and sign it with `key`; the key id is
+    /// [`PublicKey::key_id`].
+    pub fn sign(statement: &InTotoStatement, key: &SecretKey) -> Result<Envelope, Error> {
+        let payload = statement.to_json();
+        let sig = key.sign_bytes(&pae(PAYLOAD_TYPE, &payload))?;
+        Ok(Envelope {
+            payload_type: PAYLOAD_TYPE.to_string(),
+            payload,
+            signatures: vec![EnvelopeSignature {
+                keyid: key.public_key()?.key_id(),
+                sig,
+            }],
+        })
+    }
+
+    /// Check that a signature verifies with `key` and return the in-toto
+    /// statement it covers. Signatures naming another key id are skipped.
+    pub fn verify(&self, key: &PublicKey) -> Result<InTotoStatement, Error> {
+        if self.payload_type != PAYLOAD_TYPE {
+            return Err(Error::malformed(format!(
+                "dsse: payloadType is not {}",
+                PAYLOAD_TYPE
+            )));
+        }
+        let msg = pae(&self.payload_type, &self.payload);
+        let key_id = key.key_id();
+        let verified = self
+            .signatures
+            .iter()
+            .filter(|s| s.keyid.is_empty() || s.keyid == key_id)
+            .any(|s| key.verify_bytes(&msg, &s.sig).is_ok());
+        if !verified {
+            return Err(Error::signature(
+                "dsse: no signature verifies with this key",
+            ));
+        }
+        InTotoStatement::parse(&self.payload)
+    }
+
+    /// The envelope as JSON.
+    pub fn to_json(&self) -> Vec<u8> {
+        let signatures: Vec<Value> = self
+            .signatures
+            .iter()
+            .map(|s| json!({ "keyid": s.keyid, "sig": STANDARD.encode(&s.sig) }))
+            .collect();
+        let v = json!({
+            "payloadType": self.payload_type,
+            "payload": STANDARD.encode(&self.payload),
+            "signatures": signatures,
+        });
+        serde_json::to_vec(&v).expect("envelope serializes")
+    }
+
+    /// Parse an envelope; base64 fields may be standard or URL-safe.
+    pub fn parse(bytes: &[u8]) -> Result<Envelope, Error> {
+        let v: Value =
+            serde_json::from_slice(bytes).map_err(|e| Error::malformed(format!("dsse: {}", e)))?;
+        let field = |v: &Value, name: &str| -> Result<String, Error> {
+            v[name]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| Error::malformed(format!("dsse: missing {}", name)))
+        };
+        let signatures = v["signatures"]
+            .as_array()
+            .ok_or_else(|| Error::malformed("dsse: missing signatures"))?
+            .iter()
+            .map(|s| {
+                Ok(EnvelopeSignature {
+                    keyid: s["keyid"].as_str().unwrap_or_default().to_string(),
+                    sig: base64_decode(&field(s, "sig")?)?,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Envelope {
+            payload_type: field(&v, "payloadType")?,
+            payload: base64_decode(&field(&v, "payload")?)?,
+            signatures,
+        })
+    }
+}
+
+/// DSSE pre-authentication encoding: what the signatures cover.
+pub fn pae(payload_type: &str, payload: &[u8]) -> Vec<u8> {
+    let mut out = format!(
+        "DSSEv1 {} {} {} ",
+        payload_type.len(),
+        payload_type,
+        payload.len()
+    )
+    .into_bytes();
+    out.extend_from_slice(payload);
+    out
+}
+
+fn base64_decode(s: &str) -> Result<Vec<u8>, Error> {
+    STANDARD
+        .decode(s)
+        .or_else(|_| URL_SAFE.decode(s))
+        .map_err(|e| Error::malformed(format!("dsse: bad base64: {}", e)))
+}
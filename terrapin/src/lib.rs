@@ -7,20 +7,99 @@
 //! `terrapin-sha256:<64 hex>`. See `docs/spec.md` for the normative definition.
 //!
 //! * [`identifier`] / [`tree_root`] — in-memory reference over a full slice.
-//! * [`identifier_from_reader`] / [`build_from_reader`] — streaming + parallel
-//!   construction that never holds the dataset in memory.
+//! * [`build_from_reader_sync`] — streaming + parallel construction on worker
+//!   threads that never holds the dataset in memory; [`identifier_from_reader`]
+//!   / [`build_from_reader`] are the async forms (feature `tokio`, default).
+//!   [`build_from_file_sync`] / [`build_from_file`] skip the holes of sparse
+//!   files, and [`zero_identifier`] needs no data at all.
+//! * [`Hasher`] — incremental `update`/`finalize` (and `io::Write`) for data
+//!   produced piecewise in memory.
 //! * [`PersistedTree`] — write a publishable two-file tree and validate (or
 //!   stream) arbitrary byte ranges without reading the whole dataset.
+//! * [`Profile`] — the hash profile; every `*_with` function and
+//!   [`TreeBuilder::with_profile`] take one (default `terrapin-sha256`; feature
+//!   `blake3` adds `terrapin-blake3`).
+//! * [`DirListing`] / [`attest_dir`] — directory datasets: per-file identifiers
+//!   committed by a canonical sorted listing into one directory identifier.
+//! * [`Store`] — a local content-addressed store of datasets and their trees,
+//!   keyed by identifier.
+//! * [`SignedStatement`] — a signature over an identifier and length, so the
+//!   trusted identifier can come from a verified signature (feature `sign`).
+//! * [`InTotoStatement`] / [`Envelope`] — in-toto attestations with the
+//!   identifier as subject digest, in DSSE envelopes (feature `intoto`).
+//! * [`Sbom`] — SPDX 2.3 / CycloneDX 1.5 documents listing files by
+//!   identifier, and validating them back (feature `sbom`).
+//! * [`oci_export`] / [`OciDataset`] — a dataset and its tree as layers of an
+//!   OCI image layout, validated in place (feature `oci`).
+//! * [`IdCache`] — identifiers cached per file in extended attributes or a
+//!   local database, invalidated when the file's size or mtime changes (in
+//!   the database, any metadata change; feature `cache`).
+//!
+//! Fallible calls return an [`Error`], whose [`ErrorKind`] says what went
+//! wrong without parsing the message.
+//!
+//! The primitives and [`PathVerifier`] come from the `no_std` `terrapin-core`
+//! crate and are re-exported here; this crate adds the `std` layers on top.
+//!
+//! The SHA-256 implementation is a cargo feature: `sha2` (default, pure Rust),
+//! `sha2-asm`, `ring` or `boringssl`; [`sha256_backend`] names the one in use.
 
 mod builder;
-mod manifest;
+#[cfg(feature = "cache")]
+mod cache;
+mod dir;
+mod hasher;
+#[cfg(feature = "intoto")]
+mod intoto;
+#[cfg(feature = "oci")]
+mod oci;
+#[cfg(feature = "sbom")]
+mod sbom;
+#[cfg(feature = "sign")]
+mod sign;
+mod sparse;
+mod store;
 mod stream;
 mod tree;
 
 pub use builder::{BuiltTree, TreeBuilder};
-pub use manifest::{
-    g, identifier, identifier_from_parts, manifest_bytes, parse_manifest, to_hex, tree_root, BLOCK,
-    FANOUT,
+#[cfg(feature = "cache")]
+pub use cache::{CacheEntry, FileKey, IdCache};
+pub use dir::{attest_dir, dir_listing, member_base, DirEntry, DirListing, DIR_PREFIX};
+pub use hasher::Hasher;
+#[cfg(feature = "intoto")]
+pub use intoto::{
+    pae, Envelope, EnvelopeSignature, InTotoStatement, Subject, PAYLOAD_TYPE, PREDICATE_TYPE,
+    STATEMENT_TYPE,
+};
+#[cfg(feature = "oci")]
+pub use oci::{
+    oci_export, OciDataset, OciExport, ARTIFACT_TYPE, BLOCKS_MEDIA_TYPE, DATA_MEDIA_TYPE,
+    HEAD_MEDIA_TYPE, IDENTIFIER_ANNOTATION,
+};
+#[cfg(feature = "sbom")]
+pub use sbom::{Sbom, SbomEntry, SbomFormat};
+#[cfg(feature = "sign")]
+pub use sign::{PublicKey, SecretKey, SignatureAlgorithm, SignedStatement, Statement};
+pub use store::{Added, Store, StoreEntry, StoreMode, StoreStats};
+pub use sparse::SparseSource;
+pub use stream::{
+    build_from_file_sync, build_from_file_sync_with, build_from_reader_sync,
+    build_from_reader_sync_with,
+};
+#[cfg(feature = "tokio")]
+pub use stream::{
+    build_from_file, build_from_file_with, build_from_reader, build_from_reader_with,
+    identifier_from_reader,
+};
+pub use tree::{BlockVerifier, HeadFormat, PathStep, PersistedTree};
+#[cfg(feature = "blake3")]
+pub use terrapin_core::{TerrapinBlake3, BLAKE3};
+pub use terrapin_core::{
+    derive_counts, g, g_many, hex_to_32, identifier, identifier_from_parts,
+    identifier_from_parts_with, identifier_with, manifest_bytes, manifest_bytes_with,
+    offsets_from_counts, parse_manifest, parse_manifest_with, profile_by_name, profile_of,
+    profiles, sha256_backend, to_hex, tree_root, tree_root_with, zero_identifier,
+    zero_identifier_with, zero_tree_root, zero_tree_root_with, Error, ErrorKind, PathLayer,
+    PathVerifier, Profile, Sha256Context, TerrapinSha256, BLOCK, FANOUT, SHA256,
 };
-pub use stream::{build_from_reader, identifier_from_reader};
-pub use tree::{derive_counts, PersistedTree};
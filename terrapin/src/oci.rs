@@ -0,0 +1,323 @@
+//! OCI image-layout export of a dataset and its tree (feature `oci`).
+//!
+//! [`oci_export`] writes an OCI image layout directory holding one artifact
+//! manifest per dataset, with three layers: the data blob, the `.head` and the
+//! `.blocks`. The manifest's `io.terrapin.identifier` annotation carries the
+//! identifier; `index.json` names the manifest by a ref
+//! (`org.opencontainers.image.ref.name`). The layout can be pushed to a
+//! registry with any OCI tool and pulled back unchanged.
+//!
+//! [`OciDataset::open`] reads a layout back. The index, manifest and head are
+//! small and checked against their digests; the data and `.blocks` blobs are
+//! not hashed whole — [`OciDataset::validate`] verifies just the requested
+//! range against the tree.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::tree::with_ext;
+use crate::{build_from_reader_sync, Error, PersistedTree, Sha256Context};
+
+/// `artifactType` of a terrapin dataset manifest.
+pub const ARTIFACT_TYPE: &str = "application/vnd.terrapin.dataset.v1";
+/// Media type of the data layer.
+pub const DATA_MEDIA_TYPE: &str = "application/vnd.terrapin.data.v1";
+/// Media type of the `.head` layer.
+pub const HEAD_MEDIA_TYPE: &str = "application/vnd.terrapin.tree.head.v1";
+/// Media type of the `.blocks` layer.
+pub const BLOCKS_MEDIA_TYPE: &str = "application/vnd.terrapin.tree.blocks.v1";
+/// Manifest annotation holding the identifier.
+pub const IDENTIFIER_ANNOTATION: &str = "io.terrapin.identifier";
+
+const MANIFEST_MEDIA_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+const INDEX_MEDIA_TYPE: &str = "application/vnd.oci.image.index.v1+json";
+const EMPTY_MEDIA_TYPE: &str = "application/vnd.oci.empty.v1+json";
+const REF_ANNOTATION: &str = "org.opencontainers.image.ref.name";
+const TITLE_ANNOTATION: &str = "org.opencontainers.image.title";
+
+/// What [`oci_export`] wrote.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OciExport {
+    pub identifier: String,
+    /// `sha256:<hex>` of the artifact manifest.
+    pub manifest_digest: String,
+    pub reference: String,
+}
+
+/// Export the file at `data` with its tree into the OCI image layout at
+/// `layout` (created if missing), under `reference` (default: the file name).
+/// An existing manifest with the same ref is replaced in `index.json`; blobs
+/// are never deleted.
+pub fn oci_export(data: &Path, layout: &Path, reference: Option<&str>) -> Result<OciExport, Error> {
+    let name = data
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .ok_or_else(|| Error::other(format!("{}: not a file name", data.display())))?;
+    let reference = reference.unwrap_or(&name).to_string();
+    let blobs = layout.join("blobs").join("sha256");
+    fs::create_dir_all(&blobs).map_err(|e| io_err(&blobs, e))?;
+    write_file(
+        &layout.join("oci-layout"),
+        br#"{"imageLayoutVersion":"1.0.0"}"#,
+    )?;
+
+    // One pass over the data: copy it into a blob, hash it, build the tree.
+    let tmp = blobs.join(format!(".tmp-{}", std::process::id()));
+    let src = File::open(data).map_err(|e| io_err(data, e))?;
+    let dst = File::create(&tmp).map_err(|e| io_err(&tmp, e))?;
+    let mut tee = CopyTee {
+        inner: src,
+        out: dst,
+        ctx: Sha256Context::new(),
+    };
+    let tree = build_from_reader_sync(&mut tee)
+        .map_err(|e| Error::io(format!("hashing {} failed: {}", data.display(), e)))?;
+    tee.out.flush().map_err(|e| io_err(&tmp, e))?;
+    let data_digest = format!("sha256:{}", hex::encode(tee.ctx.finish()));
+    fs::rename(&tmp, blob_path(layout, &data_digest)?).map_err(|e| io_err(&tmp, e))?;
+
+    PersistedTree::write(&tmp, &tree).map_err(|e| io_err(&tmp, e))?;
+    let mut tree_blobs = Vec::new();
+    for ext in ["head", "blocks"] {
+        let path = with_ext(&tmp, ext);
+        let bytes = fs::read(&path).map_err(|e| io_err(&path, e))?;
+        let _ = fs::remove_file(&path);
+        tree_blobs.push(put_blob(layout, &bytes)?);
+    }
+
+    let config = put_blob(layout, b"{}")?;
+    let (head, blocks) = (&tree_blobs[0], &tree_blobs[1]);
+    let manifest = json!({
+        "schemaVersion": 2,
+        "mediaType": MANIFEST_MEDIA_TYPE,
+        "artifactType": ARTIFACT_TYPE,
+        "config": { "mediaType": EMPTY_MEDIA_TYPE, "digest": config.0, "size": config.1 },
+        "layers": [
+            descriptor(DATA_MEDIA_TYPE, &data_digest, tree.length, &name),
+            descriptor(HEAD_MEDIA_TYPE, &head.0, head.1, &format!("{}.head", name)),
+            descriptor(BLOCKS_MEDIA_TYPE, &blocks.0, blocks.1, &format!("{}.blocks", name)),
+        ],
+        "annotations": {
+            IDENTIFIER_ANNOTATION: tree.identifier(),
+            "io.terrapin.length": tree.length.to_string(),
+        },
+    });
+    let manifest = serde_json::to_vec(&manifest).expect("manifest serializes");
+    let (manifest_digest, manifest_size) = put_blob(layout, &manifest)?;
+
+    let index_path = layout.join("index.json");
+    let mut manifests = match fs::read(&index_path) {
+        Ok(bytes) => read_index(&bytes)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(io_err(&index_path, e)),
+    };
+    manifests.retain(|m| m["annotations"][REF_ANNOTATION] != reference.as_str());
+    manifests.push(json!({
+        "mediaType": MANIFEST_MEDIA_TYPE,
+        "artifactType": ARTIFACT_TYPE,
+        "digest": manifest_digest,
+        "size": manifest_size,
+        "annotations": { REF_ANNOTATION: reference },
+    }));
+    let index = json!({
+        "schemaVersion": 2,
+        "mediaType": INDEX_MEDIA_TYPE,
+        "manifests": manifests,
+    });
+    write_file(
+        &index_path,
+        &serde_json::to_vec_pretty(&index).expect("index serializes"),
+    )?;
+
+    Ok(OciExport {
+        identifier: tree.identifier(),
+        manifest_digest,
+        reference,
+    })
+}
+
+/// A dataset read back from an OCI image layout.
+pub struct OciDataset {
+    pub reference: String,
+    pub manifest_digest: String,
+    /// The identifier the manifest annotates (equal to the tree's).
+    pub identifier: String,
+    data_path: PathBuf,
+    tree: PersistedTree,
+}
+
+impl OciDataset {
+    /// Open the dataset named `reference` in the layout at `layout` (default:
+    /// the only terrapin manifest in the index).
+    pub fn open(layout: &Path, reference: Option<&str>) -> Result<OciDataset, Error> {
+        let index_path = layout.join("index.json");
+        let bytes = fs::read(&index_path).map_err(|e| io_err(&index_path, e))?;
+        let ours: Vec<Value> = read_index(&bytes)?
+            .into_iter()
+            .filter(|m| m["artifactType"] == ARTIFACT_TYPE)
+            .collect();
+        let entry = match reference {
+            Some(r) => ours
+                .iter()
+                .find(|m| m["annotations"][REF_ANNOTATION] == r)
+                .ok_or_else(|| {
+                    Error::malformed(format!("oci: no terrapin dataset with ref {:?}", r))
+                })?,
+            None if ours.len() == 1 => &ours[0],
+            None => {
+                return Err(Error::malformed(format!(
+                    "oci: {} terrapin datasets in the index; choose one by ref",
+                    ours.len()
+                )))
+            }
+        };
+        let manifest_digest = entry["digest"]
+            .as_str()
+            .ok_or_else(|| Error::malformed("oci: index entry without digest"))?
+            .to_string();
+        let manifest: Value = serde_json::from_slice(&read_blob(layout, &manifest_digest)?)
+            .map_err(|e| Error::malformed(format!("oci: manifest: {}", e)))?;
+        if manifest["artifactType"] != ARTIFACT_TYPE {
+            return Err(Error::malformed("oci: manifest is not a terrapin dataset"));
+        }
+        let layer = |media_type: &str| -> Result<(String, u64), Error> {
+            let l = manifest["layers"]
+                .as_array()
+                .and_then(|ls| ls.iter().find(|l| l["mediaType"] == media_type))
+                .ok_or_else(|| {
+                    Error::malformed(format!("oci: manifest has no {} layer", media_type))
+                })?;
+            match (l["digest"].as_str(), l["size"].as_u64()) {
+                (Some(d), Some(n)) => Ok((d.to_string(), n)),
+                _ => Err(Error::malformed(format!(
+                    "oci: bad {} descriptor",
+                    media_type
+                ))),
+            }
+        };
+        let (data_digest, data_size) = layer(DATA_MEDIA_TYPE)?;
+        let (head_digest, _) = layer(HEAD_MEDIA_TYPE)?;
+        let (blocks_digest, _) = layer(BLOCKS_MEDIA_TYPE)?;
+
+        let head = String::from_utf8(read_blob(layout, &head_digest)?)
+            .map_err(|_| Error::malformed("oci: head is not utf-8"))?;
+        let tree = PersistedTree::from_parts(&head, &blob_path(layout, &blocks_digest)?)?;
+        let identifier = manifest["annotations"][IDENTIFIER_ANNOTATION]
+            .as_str()
+            .ok_or_else(|| Error::malformed("oci: manifest has no identifier annotation"))?
+            .to_string();
+        tree.check_against(&identifier)?;
+        if tree.length != data_size {
+            return Err(Error::malformed(format!(
+                "oci: data layer size {} != tree length {}",
+                data_size, tree.length
+            )));
+        }
+        Ok(OciDataset {
+            reference: entry["annotations"][REF_ANNOTATION]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            manifest_digest,
+            identifier,
+            data_path: blob_path(layout, &data_digest)?,
+            tree,
+        })
+    }
+
+    /// The dataset's tree (head from the layout, `.blocks` read in place).
+    pub fn tree(&self) -> &PersistedTree {
+        &self.tree
+    }
+
+    /// The data blob.
+    pub fn data_path(&self) -> &Path {
+        &self.data_path
+    }
+
+    /// Validate bytes `[start, end)` of the data blob (the whole dataset by
+    /// default) against the tree, optionally streaming them to `writer`.
+    pub fn validate(
+        &self,
+        start: Option<u64>,
+        end: Option<u64>,
+        writer: Option<&mut dyn Write>,
+    ) -> Result<(), Error> {
+        self.tree.validate(&self.data_path, start, end, writer)
+    }
+}
+
+/// Reader that copies everything read to `out` and through a SHA-256 context.
+struct CopyTee<R> {
+    inner: R,
+    out: File,
+    ctx: Sha256Context,
+}
+
+impl<R: Read> Read for CopyTee<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.out.write_all(&buf[..n])?;
+        self.ctx.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+fn descriptor(media_type: &str, digest: &str, size: u64, title: &str) -> Value {
+    json!({
+        "mediaType": media_type,
+        "digest": digest,
+        "size": size,
+        "annotations": { TITLE_ANNOTATION: title },
+    })
+}
+
+fn read_index(bytes: &[u8]) -> Result<Vec<Value>, Error> {
+    let index: Value = serde_json::from_slice(bytes)
+        .map_err(|e| Error::malformed(format!("oci: index.json: {}", e)))?;
+    index["manifests"]
+        .as_array()
+        .cloned()
+        .ok_or_else(|| Error::malformed("oci: index.json has no manifests"))
+}
+
+/// Store `bytes` as a blob; returns its digest and size.
+fn put_blob(layout: &Path, bytes: &[u8]) -> Result<(String, u64), Error> {
+    let d = format!("sha256:{}", hex::encode(Sha256Context::digest(bytes)));
+    write_file(&blob_path(layout, &d)?, bytes)?;
+    Ok((d, bytes.len() as u64))
+}
+
+/// Read a (small) blob and check it against its digest.
+fn read_blob(layout: &Path, d: &str) -> Result<Vec<u8>, Error> {
+    let path = blob_path(layout, d)?;
+    let bytes = fs::read(&path).map_err(|e| io_err(&path, e))?;
+    if format!("sha256:{}", hex::encode(Sha256Context::digest(&bytes))) != d {
+        return Err(Error::malformed(format!(
+            "oci: blob {} does not match its digest",
+            d
+        )));
+    }
+    Ok(bytes)
+}
+
+/// `blobs/sha256/<hex>` for `sha256:<hex>`; other algorithms are refused.
+fn blob_path(layout: &Path, d: &str) -> Result<PathBuf, Error> {
+    match d.strip_prefix("sha256:") {
+        Some(h) if h.len() == 64 && h.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f')) => {
+            Ok(layout.join("blobs").join("sha256").join(h))
+        }
+        _ => Err(Error::malformed(format!("oci: unsupported digest {:?}", d))),
+    }
+}
+
+fn write_file(path: &Path, bytes: &[u8]) -> Result<(), Error> {
+    fs::write(path, bytes).map_err(|e| io_err(path, e))
+}
+
+fn io_err(path: &Path, e: io::Error) -> Error {
+    Error::io(format!("{}: {}", path.display(), e))
+}
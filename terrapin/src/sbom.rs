@@ -0,0 +1,380 @@
+//! SPDX 2.3 and CycloneDX 1.5 SBOMs listing attested files (feature `sbom`).
+//!
+//! Neither format has a terrapin hash algorithm, so the identifier goes in
+//! each format's extension point and the native hash list holds only the
+//! optional plain SHA-256:
+//!
+//! * SPDX: one package per file (`primaryPackagePurpose` `FILE`) with an
+//!   `OTHER`/`terrapin` external reference whose locator is the identifier.
+//! * CycloneDX: one `data` component per file with a `terrapin:identifier`
+//!   property.
+//!
+//! [`Sbom::parse`] reads either back; [`SbomEntry::validate`] then checks a
+//! listed file against its identifier.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+
+use crate::tree::with_ext;
+use crate::{
+    build_from_reader_sync_with, g, profile_of, to_hex, Error, PersistedTree, Sha256Context,
+};
+
+/// SPDX external reference type carrying the identifier.
+const SPDX_REF_TYPE: &str = "terrapin";
+/// CycloneDX property carrying the identifier.
+const CDX_PROPERTY: &str = "terrapin:identifier";
+
+/// An SBOM document format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SbomFormat {
+    /// SPDX 2.3 JSON.
+    Spdx,
+    /// CycloneDX 1.5 JSON.
+    CycloneDx,
+}
+
+impl SbomFormat {
+    /// `spdx` or `cyclonedx`.
+    pub fn from_name(name: &str) -> Option<SbomFormat> {
+        match name {
+            "spdx" => Some(SbomFormat::Spdx),
+            "cyclonedx" => Some(SbomFormat::CycloneDx),
+            _ => None,
+        }
+    }
+}
+
+/// One listed file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SbomEntry {
+    /// The file's path as listed, relative to the directory it is validated in.
+    pub name: String,
+    pub identifier: String,
+    /// Lowercase hex SHA-256 of the file, if recorded.
+    pub sha256: Option<String>,
+}
+
+impl SbomEntry {
+    /// Hash the file at `path` into an entry called `name`, writing its tree
+    /// to `tree_base` (`<tree_base>.head` / `.blocks`). With `sha256` the
+    /// plain SHA-256 is computed in the same pass.
+    pub fn attest(
+        path: &Path,
+        name: &str,
+        tree_base: &Path,
+        sha256: bool,
+    ) -> Result<SbomEntry, Error> {
+        let (identifier, digest) = hash_file(path, "terrapin-sha256", Some(tree_base), sha256)?;
+        Ok(SbomEntry {
+            name: name.to_string(),
+            identifier,
+            sha256: digest,
+        })
+    }
+
+    /// Check the file at `path` against this entry. With a tree at
+    /// `tree_base` (either artifact form) it must match the identifier and
+    /// the file must validate against it; without one the file is re-hashed.
+    /// Either way a listed SHA-256 is compared too, from the same read.
+    pub fn validate(&self, path: &Path, tree_base: &Path) -> Result<(), Error> {
+        let digest = if with_ext(tree_base, "head").exists() || tree_base.is_file() {
+            let pt = PersistedTree::read(tree_base)?;
+            pt.check_against(&self.identifier)?;
+            let mut sink = self
+                .sha256
+                .is_some()
+                .then(|| Sha256Sink(Sha256Context::new()));
+            pt.validate(path, None, None, sink.as_mut().map(|s| s as &mut dyn Write))?;
+            sink.map(|s| hex::encode(s.0.finish()))
+        } else {
+            let profile = profile_of(&self.identifier).ok_or_else(|| {
+                Error::malformed(format!("sbom: unknown identifier {}", self.identifier))
+            })?;
+            let (identifier, digest) =
+                hash_file(path, profile.name(), None, self.sha256.is_some())?;
+            if identifier != self.identifier {
+                return Err(Error::mismatch(format!(
+                    "{}: identifier {} does not match listed {}",
+                    self.name, identifier, self.identifier
+                )));
+            }
+            digest
+        };
+        if digest != self.sha256 {
+            return Err(Error::validation(format!(
+                "{}: SHA-256 does not match",
+                self.name
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// An SBOM: a document name and the files it lists.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sbom {
+    pub name: String,
+    pub entries: Vec<SbomEntry>,
+}
+
+impl Sbom {
+    /// The document as JSON. The creation time is `SOURCE_DATE_EPOCH` when
+    /// set, else now; the SPDX namespace is derived from the entries.
+    pub fn to_json(&self, format: SbomFormat) -> Vec<u8> {
+        let created = timestamp();
+        let v = match format {
+            SbomFormat::Spdx => self.spdx(&created),
+            SbomFormat::CycloneDx => self.cyclonedx(&created),
+        };
+        serde_json::to_vec_pretty(&v).expect("sbom serializes")
+    }
+
+    fn spdx(&self, created: &str) -> Value {
+        let packages: Vec<Value> = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, e)| {
+                let mut p = json!({
+                    "SPDXID": format!("SPDXRef-File-{}", i + 1),
+                    "name": e.name,
+                    "packageFileName": e.name,
+                    "downloadLocation": "NOASSERTION",
+                    "filesAnalyzed": false,
+                    "primaryPackagePurpose": "FILE",
+                    "externalRefs": [{
+                        "referenceCategory": "OTHER",
+                        "referenceType": SPDX_REF_TYPE,
+                        "referenceLocator": e.identifier,
+                    }],
+                });
+                if let Some(sha) = &e.sha256 {
+                    p["checksums"] = json!([{ "algorithm": "SHA256", "checksumValue": sha }]);
+                }
+                p
+            })
+            .collect();
+        let relationships: Vec<Value> = (1..=self.entries.len())
+            .map(|i| {
+                json!({
+                    "spdxElementId": "SPDXRef-DOCUMENT",
+                    "relationshipType": "DESCRIBES",
+                    "relatedSpdxElement": format!("SPDXRef-File-{}", i),
+                })
+            })
+            .collect();
+        json!({
+            "spdxVersion": "SPDX-2.3",
+            "dataLicense": "CC0-1.0",
+            "SPDXID": "SPDXRef-DOCUMENT",
+            "name": self.name,
+            "documentNamespace": format!(
+                "https://terrapin.io/spdx/{}-{}",
+                self.name.replace(|c: char| !c.is_ascii_alphanumeric(), "-"),
+                self.digest()
+            ),
+            "creationInfo": {
+                "created": created,
+                "creators": [format!("Tool: terrapin-{}", env!("CARGO_PKG_VERSION"))],
+            },
+            "packages": packages,
+            "relationships": relationships,
+        })
+    }
+
+    fn cyclonedx(&self, created: &str) -> Value {
+        let components: Vec<Value> = self
+            .entries
+            .iter()
+            .map(|e| {
+                let mut c = json!({
+                    "type": "data",
+                    "bom-ref": e.name,
+                    "name": e.name,
+                    "properties": [{ "name": CDX_PROPERTY, "value": e.identifier }],
+                });
+                if let Some(sha) = &e.sha256 {
+                    c["hashes"] = json!([{ "alg": "SHA-256", "content": sha }]);
+                }
+                c
+            })
+            .collect();
+        json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "version": 1,
+            "metadata": {
+                "timestamp": created,
+                "tools": [{ "name": "terrapin", "version": env!("CARGO_PKG_VERSION") }],
+                "component": { "type": "data", "name": self.name },
+            },
+            "components": components,
+        })
+    }
+
+    /// A digest of the listed names and identifiers.
+    fn digest(&self) -> String {
+        let mut s = String::new();
+        for e in &self.entries {
+            s.push_str(&format!("{} {}\n", e.identifier, e.name));
+        }
+        to_hex(&g(s.as_bytes()))
+    }
+
+    /// Parse an SPDX 2.3 or CycloneDX 1.5 JSON document. Only entries with a
+    /// terrapin identifier are returned.
+    pub fn parse(bytes: &[u8]) -> Result<Sbom, Error> {
+        let v: Value =
+            serde_json::from_slice(bytes).map_err(|e| Error::malformed(format!("sbom: {}", e)))?;
+        let (name, items, format) = if v["spdxVersion"].is_string() {
+            (&v["name"], &v["packages"], SbomFormat::Spdx)
+        } else if v["bomFormat"] == "CycloneDX" {
+            (
+                &v["metadata"]["component"]["name"],
+                &v["components"],
+                SbomFormat::CycloneDx,
+            )
+        } else {
+            return Err(Error::malformed("sbom: neither SPDX nor CycloneDX JSON"));
+        };
+        let entries = items
+            .as_array()
+            .map(|a| a.iter().filter_map(|item| entry(item, format)).collect())
+            .unwrap_or_default();
+        Ok(Sbom {
+            name: name.as_str().unwrap_or_default().to_string(),
+            entries,
+        })
+    }
+}
+
+/// The entry an SPDX package or CycloneDX component describes, if it has a
+/// terrapin identifier.
+fn entry(item: &Value, format: SbomFormat) -> Option<SbomEntry> {
+    let (identifier, hashes, alg, value) = match format {
+        SbomFormat::Spdx => (
+            item["externalRefs"]
+                .as_array()?
+                .iter()
+                .find(|r| r["referenceType"] == SPDX_REF_TYPE)?["referenceLocator"]
+                .as_str()?,
+            &item["checksums"],
+            ("algorithm", "SHA256"),
+            "checksumValue",
+        ),
+        SbomFormat::CycloneDx => (
+            item["properties"]
+                .as_array()?
+                .iter()
+                .find(|p| p["name"] == CDX_PROPERTY)?["value"]
+                .as_str()?,
+            &item["hashes"],
+            ("alg", "SHA-256"),
+            "content",
+        ),
+    };
+    let sha256 = hashes.as_array().and_then(|hs| {
+        hs.iter()
+            .find(|h| h[alg.0] == alg.1)
+            .and_then(|h| h[value].as_str())
+            .map(str::to_ascii_lowercase)
+    });
+    Some(SbomEntry {
+        name: item["name"].as_str()?.to_string(),
+        identifier: identifier.to_string(),
+        sha256,
+    })
+}
+
+/// Reader that feeds everything read through a SHA-256 context.
+struct Sha256Tee<R> {
+    inner: R,
+    ctx: Option<Sha256Context>,
+}
+
+impl<R: Read> Read for Sha256Tee<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if let Some(ctx) = &mut self.ctx {
+            ctx.update(&buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+/// Writer that only feeds a SHA-256 context, for bytes a tree has verified.
+struct Sha256Sink(Sha256Context);
+
+impl Write for Sha256Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Identifier (under `profile`) and optional SHA-256 of the file at `path`,
+/// in one read; writes the tree to `tree_base` if given.
+fn hash_file(
+    path: &Path,
+    profile: &str,
+    tree_base: Option<&Path>,
+    sha256: bool,
+) -> Result<(String, Option<String>), Error> {
+    let profile = crate::profile_by_name(profile)
+        .ok_or_else(|| Error::other(format!("unknown profile {}", profile)))?;
+    let file = File::open(path)
+        .map_err(|e| Error::io(format!("cannot open {}: {}", path.display(), e)))?;
+    let mut tee = Sha256Tee {
+        inner: file,
+        ctx: sha256.then(Sha256Context::new),
+    };
+    let tree = build_from_reader_sync_with(profile, &mut tee)
+        .map_err(|e| Error::io(format!("hashing {} failed: {}", path.display(), e)))?;
+    if let Some(base) = tree_base {
+        PersistedTree::write(base, &tree)
+            .map_err(|e| Error::io(format!("writing tree {} failed: {}", base.display(), e)))?;
+    }
+    let digest = tee.ctx.map(|c| hex::encode(c.finish()));
+    Ok((tree.identifier(), digest))
+}
+
+/// RFC 3339 UTC time of `SOURCE_DATE_EPOCH`, else of now.
+fn timestamp() -> String {
+    let secs = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs())
+        });
+    // Days since 1970-01-01 to a civil date (Howard Hinnant's algorithm).
+    let z = (secs / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    let rem = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
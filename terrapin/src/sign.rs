@@ -0,0 +1,514 @@
+//! Signed statements over identifiers (feature `sign`).
+//!
+//! A signer vouches for a dataset by signing a canonical statement:
+//!
+//! ```text
+//! terrapin-statement: 1
+//! identifier: terrapin-sha256:<64 hex>
+//! length: <decimal>
+//! meta.<key>: <value>
+//! ```
+//!
+//! `meta.*` lines are optional, sorted by key and unique. The signature covers
+//! the exact statement bytes; a signature file is the statement followed by
+//! `signature-algorithm: <alg>` and `signature: <hex>` lines. Verifying it
+//! against a trusted public key yields the identifier to pass to
+//! [`crate::PersistedTree::check_against`], so the identifier no longer has
+//! to travel out-of-band.
+//!
+//! Algorithms are Ed25519 and ECDSA P-256 with SHA-256. Keys are stored as
+//! text: `.key` holds the PKCS#8 secret key, `.pub` the raw public key, both
+//! hex-encoded.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use ring::digest::{digest, SHA256};
+use ring::rand::SystemRandom;
+use ring::signature::{
+    EcdsaKeyPair, Ed25519KeyPair, KeyPair, UnparsedPublicKey, ECDSA_P256_SHA256_FIXED,
+    ECDSA_P256_SHA256_FIXED_SIGNING, ED25519,
+};
+
+use crate::Error;
+
+const STATEMENT_HEADER: &str = "terrapin-statement: 1\n";
+const SECRET_HEADER: &str = "terrapin-secret-key: 1\n";
+const PUBLIC_HEADER: &str = "terrapin-public-key: 1\n";
+
+/// A signature algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignatureAlgorithm {
+    Ed25519,
+    /// ECDSA over P-256 with SHA-256, fixed-length `r || s` signatures.
+    EcdsaP256,
+}
+
+impl SignatureAlgorithm {
+    /// `ed25519` or `ecdsa-p256`.
+    pub fn name(self) -> &'static str {
+        match self {
+            SignatureAlgorithm::Ed25519 => "ed25519",
+            SignatureAlgorithm::EcdsaP256 => "ecdsa-p256",
+        }
+    }
+
+    /// The algorithm called `name`, if supported.
+    pub fn from_name(name: &str) -> Option<SignatureAlgorithm> {
+        match name {
+            "ed25519" => Some(SignatureAlgorithm::Ed25519),
+            "ecdsa-p256" => Some(SignatureAlgorithm::EcdsaP256),
+            _ => None,
+        }
+    }
+}
+
+/// What a signer vouches for: an identifier, its length, and metadata.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Statement {
+    pub identifier: String,
+    pub length: u64,
+    /// `meta.<key>` lines, in key order.
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl Statement {
+    /// A statement with no metadata; rejects a malformed identifier.
+    pub fn new(identifier: &str, length: u64) -> Result<Statement, Error> {
+        check_identifier(identifier)?;
+        Ok(Statement {
+            identifier: identifier.to_string(),
+            length,
+            metadata: BTreeMap::new(),
+        })
+    }
+
+    /// Add (or replace) metadata `key: value`. Keys are `[a-z0-9][a-z0-9._-]*`;
+    /// values are non-empty printable text without surrounding whitespace.
+    pub fn with_metadata(mut self, key: &str, value: &str) -> Result<Statement, Error> {
+        check_meta(key, value)?;
+        self.metadata.insert(key.to_string(), value.to_string());
+        Ok(self)
+    }
+
+    /// Canonical statement bytes (what is signed).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut s = String::from(STATEMENT_HEADER);
+        s.push_str(&format!("identifier: {}\n", self.identifier));
+        s.push_str(&format!("length: {}\n", self.length));
+        for (k, v) in &self.metadata {
+            s.push_str(&format!("meta.{}: {}\n", k, v));
+        }
+        s.into_bytes()
+    }
+
+    /// Parse statement bytes, accepting only the exact canonical form.
+    pub fn parse(bytes: &[u8]) -> Result<Statement, Error> {
+        let text =
+            std::str::from_utf8(bytes).map_err(|_| Error::malformed("statement: not utf-8"))?;
+        let body = text
+            .strip_prefix(STATEMENT_HEADER)
+            .ok_or_else(|| Error::malformed("statement: missing terrapin-statement header"))?;
+        if !body.ends_with('\n') {
+            return Err(Error::malformed("statement: missing final newline"));
+        }
+        let mut lines = body.split_terminator('\n');
+        let identifier = lines
+            .next()
+            .and_then(|l| l.strip_prefix("identifier: "))
+            .ok_or_else(|| Error::malformed("statement: missing identifier"))?;
+        let length = lines
+            .next()
+            .and_then(|l| l.strip_prefix("length: "))
+            .ok_or_else(|| Error::malformed("statement: missing length"))?;
+        let canonical_len = !length.is_empty()
+            && length.bytes().all(|c| c.is_ascii_digit())
+            && (length == "0" || !length.starts_with('0'));
+        let length: u64 = match length.parse() {
+            Ok(n) if canonical_len => n,
+            _ => {
+                return Err(Error::malformed(format!(
+                    "statement: bad length {:?}",
+                    length
+                )))
+            }
+        };
+        let mut st = Statement::new(identifier, length)?;
+        let mut last: Option<&str> = None;
+        for line in lines {
+            let (key, value) = line
+                .strip_prefix("meta.")
+                .and_then(|l| l.split_once(": "))
+                .ok_or_else(|| {
+                    Error::malformed(format!("statement: unexpected line {:?}", line))
+                })?;
+            if last.is_some_and(|p| p.as_bytes() >= key.as_bytes()) {
+                return Err(Error::malformed(format!(
+                    "statement: meta.{} out of order or repeated",
+                    key
+                )));
+            }
+            last = Some(key);
+            st = st.with_metadata(key, value)?;
+        }
+        Ok(st)
+    }
+}
+
+/// A secret signing key.
+pub struct SecretKey {
+    algorithm: SignatureAlgorithm,
+    pkcs8: Vec<u8>,
+}
+
+impl SecretKey {
+    /// A fresh key from the system random source.
+    pub fn generate(algorithm: SignatureAlgorithm) -> Result<SecretKey, Error> {
+        let rng = SystemRandom::new();
+        let doc = match algorithm {
+            SignatureAlgorithm::Ed25519 => Ed25519KeyPair::generate_pkcs8(&rng),
+            SignatureAlgorithm::EcdsaP256 => {
+                EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+            }
+        }
+        .map_err(|_| Error::other("key generation failed"))?;
+        Ok(SecretKey {
+            algorithm,
+            pkcs8: doc.as_ref().to_vec(),
+        })
+    }
+
+    /// A key from its PKCS#8 (v2 for Ed25519) document.
+    pub fn from_pkcs8(algorithm: SignatureAlgorithm, pkcs8: &[u8]) -> Result<SecretKey, Error> {
+        let key = SecretKey {
+            algorithm,
+            pkcs8: pkcs8.to_vec(),
+        };
+        key.public_key()?;
+        Ok(key)
+    }
+
+    pub fn algorithm(&self) -> SignatureAlgorithm {
+        self.algorithm
+    }
+
+    /// The matching public key.
+    pub fn public_key(&self) -> Result<PublicKey, Error> {
+        let key = match self.algorithm {
+            SignatureAlgorithm::Ed25519 => {
+                Ed25519KeyPair::from_pkcs8(&self.pkcs8).map(|k| k.public_key().as_ref().to_vec())
+            }
+            SignatureAlgorithm::EcdsaP256 => EcdsaKeyPair::from_pkcs8(
+                &ECDSA_P256_SHA256_FIXED_SIGNING,
+                &self.pkcs8,
+                &SystemRandom::new(),
+            )
+            .map(|k| k.public_key().as_ref().to_vec()),
+        }
+        .map_err(|e| {
+            Error::malformed(format!(
+                "key: invalid {} secret key: {}",
+                self.algorithm.name(),
+                e
+            ))
+        })?;
+        Ok(PublicKey {
+            algorithm: self.algorithm,
+            key,
+        })
+    }
+
+    /// Sign `statement`.
+    pub fn sign(&self, statement: &Statement) -> Result<SignedStatement, Error> {
+        Ok(SignedStatement {
+            statement: statement.clone(),
+            algorithm: self.algorithm,
+            signature: self.sign_bytes(&statement.to_bytes())?,
+        })
+    }
+
+    /// Sign arbitrary bytes; Ed25519 signatures are 64 bytes, P-256 ones the
+    /// fixed-length 64-byte `r || s`.
+    pub fn sign_bytes(&self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        let rng = SystemRandom::new();
+        match self.algorithm {
+            SignatureAlgorithm::Ed25519 => Ed25519KeyPair::from_pkcs8(&self.pkcs8)
+                .map_err(|e| e.to_string())
+                .map(|k| k.sign(msg).as_ref().to_vec()),
+            SignatureAlgorithm::EcdsaP256 => {
+                EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &self.pkcs8, &rng)
+                    .map_err(|e| e.to_string())
+                    .and_then(|k| k.sign(&rng, msg).map_err(|_| "signing failed".to_string()))
+                    .map(|s| s.as_ref().to_vec())
+            }
+        }
+        .map_err(|e| Error::other(format!("sign: {}", e)))
+    }
+
+    /// The `.key` file text.
+    pub fn to_text(&self) -> String {
+        format!(
+            "{}algorithm: {}\npkcs8: {}\n",
+            SECRET_HEADER,
+            self.algorithm.name(),
+            hex::encode(&self.pkcs8)
+        )
+    }
+
+    /// Parse `.key` file text.
+    pub fn parse(text: &str) -> Result<SecretKey, Error> {
+        let (algorithm, value) = parse_key_text(text, SECRET_HEADER, "pkcs8")?;
+        SecretKey::from_pkcs8(algorithm, &value)
+    }
+
+    /// Read a `.key` file.
+    pub fn read(path: &Path) -> Result<SecretKey, Error> {
+        SecretKey::parse(&read_text(path)?)
+    }
+
+    /// Write a `.key` file, readable only by its owner on Unix.
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        write_private(path, self.to_text().as_bytes())
+            .map_err(|e| Error::io(format!("cannot write {}: {}", path.display(), e)))
+    }
+}
+
+/// A public verification key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublicKey {
+    algorithm: SignatureAlgorithm,
+    key: Vec<u8>,
+}
+
+impl PublicKey {
+    /// A key from its raw encoding: 32 bytes for Ed25519, the 65-byte
+    /// uncompressed point for P-256.
+    pub fn from_bytes(algorithm: SignatureAlgorithm, key: &[u8]) -> Result<PublicKey, Error> {
+        let want = match algorithm {
+            SignatureAlgorithm::Ed25519 => 32,
+            SignatureAlgorithm::EcdsaP256 => 65,
+        };
+        if key.len() != want {
+            return Err(Error::malformed(format!(
+                "key: {} public key must be {} bytes",
+                algorithm.name(),
+                want
+            )));
+        }
+        Ok(PublicKey {
+            algorithm,
+            key: key.to_vec(),
+        })
+    }
+
+    pub fn algorithm(&self) -> SignatureAlgorithm {
+        self.algorithm
+    }
+
+    /// The raw key bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Key id: hex SHA-256 of the raw key bytes.
+    pub fn key_id(&self) -> String {
+        hex::encode(digest(&SHA256, &self.key))
+    }
+
+    /// Check `signature` over `msg` (see [`SecretKey::sign_bytes`]).
+    pub fn verify_bytes(&self, msg: &[u8], signature: &[u8]) -> Result<(), Error> {
+        let alg: &dyn ring::signature::VerificationAlgorithm = match self.algorithm {
+            SignatureAlgorithm::Ed25519 => &ED25519,
+            SignatureAlgorithm::EcdsaP256 => &ECDSA_P256_SHA256_FIXED,
+        };
+        UnparsedPublicKey::new(alg, &self.key)
+            .verify(msg, signature)
+            .map_err(|_| Error::signature("signature: does not verify with this key"))
+    }
+
+    /// The `.pub` file text.
+    pub fn to_text(&self) -> String {
+        format!(
+            "{}algorithm: {}\nkey: {}\n",
+            PUBLIC_HEADER,
+            self.algorithm.name(),
+            hex::encode(&self.key)
+        )
+    }
+
+    /// Parse `.pub` file text.
+    pub fn parse(text: &str) -> Result<PublicKey, Error> {
+        let (algorithm, value) = parse_key_text(text, PUBLIC_HEADER, "key")?;
+        PublicKey::from_bytes(algorithm, &value)
+    }
+
+    /// Read a `.pub` file.
+    pub fn read(path: &Path) -> Result<PublicKey, Error> {
+        PublicKey::parse(&read_text(path)?)
+    }
+}
+
+/// A statement with its signature, as carried in a signature file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedStatement {
+    pub statement: Statement,
+    pub algorithm: SignatureAlgorithm,
+    pub signature: Vec<u8>,
+}
+
+impl SignedStatement {
+    /// Check the signature with `key` and return the statement it covers.
+    /// Only a statement returned from here is trustworthy.
+    pub fn verify(&self, key: &PublicKey) -> Result<&Statement, Error> {
+        if key.algorithm != self.algorithm {
+            return Err(Error::signature(format!(
+                "signature: made with {}, key is {}",
+                self.algorithm.name(),
+                key.algorithm.name()
+            )));
+        }
+        key.verify_bytes(&self.statement.to_bytes(), &self.signature)?;
+        Ok(&self.statement)
+    }
+
+    /// The signature file text.
+    pub fn to_text(&self) -> String {
+        let mut s = String::from_utf8(self.statement.to_bytes()).expect("statement is utf-8");
+        s.push_str(&format!(
+            "signature-algorithm: {}\nsignature: {}\n",
+            self.algorithm.name(),
+            hex::encode(&self.signature)
+        ));
+        s
+    }
+
+    /// Parse signature file text. The signature is not checked here; call
+    /// [`Self::verify`].
+    pub fn parse(text: &str) -> Result<SignedStatement, Error> {
+        let at = text
+            .rfind("\nsignature-algorithm: ")
+            .ok_or_else(|| Error::signature("signature: missing signature-algorithm"))?;
+        let (statement, trailer) = text.split_at(at + 1);
+        let statement = Statement::parse(statement.as_bytes())?;
+        let mut lines = trailer.split_terminator('\n');
+        let algorithm = lines
+            .next()
+            .and_then(|l| l.strip_prefix("signature-algorithm: "))
+            .and_then(SignatureAlgorithm::from_name)
+            .ok_or_else(|| Error::signature("signature: unsupported signature-algorithm"))?;
+        let signature = lines
+            .next()
+            .and_then(|l| l.strip_prefix("signature: "))
+            .and_then(|h| hex::decode(h).ok())
+            .ok_or_else(|| Error::signature("signature: bad signature line"))?;
+        if lines.next().is_some() || !trailer.ends_with('\n') {
+            return Err(Error::signature("signature: trailing data"));
+        }
+        Ok(SignedStatement {
+            statement,
+            algorithm,
+            signature,
+        })
+    }
+
+    /// Read a signature file.
+    pub fn read(path: &Path) -> Result<SignedStatement, Error> {
+        SignedStatement::parse(&read_text(path)?)
+    }
+}
+
+fn read_text(path: &Path) -> Result<String, Error> {
+    fs::read_to_string(path)
+        .map_err(|e| Error::io(format!("cannot read {}: {}", path.display(), e)))
+}
+
+/// `<header>algorithm: <alg>\n<field>: <hex>\n` -> `(alg, bytes)`.
+fn parse_key_text(
+    text: &str,
+    header: &str,
+    field: &str,
+) -> Result<(SignatureAlgorithm, Vec<u8>), Error> {
+    let body = text
+        .strip_prefix(header)
+        .ok_or_else(|| Error::malformed(format!("key: missing {:?} header", header.trim_end())))?;
+    let mut lines = body.lines();
+    let algorithm = lines
+        .next()
+        .and_then(|l| l.strip_prefix("algorithm: "))
+        .and_then(SignatureAlgorithm::from_name)
+        .ok_or_else(|| Error::malformed("key: unsupported algorithm"))?;
+    let value = lines
+        .next()
+        .and_then(|l| l.strip_prefix(field))
+        .and_then(|l| l.strip_prefix(": "))
+        .and_then(|h| hex::decode(h).ok())
+        .ok_or_else(|| Error::malformed(format!("key: bad {} line", field)))?;
+    if lines.next().is_some() {
+        return Err(Error::malformed("key: trailing data"));
+    }
+    Ok((algorithm, value))
+}
+
+fn check_identifier(id: &str) -> Result<(), Error> {
+    let ok = id.split_once(':').is_some_and(|(name, hex)| {
+        !name.is_empty()
+            && name
+                .bytes()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'-')
+            && hex.len() == 64
+            && hex.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f'))
+    });
+    if !ok {
+        return Err(Error::malformed(format!(
+            "statement: bad identifier {:?}",
+            id
+        )));
+    }
+    Ok(())
+}
+
+fn check_meta(key: &str, value: &str) -> Result<(), Error> {
+    let key_ok = key
+        .bytes()
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && key.bytes().all(|c| {
+            c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, b'.' | b'_' | b'-')
+        });
+    if !key_ok {
+        return Err(Error::malformed(format!(
+            "statement: bad metadata key {:?}",
+            key
+        )));
+    }
+    let value_ok =
+        !value.is_empty() && value == value.trim() && !value.chars().any(|c| c.is_control());
+    if !value_ok {
+        return Err(Error::malformed(format!(
+            "statement: bad metadata value for {:?}",
+            key
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+    let mut f = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    // `mode` only applies to a new file: an existing one keeps its own.
+    f.set_permissions(fs::Permissions::from_mode(0o600))?;
+    f.write_all(bytes)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    fs::write(path, bytes)
+}
@@ -0,0 +1,96 @@
+//! Where a seekable source holds data, so hashing can skip its holes.
+//!
+//! A hole reads as zeros, and a block lying wholly in one hashes to a leaf
+//! that is a constant of the profile: [`crate::build_from_file_sync`] uses
+//! [`SparseSource::data_region`] to recognize such blocks without reading
+//! them. [`File`] answers with `SEEK_DATA` / `SEEK_HOLE` on Linux; elsewhere,
+//! and on filesystems without hole reporting, the whole file is data. A source
+//! without a length (a pipe, a terminal) is read as a plain stream.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// A seekable source that can report its data regions.
+pub trait SparseSource: Read + Seek {
+    /// The first data region at or after `offset`, as `(start, end)` with
+    /// `start >= offset` and everything in `[offset, start)` reading as
+    /// zeros; `None` if only zeros follow `offset`. Reporting holes is
+    /// optional: `Some((offset, u64::MAX))` is always correct.
+    fn data_region(&mut self, offset: u64) -> io::Result<Option<(u64, u64)>>;
+
+    /// The source's length if its blocks can be read by offset, or `None` to
+    /// read it from its current position as a plain stream. By default, its
+    /// end if it can seek there.
+    fn length(&mut self) -> Option<u64> {
+        self.seek(SeekFrom::End(0)).ok()
+    }
+}
+
+impl<S: SparseSource + ?Sized> SparseSource for &mut S {
+    fn data_region(&mut self, offset: u64) -> io::Result<Option<(u64, u64)>> {
+        (**self).data_region(offset)
+    }
+
+    fn length(&mut self) -> Option<u64> {
+        (**self).length()
+    }
+}
+
+/// A file's length if it is a regular file or a block device; a pipe, socket
+/// or character device may not seek, or seek but report no length.
+fn file_length(file: &mut File) -> Option<u64> {
+    let kind = file.metadata().ok()?.file_type();
+    #[cfg(unix)]
+    let device = std::os::unix::fs::FileTypeExt::is_block_device(&kind);
+    #[cfg(not(unix))]
+    let device = false;
+    if !kind.is_file() && !device {
+        return None;
+    }
+    file.seek(SeekFrom::End(0)).ok()
+}
+
+#[cfg(target_os = "linux")]
+impl SparseSource for File {
+    fn data_region(&mut self, offset: u64) -> io::Result<Option<(u64, u64)>> {
+        use std::os::unix::io::AsRawFd;
+
+        let fd = self.as_raw_fd();
+        let seek = |offset: u64, whence| {
+            let off = libc::off_t::try_from(offset)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "offset too large"))?;
+            // SAFETY: lseek on an open descriptor; it only moves the file
+            // position, which every read here sets explicitly first.
+            let r = unsafe { libc::lseek(fd, off, whence) };
+            if r < 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(r as u64)
+            }
+        };
+        let start = match seek(offset, libc::SEEK_DATA) {
+            Ok(start) => start,
+            // No data at or after `offset`.
+            Err(e) if e.raw_os_error() == Some(libc::ENXIO) => return Ok(None),
+            // No hole reporting here (e.g. not a regular file): all data.
+            Err(_) => return Ok(Some((offset, u64::MAX))),
+        };
+        let end = seek(start, libc::SEEK_HOLE).unwrap_or(u64::MAX);
+        Ok(Some((start, end.max(start))))
+    }
+
+    fn length(&mut self) -> Option<u64> {
+        file_length(self)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl SparseSource for File {
+    fn data_region(&mut self, offset: u64) -> io::Result<Option<(u64, u64)>> {
+        Ok(Some((offset, u64::MAX)))
+    }
+
+    fn length(&mut self) -> Option<u64> {
+        file_length(self)
+    }
+}
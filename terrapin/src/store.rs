@@ -0,0 +1,585 @@
+//! Content-addressed local store keyed by Terrapin identifier.
+//!
+//! Layout under the store root:
+//!
+//! * `objects/<algorithm>/<hex>/data` — the dataset bytes.
+//! * `objects/<algorithm>/<hex>/tree.head`, `tree.blocks` — its persisted tree.
+//! * `refs/<name>` — one line, the identifier a human-readable name points at.
+//! * `tmp/` — staging for in-flight ingests.
+//! * `config` — `mode: whole|blocks`; absent means `whole`.
+//! * `lock` — empty; locked shared by each `add`, exclusively by `gc`.
+//!
+//! In [`StoreMode::Blocks`] an object keeps no `data` file. Each unique data
+//! block is stored once as `blocks/<hh>/<leaf hex>`, named by its leaf hash
+//! `g(block)`, and a dataset is reassembled from the leaf layer of its
+//! `tree.blocks` — so snapshots that share most of their blocks share most of
+//! their storage. Every reassembled block is still verified up to the tree
+//! root before it is returned.
+//!
+//! An object is staged under `tmp/` and renamed into place whole, so readers
+//! never observe a half-written entry. Ingesting data whose identifier is
+//! already present only updates the ref (deduplication by identifier).
+//! Objects no ref points at, and blocks no object uses, are removed by
+//! [`Store::gc`]. An `add` holds the `lock` file shared from staging until its
+//! ref is written, and `gc` holds it exclusively, so a collection in any
+//! process waits for in-flight ingests: it never removes their staging, nor an
+//! object committed but not yet referenced.
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use terrapin_core::{profile_by_name, to_hex, Error, BLOCK, FANOUT};
+
+use crate::stream::build_from_reader_sync;
+use crate::tree::PersistedTree;
+
+static STAGING: AtomicU64 = AtomicU64::new(0);
+
+/// How a store keeps dataset bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StoreMode {
+    /// One `data` file per object.
+    Whole,
+    /// Each unique data block once, keyed by its leaf hash.
+    Blocks,
+}
+
+impl StoreMode {
+    /// The name used in the store's `config`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StoreMode::Whole => "whole",
+            StoreMode::Blocks => "blocks",
+        }
+    }
+}
+
+/// A handle on a store directory.
+pub struct Store {
+    root: PathBuf,
+    mode: StoreMode,
+}
+
+/// The outcome of [`Store::add`].
+pub struct Added {
+    pub identifier: String,
+    pub length: u64,
+    /// `true` when the object was already present and only the ref changed.
+    pub deduplicated: bool,
+}
+
+/// One stored object, as listed by [`Store::list`].
+pub struct StoreEntry {
+    pub identifier: String,
+    pub length: u64,
+    /// Names of the refs pointing at this object, sorted.
+    pub refs: Vec<String>,
+}
+
+/// Space accounting, as reported by [`Store::stats`].
+pub struct StoreStats {
+    pub objects: u64,
+    /// Sum of the lengths of all stored datasets.
+    pub logical_bytes: u64,
+    /// Dataset bytes actually on disk (`data` files or unique blocks).
+    pub stored_bytes: u64,
+    /// Data blocks across all datasets (leaf-layer entries).
+    pub blocks: u64,
+    /// Distinct data blocks (by leaf hash) across all datasets.
+    pub unique_blocks: u64,
+}
+
+impl StoreStats {
+    /// `logical_bytes / stored_bytes`; 1.0 for an empty store.
+    pub fn dedup_ratio(&self) -> f64 {
+        if self.stored_bytes == 0 {
+            return 1.0;
+        }
+        self.logical_bytes as f64 / self.stored_bytes as f64
+    }
+}
+
+/// Copies everything read through it into `copy`, so one pass both hashes
+/// and stages the data.
+struct TeeReader {
+    inner: File,
+    copy: File,
+}
+
+impl Read for TeeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.copy.write_all(&buf[..n])?;
+        Ok(n)
+    }
+}
+
+impl Store {
+    /// Open (creating if needed) the store rooted at `root`, in the mode it
+    /// was initialised with ([`StoreMode::Whole`] if never initialised).
+    pub fn open(root: &Path) -> Result<Store, Error> {
+        for sub in ["objects", "refs", "tmp", "blocks"] {
+            let dir = root.join(sub);
+            fs::create_dir_all(&dir)
+                .map_err(|e| Error::io(format!("cannot create {}: {}", dir.display(), e)))?;
+        }
+        let config = root.join("config");
+        let mode = match fs::read_to_string(&config) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => StoreMode::Whole,
+            Err(e) => {
+                return Err(Error::io(format!(
+                    "cannot read {}: {}",
+                    config.display(),
+                    e
+                )))
+            }
+            Ok(text) => match text.trim_end() {
+                "mode: whole" => StoreMode::Whole,
+                "mode: blocks" => StoreMode::Blocks,
+                other => return Err(Error::malformed(format!("store: bad config {:?}", other))),
+            },
+        };
+        Ok(Store {
+            root: root.to_path_buf(),
+            mode,
+        })
+    }
+
+    /// Open the store at `root`, fixing its mode if it is new. An existing
+    /// store in a different mode is an error; stores are not converted.
+    pub fn init(root: &Path, mode: StoreMode) -> Result<Store, Error> {
+        let mut store = Store::open(root)?;
+        let config = root.join("config");
+        if config.exists() || !store.objects()?.is_empty() {
+            if store.mode != mode {
+                return Err(Error::malformed(format!(
+                    "store: {} is a {} store",
+                    root.display(),
+                    store.mode.as_str()
+                )));
+            }
+            return Ok(store);
+        }
+        fs::write(&config, format!("mode: {}\n", mode.as_str()))
+            .map_err(|e| Error::io(format!("cannot write {}: {}", config.display(), e)))?;
+        store.mode = mode;
+        Ok(store)
+    }
+
+    /// The mode this store keeps data in.
+    pub fn mode(&self) -> StoreMode {
+        self.mode
+    }
+
+    /// Ingest the file at `data`, pointing ref `name` (default: the file name)
+    /// at its identifier.
+    pub fn add(&self, data: &Path, name: Option<&str>) -> Result<Added, Error> {
+        let name = match name {
+            Some(n) => n.to_string(),
+            None => data
+                .file_name()
+                .and_then(|n| n.to_str())
+                .ok_or_else(|| {
+                    Error::other(format!("{}: cannot derive a ref name", data.display()))
+                })?
+                .to_string(),
+        };
+        check_ref_name(&name)?;
+
+        let _lock = self.lock(false)?;
+        let stage = self.staging_dir()?;
+        let result = self.ingest(data, &stage);
+        let added = match result {
+            Ok(a) => a,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&stage);
+                return Err(e);
+            }
+        };
+        self.set_ref(&name, &added.identifier)?;
+        Ok(added)
+    }
+
+    fn ingest(&self, data: &Path, stage: &Path) -> Result<Added, Error> {
+        let inner = File::open(data)
+            .map_err(|e| Error::io(format!("cannot open {}: {}", data.display(), e)))?;
+        let copy = File::create(stage.join("data"))
+            .map_err(|e| Error::io(format!("cannot stage {}: {}", data.display(), e)))?;
+        let tree = build_from_reader_sync(TeeReader { inner, copy })
+            .map_err(|e| Error::io(format!("hashing failed: {}", e)))?;
+        let identifier = tree.identifier();
+
+        let dest = self.object_dir(&identifier)?;
+        if dest.exists() {
+            let _ = fs::remove_dir_all(stage);
+            return Ok(Added {
+                identifier,
+                length: tree.length,
+                deduplicated: true,
+            });
+        }
+        if self.mode == StoreMode::Blocks {
+            // Blocks go in before the object that needs them becomes visible.
+            self.put_blocks(&stage.join("data"), &tree.layers[0])?;
+            let _ = fs::remove_file(stage.join("data"));
+        }
+        PersistedTree::write(&stage.join("tree"), &tree)
+            .map_err(|e| Error::io(format!("writing tree failed: {}", e)))?;
+        let parent = dest.parent().expect("object dir has a parent");
+        fs::create_dir_all(parent)
+            .map_err(|e| Error::io(format!("cannot create {}: {}", parent.display(), e)))?;
+        if let Err(e) = fs::rename(stage, &dest) {
+            // Lost a race with a concurrent ingest of the same data.
+            if !dest.exists() {
+                return Err(Error::io(format!(
+                    "cannot commit {}: {}",
+                    dest.display(),
+                    e
+                )));
+            }
+            let _ = fs::remove_dir_all(stage);
+            return Ok(Added {
+                identifier,
+                length: tree.length,
+                deduplicated: true,
+            });
+        }
+        Ok(Added {
+            identifier,
+            length: tree.length,
+            deduplicated: false,
+        })
+    }
+
+    /// File each not-yet-stored block of the staged `data` under its leaf hash.
+    fn put_blocks(&self, data: &Path, leaves: &[u8]) -> Result<(), Error> {
+        let mut f =
+            File::open(data).map_err(|e| Error::io(format!("cannot open staged data: {}", e)))?;
+        let mut buf = vec![0u8; BLOCK];
+        for leaf in leaves.chunks_exact(32) {
+            let n = read_full(&mut f, &mut buf)
+                .map_err(|e| Error::io(format!("staged data: {}", e)))?;
+            let path = self.block_path(leaf);
+            if path.exists() {
+                continue;
+            }
+            let parent = path.parent().expect("block path has a parent");
+            let tmp = self.staging_name("blk");
+            fs::create_dir_all(parent)
+                .and_then(|_| fs::write(&tmp, &buf[..n]))
+                .and_then(|_| fs::rename(&tmp, &path))
+                .map_err(|e| Error::io(format!("cannot store block {}: {}", to_hex(leaf), e)))?;
+        }
+        Ok(())
+    }
+
+    /// Path of the stored data for `identifier` (whole mode only).
+    pub fn data_path(&self, identifier: &str) -> Result<PathBuf, Error> {
+        let dir = self.object_dir(identifier)?;
+        if !dir.is_dir() {
+            return Err(Error::malformed(format!("store: no object {}", identifier)));
+        }
+        if self.mode == StoreMode::Blocks {
+            return Err(Error::malformed(
+                "store: block-mode objects have no data file",
+            ));
+        }
+        Ok(dir.join("data"))
+    }
+
+    /// Open the stored tree for `identifier`, checked against the identifier
+    /// it is filed under.
+    pub fn tree(&self, identifier: &str) -> Result<PersistedTree, Error> {
+        let dir = self.object_dir(identifier)?;
+        if !dir.is_dir() {
+            return Err(Error::malformed(format!("store: no object {}", identifier)));
+        }
+        let tree = PersistedTree::read(&dir.join("tree"))?;
+        tree.check_against(identifier)?;
+        Ok(tree)
+    }
+
+    /// Validate `[start, end)` of the object `identifier`, optionally streaming
+    /// the verified bytes to `writer` (see [`PersistedTree::validate`]).
+    pub fn get(
+        &self,
+        identifier: &str,
+        start: Option<u64>,
+        end: Option<u64>,
+        writer: Option<&mut dyn Write>,
+    ) -> Result<(), Error> {
+        let tree = self.tree(identifier)?;
+        match self.mode {
+            StoreMode::Whole => tree.validate(&self.data_path(identifier)?, start, end, writer),
+            StoreMode::Blocks => tree.validate_with(start, end, writer, |index, _| {
+                let leaf = tree.leaf_hashes(index, 1)?[0];
+                let path = self.block_path(&leaf);
+                fs::read(&path)
+                    .map_err(|e| Error::malformed(format!("store: block {}: {}", to_hex(&leaf), e)))
+            }),
+        }
+    }
+
+    /// Resolve a ref name to the identifier it points at.
+    pub fn resolve(&self, name: &str) -> Result<String, Error> {
+        check_ref_name(name)?;
+        let path = self.root.join("refs").join(name);
+        let text = fs::read_to_string(&path)
+            .map_err(|_| Error::malformed(format!("store: no ref {}", name)))?;
+        Ok(text.trim_end().to_string())
+    }
+
+    /// Delete ref `name`; the object it pointed at becomes collectable.
+    pub fn remove_ref(&self, name: &str) -> Result<(), Error> {
+        check_ref_name(name)?;
+        fs::remove_file(self.root.join("refs").join(name))
+            .map_err(|_| Error::malformed(format!("store: no ref {}", name)))
+    }
+
+    /// Every stored object with the refs that point at it, by identifier.
+    pub fn list(&self) -> Result<Vec<StoreEntry>, Error> {
+        let refs = self.refs()?;
+        let mut out = Vec::new();
+        for identifier in self.objects()? {
+            let tree = PersistedTree::read(&self.object_dir(&identifier)?.join("tree"))?;
+            let names = refs
+                .iter()
+                .filter(|(_, id)| *id == identifier)
+                .map(|(n, _)| n.clone())
+                .collect();
+            out.push(StoreEntry {
+                identifier,
+                length: tree.length,
+                refs: names,
+            });
+        }
+        Ok(out)
+    }
+
+    /// Remove every object no ref points at, and any abandoned staging,
+    /// once in-flight [`Store::add`]s have finished. Returns the identifiers
+    /// removed.
+    pub fn gc(&self) -> Result<Vec<String>, Error> {
+        let _lock = self.lock(true)?;
+        let live: Vec<String> = self.refs()?.into_iter().map(|(_, id)| id).collect();
+        let mut removed = Vec::new();
+        for identifier in self.objects()? {
+            if !live.contains(&identifier) {
+                let dir = self.object_dir(&identifier)?;
+                fs::remove_dir_all(&dir)
+                    .map_err(|e| Error::io(format!("cannot remove {}: {}", dir.display(), e)))?;
+                removed.push(identifier);
+            }
+        }
+        if self.mode == StoreMode::Blocks {
+            let live = self.live_blocks()?;
+            for path in self.stored_blocks()? {
+                let name = path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or_default();
+                if !live.contains(name) {
+                    fs::remove_file(&path).map_err(|e| {
+                        Error::io(format!("cannot remove {}: {}", path.display(), e))
+                    })?;
+                }
+            }
+        }
+        for entry in read_dir(&self.root.join("tmp"))? {
+            let _ = fs::remove_dir_all(&entry);
+            let _ = fs::remove_file(&entry);
+        }
+        Ok(removed)
+    }
+
+    /// Logical versus stored size, and block sharing across all objects.
+    pub fn stats(&self) -> Result<StoreStats, Error> {
+        let mut stats = StoreStats {
+            objects: 0,
+            logical_bytes: 0,
+            stored_bytes: 0,
+            blocks: 0,
+            unique_blocks: 0,
+        };
+        let mut unique = HashSet::new();
+        for identifier in self.objects()? {
+            let tree = self.tree(&identifier)?;
+            stats.objects += 1;
+            stats.logical_bytes += tree.length;
+            stats.blocks += tree.counts[0];
+            for_each_leaf(&tree, |leaf| {
+                unique.insert(to_hex(leaf));
+            })?;
+            if self.mode == StoreMode::Whole {
+                stats.stored_bytes += tree.length;
+            }
+        }
+        stats.unique_blocks = unique.len() as u64;
+        if self.mode == StoreMode::Blocks {
+            for path in self.stored_blocks()? {
+                let meta = fs::metadata(&path)
+                    .map_err(|e| Error::io(format!("stat {}: {}", path.display(), e)))?;
+                stats.stored_bytes += meta.len();
+            }
+        }
+        Ok(stats)
+    }
+
+    fn set_ref(&self, name: &str, identifier: &str) -> Result<(), Error> {
+        let path = self.root.join("refs").join(name);
+        let tmp = self.staging_name("ref");
+        fs::write(&tmp, format!("{}\n", identifier))
+            .and_then(|_| fs::rename(&tmp, &path))
+            .map_err(|e| Error::io(format!("cannot write ref {}: {}", name, e)))
+    }
+
+    /// All `(name, identifier)` refs, sorted by name.
+    fn refs(&self) -> Result<Vec<(String, String)>, Error> {
+        let mut out = Vec::new();
+        for path in read_dir(&self.root.join("refs"))? {
+            let name = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default()
+                .to_string();
+            out.push((name.clone(), self.resolve(&name)?));
+        }
+        Ok(out)
+    }
+
+    /// Identifiers of all stored objects, sorted.
+    fn objects(&self) -> Result<Vec<String>, Error> {
+        let mut out = Vec::new();
+        for alg in read_dir(&self.root.join("objects"))? {
+            let alg_name = alg.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            for obj in read_dir(&alg)? {
+                let hex = obj.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+                out.push(format!("{}:{}", alg_name, hex));
+            }
+        }
+        out.sort();
+        Ok(out)
+    }
+
+    /// Leaf hex names of every block the remaining objects use.
+    fn live_blocks(&self) -> Result<HashSet<String>, Error> {
+        let mut live = HashSet::new();
+        for identifier in self.objects()? {
+            for_each_leaf(&self.tree(&identifier)?, |leaf| {
+                live.insert(to_hex(leaf));
+            })?;
+        }
+        Ok(live)
+    }
+
+    /// Paths of all stored blocks.
+    fn stored_blocks(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut out = Vec::new();
+        for fan in read_dir(&self.root.join("blocks"))? {
+            out.extend(read_dir(&fan)?);
+        }
+        Ok(out)
+    }
+
+    fn block_path(&self, leaf: &[u8]) -> PathBuf {
+        let hex = to_hex(leaf);
+        self.root.join("blocks").join(&hex[..2]).join(hex)
+    }
+
+    fn object_dir(&self, identifier: &str) -> Result<PathBuf, Error> {
+        let (alg, hex) = identifier.split_once(':').ok_or_else(|| {
+            Error::malformed(format!("store: malformed identifier {}", identifier))
+        })?;
+        let hex_ok = hex.len() == 64 && hex.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f'));
+        if profile_by_name(alg).is_none() || !hex_ok {
+            return Err(Error::malformed(format!(
+                "store: malformed identifier {}",
+                identifier
+            )));
+        }
+        Ok(self.root.join("objects").join(alg).join(hex))
+    }
+
+    /// Lock the store's `lock` file, exclusively or shared, until the returned
+    /// handle is dropped.
+    fn lock(&self, exclusive: bool) -> Result<File, Error> {
+        let path = self.root.join("lock");
+        let f = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(|e| Error::io(format!("cannot open {}: {}", path.display(), e)))?;
+        let locked = if exclusive { f.lock() } else { f.lock_shared() };
+        locked.map_err(|e| Error::io(format!("cannot lock {}: {}", path.display(), e)))?;
+        Ok(f)
+    }
+
+    /// A fresh, unused name under `tmp/`.
+    fn staging_name(&self, kind: &str) -> PathBuf {
+        let n = STAGING.fetch_add(1, Ordering::Relaxed);
+        self.root
+            .join("tmp")
+            .join(format!("{}-{}-{}", kind, std::process::id(), n))
+    }
+
+    fn staging_dir(&self) -> Result<PathBuf, Error> {
+        let dir = self.staging_name("add");
+        fs::create_dir_all(&dir)
+            .map_err(|e| Error::io(format!("cannot create {}: {}", dir.display(), e)))?;
+        Ok(dir)
+    }
+}
+
+/// Call `f` with each leaf hash of `tree`, a group at a time.
+fn for_each_leaf(tree: &PersistedTree, mut f: impl FnMut(&[u8; 32])) -> Result<(), Error> {
+    let mut first = 0;
+    while first < tree.counts[0] {
+        let n = (tree.counts[0] - first).min(FANOUT as u64);
+        for leaf in tree.leaf_hashes(first, n)? {
+            f(&leaf);
+        }
+        first += n;
+    }
+    Ok(())
+}
+
+/// Fill `buf` as far as `r` allows; returns the bytes read.
+fn read_full(r: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match r.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(k) => n += k,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(n)
+}
+
+/// Ref names are single path components: no separators, no dot-names. Nor
+/// `:`, so a name can never be mistaken for an identifier.
+fn check_ref_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\', ':']) {
+        return Err(Error::malformed(format!(
+            "store: invalid ref name {:?}",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Entries of `dir`, sorted by path.
+fn read_dir(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut out = fs::read_dir(dir)
+        .map_err(|e| Error::io(format!("cannot read {}: {}", dir.display(), e)))?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| Error::io(format!("cannot read {}: {}", dir.display(), e)))?;
+    out.sort();
+    Ok(out)
+}
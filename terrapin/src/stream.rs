@@ -1,17 +1,54 @@
 //! Streaming, parallel construction from a reader.
 //!
-//! Data blocks are read sequentially at exact `BLOCK` boundaries and hashed on
-//! the blocking thread pool with bounded, order-preserving concurrency, then fed
-//! to a [`TreeBuilder`]. The dataset itself is never held in memory; only up to
-//! `parallelism` blocks are in flight plus the leaf hash file.
+//! Data blocks are read sequentially at exact `BLOCK` boundaries, batched
+//! [`Profile::lanes`] at a time for [`Profile::g_many`] (one at a time for
+//! the scalar profiles shipped here), and hashed with
+//! bounded, order-preserving concurrency, then fed to a [`TreeBuilder`]. The
+//! dataset itself is never held in memory; only up to `parallelism × lanes`
+//! blocks are in flight plus the leaf hash file.
+//!
+//! [`build_from_reader_sync`] hashes on scoped worker threads and needs no
+//! runtime. The async [`build_from_reader`] (feature `tokio`) hashes on the
+//! tokio blocking pool.
+//!
+//! Full blocks of zeros are not hashed: their leaf is a constant of the
+//! profile, computed once per build. [`build_from_file_sync`] goes further and
+//! does not read blocks lying wholly in a hole of a sparse file (see
+//! [`crate::SparseSource`]).
 
-use std::io::{self, ErrorKind, Read};
-use std::thread::available_parallelism;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, SeekFrom};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::thread::{self, available_parallelism};
 
+#[cfg(feature = "tokio")]
 use futures::stream::{self, StreamExt};
 
+use terrapin_core::{Profile, BLOCK, SHA256};
+
 use crate::builder::{BuiltTree, TreeBuilder};
-use crate::manifest::{g, BLOCK};
+use crate::sparse::SparseSource;
+
+/// One full block of zeros, compared against and hashed for the zero leaf.
+static ZEROS: [u8; BLOCK] = [0; BLOCK];
+
+/// A data block as the hashing workers see it.
+enum Block {
+    Data(Vec<u8>),
+    /// This many zero bytes, known without reading them.
+    Zeros(usize),
+}
+
+impl Block {
+    fn len(&self) -> usize {
+        match self {
+            Block::Data(data) => data.len(),
+            Block::Zeros(n) => *n,
+        }
+    }
+}
 
 /// Reads a `Read` source into exact `BLOCK`-sized blocks (the final block may be
 /// shorter). An empty source yields exactly one empty block, so the dataset is
@@ -33,7 +70,7 @@ impl<R: Read> BlockReader<R> {
 }
 
 impl<R: Read> Iterator for BlockReader<R> {
-    type Item = io::Result<Vec<u8>>;
+    type Item = io::Result<Block>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.finished {
@@ -56,54 +93,357 @@ impl<R: Read> Iterator for BlockReader<R> {
             self.finished = true;
             if !self.emitted {
                 self.emitted = true;
-                return Some(Ok(Vec::new())); // empty dataset -> one empty leaf
+                return Some(Ok(Block::Data(Vec::new()))); // empty dataset -> one empty leaf
             }
             return None;
         }
         self.emitted = true;
         buf.truncate(filled);
-        Some(Ok(buf))
+        Some(Ok(Block::Data(buf)))
+    }
+}
+
+/// Reads the blocks of a seekable source of `length` bytes, yielding those
+/// lying wholly before its next data region as [`Block::Zeros`] unread.
+struct FileBlocks<F> {
+    file: F,
+    length: u64,
+    pos: u64,
+    /// The data region at or after `pos` (see [`SparseSource::data_region`]),
+    /// once asked; `Some(None)` when only zeros remain.
+    region: Option<Option<(u64, u64)>>,
+    emitted: bool,
+    failed: bool,
+}
+
+impl<F: SparseSource> FileBlocks<F> {
+    /// Blocks of `file` from its start to `length`.
+    fn new(file: F, length: u64) -> Self {
+        FileBlocks {
+            file,
+            length,
+            pos: 0,
+            region: None,
+            emitted: false,
+            failed: false,
+        }
+    }
+
+    fn block(&mut self) -> io::Result<Block> {
+        let end = self.length.min(self.pos + BLOCK as u64);
+        let len = (end - self.pos) as usize;
+        let stale = match self.region {
+            None => true,
+            Some(Some((_, hole))) => hole <= self.pos,
+            Some(None) => false,
+        };
+        if stale {
+            self.region = Some(self.file.data_region(self.pos)?);
+        }
+        if !matches!(self.region, Some(Some((data, _))) if data < end) {
+            return Ok(Block::Zeros(len));
+        }
+        let mut buf = vec![0u8; len];
+        self.file.seek(SeekFrom::Start(self.pos))?;
+        self.file.read_exact(&mut buf)?;
+        Ok(Block::Data(buf))
+    }
+}
+
+impl<F: SparseSource> Iterator for FileBlocks<F> {
+    type Item = io::Result<Block>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || (self.emitted && self.pos >= self.length) {
+            return None;
+        }
+        self.emitted = true;
+        let block = self.block();
+        match &block {
+            Ok(b) => self.pos += b.len() as u64,
+            Err(_) => self.failed = true,
+        }
+        Some(block)
     }
 }
 
+/// The blocks of a [`SparseSource`]: by offset, skipping holes, if it has a
+/// length, or else streamed like any reader.
+enum SourceBlocks<F> {
+    File(FileBlocks<F>),
+    Stream(BlockReader<F>),
+}
+
+impl<F: SparseSource> SourceBlocks<F> {
+    fn new(mut file: F) -> Self {
+        match file.length() {
+            Some(length) => SourceBlocks::File(FileBlocks::new(file, length)),
+            None => SourceBlocks::Stream(BlockReader::new(file)),
+        }
+    }
+}
+
+impl<F: SparseSource> Iterator for SourceBlocks<F> {
+    type Item = io::Result<Block>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            SourceBlocks::File(blocks) => blocks.next(),
+            SourceBlocks::Stream(blocks) => blocks.next(),
+        }
+    }
+}
+
+/// Groups consecutive blocks into batches of up to `lanes` for
+/// [`Profile::g_many`]. A read error ends the batch it interrupts.
+struct Batches<I> {
+    blocks: I,
+    lanes: usize,
+    pending: Option<io::Error>,
+}
+
+impl<I> Batches<I> {
+    fn new(blocks: I, lanes: usize) -> Self {
+        Batches {
+            blocks,
+            lanes,
+            pending: None,
+        }
+    }
+}
+
+impl<I: Iterator<Item = io::Result<Block>>> Iterator for Batches<I> {
+    type Item = io::Result<Vec<Block>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.pending.take() {
+            return Some(Err(e));
+        }
+        let mut batch = Vec::with_capacity(self.lanes);
+        while batch.len() < self.lanes {
+            match self.blocks.next() {
+                Some(Ok(block)) => batch.push(block),
+                Some(Err(e)) if batch.is_empty() => return Some(Err(e)),
+                Some(Err(e)) => {
+                    self.pending = Some(e);
+                    break;
+                }
+                None => break,
+            }
+        }
+        if batch.is_empty() {
+            None
+        } else {
+            Some(Ok(batch))
+        }
+    }
+}
+
+/// One batch's leaf hashes, in block order.
+type Hashes = Vec<[u8; 32]>;
+
 fn parallelism() -> usize {
     available_parallelism().map(|n| n.get()).unwrap_or(4)
 }
 
+/// The leaf hashes of a batch: `g_many` over the blocks with data, the shared
+/// zero leaf (computed on first use) for full blocks of zeros.
+fn hash_batch(profile: &dyn Profile, zero_leaf: &OnceLock<[u8; 32]>, batch: &[Block]) -> Hashes {
+    let is_zero = |b: &Block| match b {
+        Block::Data(data) => data.len() == BLOCK && data[..] == ZEROS[..],
+        Block::Zeros(n) => *n == BLOCK,
+    };
+    let inputs: Vec<&[u8]> = batch
+        .iter()
+        .filter(|b| !is_zero(b))
+        .map(|b| match b {
+            Block::Data(data) => data.as_slice(),
+            Block::Zeros(n) => &ZEROS[..*n],
+        })
+        .collect();
+    let mut hashed = profile.g_many(&inputs).into_iter();
+    batch
+        .iter()
+        .map(|b| match is_zero(b) {
+            true => *zero_leaf.get_or_init(|| profile.g(&ZEROS)),
+            false => hashed.next().expect("one hash per input"),
+        })
+        .collect()
+}
+
+/// Build the full tree from a reader, hashing blocks in parallel on worker
+/// threads; blocks the caller until the reader is exhausted.
+pub fn build_from_reader_sync<R: Read>(reader: R) -> io::Result<BuiltTree> {
+    build_from_reader_sync_with(SHA256, reader)
+}
+
+/// [`build_from_reader_sync`] under `profile`.
+pub fn build_from_reader_sync_with<R: Read>(
+    profile: &'static dyn Profile,
+    reader: R,
+) -> io::Result<BuiltTree> {
+    build_sync(profile, BlockReader::new(reader))
+}
+
+/// Build the full tree of a seekable file, from its start to the end it has
+/// when hashing starts, like [`build_from_reader_sync`] but without reading
+/// blocks that lie wholly in a hole. A file without a length (e.g. a named
+/// pipe, see [`SparseSource::length`]) is read as a stream instead.
+pub fn build_from_file_sync(file: File) -> io::Result<BuiltTree> {
+    build_from_file_sync_with(SHA256, file)
+}
+
+/// [`build_from_file_sync`] under `profile`, for any [`SparseSource`].
+pub fn build_from_file_sync_with<F: SparseSource>(
+    profile: &'static dyn Profile,
+    file: F,
+) -> io::Result<BuiltTree> {
+    build_sync(profile, SourceBlocks::new(file))
+}
+
+fn build_sync(
+    profile: &'static dyn Profile,
+    blocks: impl Iterator<Item = io::Result<Block>>,
+) -> io::Result<BuiltTree> {
+    let n = parallelism();
+    let lanes = profile.lanes().max(1);
+    let mut batches = Batches::new(blocks, lanes);
+    let mut builder = TreeBuilder::with_profile(profile);
+    let mut length: u64 = 0;
+    let zero_leaf = OnceLock::new();
+
+    thread::scope(|s| {
+        let (job_tx, job_rx) = mpsc::channel::<(u64, Vec<Block>)>();
+        let (done_tx, done_rx) = mpsc::channel::<(u64, Vec<usize>, thread::Result<Hashes>)>();
+        let job_rx = Arc::new(Mutex::new(job_rx));
+        for _ in 0..n {
+            let job_rx = Arc::clone(&job_rx);
+            let done_tx = done_tx.clone();
+            let zero_leaf = &zero_leaf;
+            s.spawn(move || loop {
+                let job = job_rx.lock().unwrap().recv();
+                let Ok((seq, batch)) = job else { break };
+                let lens = batch.iter().map(Block::len).collect();
+                let hs = panic::catch_unwind(AssertUnwindSafe(|| {
+                    hash_batch(profile, zero_leaf, &batch)
+                }));
+                if done_tx.send((seq, lens, hs)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(done_tx);
+
+        // At most `n` batches are out at once; results are re-sequenced
+        // before reaching the builder. Dropping `job_tx` on any exit stops
+        // the workers.
+        let (mut sent, mut next) = (0u64, 0u64);
+        let mut ready = BTreeMap::new();
+        let mut exhausted = false;
+        loop {
+            while !exhausted && sent - next < n as u64 {
+                match batches.next() {
+                    Some(batch) => {
+                        job_tx.send((sent, batch?)).expect("workers alive");
+                        sent += 1;
+                    }
+                    None => exhausted = true,
+                }
+            }
+            if next == sent {
+                return Ok::<(), io::Error>(());
+            }
+            let (seq, lens, hs) = done_rx.recv().map_err(io::Error::other)?;
+            let hs = hs.map_err(|_| io::Error::other("hashing worker panicked"))?;
+            ready.insert(seq, (lens, hs));
+            while let Some((lens, hs)) = ready.remove(&next) {
+                for (len, h) in lens.iter().zip(&hs) {
+                    length += *len as u64;
+                    builder.push_leaf(h);
+                }
+                next += 1;
+            }
+        }
+    })?;
+    Ok(builder.build(length))
+}
+
 /// Build the full tree from a reader, hashing blocks in parallel.
+#[cfg(feature = "tokio")]
 pub async fn build_from_reader<R: Read + Send + 'static>(reader: R) -> io::Result<BuiltTree> {
+    build_from_reader_with(SHA256, reader).await
+}
+
+/// [`build_from_reader`] under `profile`.
+#[cfg(feature = "tokio")]
+pub async fn build_from_reader_with<R: Read + Send + 'static>(
+    profile: &'static dyn Profile,
+    reader: R,
+) -> io::Result<BuiltTree> {
+    build_async(profile, BlockReader::new(reader)).await
+}
+
+/// The async form of [`build_from_file_sync`].
+#[cfg(feature = "tokio")]
+pub async fn build_from_file(file: File) -> io::Result<BuiltTree> {
+    build_from_file_with(SHA256, file).await
+}
+
+/// [`build_from_file`] under `profile`, for any [`SparseSource`].
+#[cfg(feature = "tokio")]
+pub async fn build_from_file_with<F: SparseSource + Send + 'static>(
+    profile: &'static dyn Profile,
+    file: F,
+) -> io::Result<BuiltTree> {
+    build_async(profile, SourceBlocks::new(file)).await
+}
+
+#[cfg(feature = "tokio")]
+async fn build_async(
+    profile: &'static dyn Profile,
+    blocks: impl Iterator<Item = io::Result<Block>>,
+) -> io::Result<BuiltTree> {
     let n = parallelism();
-    let mut hashes = stream::iter(BlockReader::new(reader))
-        .map(|res| async move {
-            let block = res?;
-            let len = block.len();
-            let h = tokio::task::spawn_blocking(move || g(&block))
-                .await
-                .map_err(io::Error::other)?;
-            Ok::<(usize, [u8; 32]), io::Error>((len, h))
+    let lanes = profile.lanes().max(1);
+    let zero_leaf = Arc::new(OnceLock::new());
+    let mut hashes = stream::iter(Batches::new(blocks, lanes))
+        .map(|res| {
+            let zero_leaf = Arc::clone(&zero_leaf);
+            async move {
+                let batch = res?;
+                let lens: Vec<usize> = batch.iter().map(Block::len).collect();
+                let hs =
+                    tokio::task::spawn_blocking(move || hash_batch(profile, &zero_leaf, &batch))
+                        .await
+                        .map_err(io::Error::other)?;
+                Ok::<(Vec<usize>, Vec<[u8; 32]>), io::Error>((lens, hs))
+            }
         })
         .buffered(n);
 
-    let mut builder = TreeBuilder::new();
+    let mut builder = TreeBuilder::with_profile(profile);
     let mut length: u64 = 0;
     while let Some(item) = hashes.next().await {
-        let (len, h) = item?;
-        length += len as u64;
-        builder.push_leaf(&h);
+        let (lens, hs) = item?;
+        for (len, h) in lens.iter().zip(&hs) {
+            length += *len as u64;
+            builder.push_leaf(h);
+        }
     }
     Ok(builder.build(length))
 }
 
 /// Convenience: the `terrapin-sha256:<hex>` identifier of a reader.
+#[cfg(feature = "tokio")]
 pub async fn identifier_from_reader<R: Read + Send + 'static>(reader: R) -> io::Result<String> {
     Ok(build_from_reader(reader).await?.identifier())
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "tokio"))]
 mod tests {
     use super::*;
-    use crate::manifest::identifier;
     use std::io::Cursor;
+    use terrapin_core::identifier;
 
     /// A reader that returns at most `chunk` bytes per `read`, to exercise short
     /// reads and block reassembly.
@@ -9,49 +9,40 @@
 //!   32-byte aligned. Layer `L` starts at a known byte offset; hash `j` of layer
 //!   `L` is at `offset[L] + j*32`.
 //!
+//! The `.head` is version 1 unless written with [`HeadFormat::V2`], which
+//! fixes the key order, adds the `.blocks` size and a hash of the top layer
+//! (checked when a tree with local `.blocks` is opened), and carries
+//! `ext.<namespace>.<name>` extension lines. Both versions are read.
+//!
+//! Alternatively [`PersistedTree::write_single`] writes one file: a text
+//! header (`terrapin-tree-file: 1`, `blocks_offset: N`, then the `.head`
+//! fields), zero-padded to `N`, a multiple of 4096, then the `.blocks` bytes.
+//! Layer `L` starts at `N + offset[L]`, so the layout is still computable from
+//! the header alone and the file stays range-fetchable. [`PersistedTree::read`]
+//! detects which form is present.
+//!
 //! Validation (spec section 6) starts from the identifier, derives the exact
 //! tree shape from `length`, and recomputes `G` upward along the path for each
 //! requested data block, fetching one hash-file block per layer (cached across
 //! the range), never reading the whole leaf layer for a small slice.
 
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread::{self, available_parallelism};
+
+use terrapin_core::{derive_counts, offsets_from_counts, Error, PathVerifier, BLOCK, FANOUT};
+use terrapin_core::{hex_to_32, manifest_bytes_with, profile_by_name, to_hex, Profile, SHA256};
 
 use crate::builder::BuiltTree;
-use crate::manifest::{g, identifier_from_parts, manifest_bytes, BLOCK, FANOUT};
 
 const HEAD_VERSION: &str = "1";
-
-/// A cached hash-file group along the validation path:
-/// `(group_start_index, group_bytes, node = g(group))`.
-type GroupCache = Option<(u64, Vec<u8>, [u8; 32])>;
-
-/// Number of hashes at each layer, derived solely from `length` and the fixed
-/// block size (spec section 6 step 3). `layers[0]` is the leaf count.
-pub fn derive_counts(length: u64) -> Vec<u64> {
-    let nblocks = if length == 0 {
-        1 // empty dataset is one empty leaf
-    } else {
-        length.div_ceil(BLOCK as u64)
-    };
-    let mut counts = vec![nblocks];
-    while *counts.last().unwrap() > FANOUT as u64 {
-        let prev = *counts.last().unwrap();
-        counts.push(prev.div_ceil(FANOUT as u64));
-    }
-    counts
-}
-
-fn offsets_from_counts(counts: &[u64]) -> Vec<u64> {
-    let mut offs = Vec::with_capacity(counts.len());
-    let mut acc = 0u64;
-    for &c in counts {
-        offs.push(acc);
-        acc += c * 32;
-    }
-    offs
-}
+/// First line of a single-file artifact.
+const SINGLE_MAGIC: &str = "terrapin-tree-file: 1\n";
+/// The single-file `.blocks` offset is a multiple of this.
+const SINGLE_ALIGN: u64 = 4096;
 
 /// A read handle for a persisted tree.
 pub struct PersistedTree {
@@ -60,68 +51,191 @@ pub struct PersistedTree {
     pub identifier: String,
     pub counts: Vec<u64>,
     offsets: Vec<u64>,
-    blocks_path: PathBuf,
+    /// Local `.blocks` file; `None` for a head obtained via [`Self::from_head`].
+    blocks_path: Option<PathBuf>,
+    /// Where the `.blocks` bytes start in `blocks_path`: 0, or the header size
+    /// of a single-file artifact.
+    blocks_offset: u64,
+    /// Hash profile named by the head's `algorithm`.
+    profile: &'static dyn Profile,
+    /// `terrapin-tree` version of the head it was read from.
+    version: u32,
+    /// Version 2 `top_layer`: `g` of the top layer, checked against `.blocks`.
+    top_layer: Option<String>,
+    /// Version 2 extension fields (`ext.<namespace>.<name>` lines, prefix
+    /// stripped); empty for version 1.
+    pub extensions: BTreeMap<String, String>,
+}
+
+/// The `.head` format to write (see [`PersistedTree::write_as`]).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum HeadFormat {
+    /// `terrapin-tree: 1`, what [`PersistedTree::write`] writes.
+    #[default]
+    V1,
+    /// `terrapin-tree: 2`: the version 1 fields in canonical order, then
+    /// `blocks_size` (bytes of `.blocks`) and `top_layer` (`g` of the top
+    /// layer), then `ext.<namespace>.<name>` lines in sorted order that readers
+    /// may ignore. No duplicate keys, no CR.
+    V2 {
+        /// `<namespace>.<name>` → value, e.g. `terrapin.media_type`.
+        extensions: BTreeMap<String, String>,
+    },
 }
 
 impl PersistedTree {
     /// Write the two-file artifact `<name>.head` / `<name>.blocks`.
     pub fn write(name: &Path, tree: &BuiltTree) -> io::Result<()> {
-        let blocks_path = with_ext(name, "blocks");
-        let head_path = with_ext(name, "head");
+        PersistedTree::write_as(name, tree, &HeadFormat::V1)
+    }
 
-        let mut bf = File::create(&blocks_path)?;
+    /// [`Self::write`] with the head in `format`.
+    pub fn write_as(name: &Path, tree: &BuiltTree, format: &HeadFormat) -> io::Result<()> {
+        let head = head_text(tree, format)?;
+        let mut bf = File::create(with_ext(name, "blocks"))?;
         for layer in &tree.layers {
             bf.write_all(layer)?;
         }
         bf.flush()?;
+        std::fs::write(with_ext(name, "head"), head)
+    }
 
-        let counts: Vec<u64> = tree.layers.iter().map(|l| (l.len() / 32) as u64).collect();
-        let counts_str = counts
-            .iter()
-            .map(|c| c.to_string())
-            .collect::<Vec<_>>()
-            .join(" ");
-        let head = format!(
-            "terrapin-tree: {}\nalgorithm: terrapin-sha256\nblock_size: {}\nlength: {}\ntree: {}\nidentifier: {}\nlayer_counts: {}\n",
-            HEAD_VERSION,
-            BLOCK,
-            tree.length,
-            tree.tree_hex(),
-            tree.identifier(),
-            counts_str,
-        );
-        std::fs::write(&head_path, head)?;
-        Ok(())
+    /// Write the single-file artifact at `path` (see the module docs).
+    pub fn write_single(path: &Path, tree: &BuiltTree) -> io::Result<()> {
+        PersistedTree::write_single_as(path, tree, &HeadFormat::V1)
     }
 
-    /// Open a persisted tree by base name.
-    pub fn read(name: &Path) -> Result<PersistedTree, String> {
+    /// [`Self::write_single`] with the head in `format`.
+    pub fn write_single_as(path: &Path, tree: &BuiltTree, format: &HeadFormat) -> io::Result<()> {
+        let head = head_text(tree, format)?;
+        // Room for the longest offset keeps the header inside its padding.
+        let fixed = SINGLE_MAGIC.len() + "blocks_offset: \n".len() + 20 + head.len();
+        let offset = (fixed as u64).div_ceil(SINGLE_ALIGN) * SINGLE_ALIGN;
+        let mut header =
+            format!("{}blocks_offset: {}\n{}", SINGLE_MAGIC, offset, head).into_bytes();
+        header.resize(offset as usize, 0);
+
+        let mut f = File::create(path)?;
+        f.write_all(&header)?;
+        for layer in &tree.layers {
+            f.write_all(layer)?;
+        }
+        f.flush()
+    }
+
+    /// Open a persisted tree by base name: `<name>.head` / `<name>.blocks`,
+    /// or else a single-file artifact at `<name>` itself.
+    pub fn read(name: &Path) -> Result<PersistedTree, Error> {
         let head_path = with_ext(name, "head");
-        let blocks_path = with_ext(name, "blocks");
+        if !head_path.exists() && is_single(name) {
+            return PersistedTree::read_single(name);
+        }
         let text = std::fs::read_to_string(&head_path)
-            .map_err(|e| format!("cannot read {}: {}", head_path.display(), e))?;
+            .map_err(|e| Error::io(format!("cannot read {}: {}", head_path.display(), e)))?;
+        PersistedTree::from_parts(&text, &with_ext(name, "blocks"))
+    }
+
+    /// Open the single-file artifact at `path`.
+    pub fn read_single(path: &Path) -> Result<PersistedTree, Error> {
+        let mut f = File::open(path)
+            .map_err(|e| Error::io(format!("cannot open {}: {}", path.display(), e)))?;
+        let mut prefix = Vec::new();
+        (&mut f)
+            .take(SINGLE_ALIGN)
+            .read_to_end(&mut prefix)
+            .map_err(|e| Error::io(format!("cannot read {}: {}", path.display(), e)))?;
+        let offset = single_offset(&prefix)?;
+        if offset > prefix.len() as u64 {
+            (&mut f)
+                .take(offset - prefix.len() as u64)
+                .read_to_end(&mut prefix)
+                .map_err(|e| Error::io(format!("cannot read {}: {}", path.display(), e)))?;
+        }
+        let mut tree = PersistedTree::from_single_header(&prefix)?;
+        tree.blocks_path = Some(path.to_path_buf());
+        tree.check_declared()?;
+        Ok(tree)
+    }
+
+    /// The header length (`blocks_offset`) a single-file artifact declares on
+    /// its second line; `prefix` is the start of the file, and its first 4096
+    /// bytes always suffice.
+    pub fn single_header_len(prefix: &[u8]) -> Result<u64, Error> {
+        single_offset(prefix)
+    }
+
+    /// Parse the header of a single-file artifact from its first
+    /// `blocks_offset` bytes, e.g. range-fetched. Callers must pass all of
+    /// them: a v2 head with extensions can take the header past 4096 bytes
+    /// (see [`Self::single_header_len`]). Like [`Self::from_head`], the
+    /// result has no local `.blocks`; groups sit at [`Self::blocks_offset`]
+    /// plus [`Self::group_range`] in the file.
+    pub fn from_single_header(prefix: &[u8]) -> Result<PersistedTree, Error> {
+        let offset = single_offset(prefix)?;
+        let header = prefix
+            .get(..offset as usize)
+            .ok_or_else(|| Error::malformed("head: single-file header truncated"))?;
+        let end = header.iter().position(|&b| b == 0).unwrap_or(header.len());
+        if header[end..].iter().any(|&b| b != 0) {
+            return Err(Error::malformed("head: single-file padding not zero"));
+        }
+        let text =
+            std::str::from_utf8(&header[..end]).map_err(|_| Error::malformed("head: non-utf8"))?;
+        let (_, head) = text
+            .split_once("\nblocks_offset: ")
+            .and_then(|(_, rest)| rest.split_once('\n'))
+            .ok_or_else(|| Error::malformed("head: single-file header malformed"))?;
+        let mut tree = PersistedTree::from_head(head)?;
+        tree.blocks_offset = offset;
+        Ok(tree)
+    }
 
+    /// Open a tree whose `.head` text and `.blocks` file are stored under
+    /// other names — e.g. as content-addressed OCI blobs.
+    pub fn from_parts(head: &str, blocks: &Path) -> Result<PersistedTree, Error> {
+        let mut tree = PersistedTree::from_head(head)?;
+        tree.blocks_path = Some(blocks.to_path_buf());
+        tree.check_declared()?;
+        Ok(tree)
+    }
+
+    /// Parse `.head` text obtained through any channel (e.g. over HTTP). The
+    /// result has no local `.blocks`; verify data with [`Self::verifier`],
+    /// supplying the hash-file groups yourself. Reads versions 1 and 2.
+    pub fn from_head(text: &str) -> Result<PersistedTree, Error> {
+        if text.starts_with("terrapin-tree: 2\n") {
+            return parse_v2(text);
+        }
         let mut version = None;
         let mut block_size = None;
         let mut length = None;
         let mut tree_hex = None;
         let mut identifier = None;
         let mut counts: Option<Vec<u64>> = None;
+        let mut profile = SHA256;
+        let mut seen = Vec::new();
 
         for line in text.lines() {
             let (key, val) = line
                 .split_once(": ")
-                .ok_or_else(|| format!("head: bad line {:?}", line))?;
+                .ok_or_else(|| Error::malformed(format!("head: bad line {:?}", line)))?;
+            if seen.contains(&key) {
+                return Err(Error::malformed(format!("head: duplicate key {}", key)));
+            }
+            seen.push(key);
             match key {
                 "terrapin-tree" => version = Some(val.to_string()),
                 "algorithm" => {
-                    if val != "terrapin-sha256" {
-                        return Err(format!("head: unsupported algorithm {}", val));
-                    }
+                    profile = profile_by_name(val).ok_or_else(|| {
+                        Error::malformed(format!("head: unsupported algorithm {}", val))
+                    })?;
                 }
                 "block_size" => block_size = Some(val.to_string()),
                 "length" => {
-                    length = Some(val.parse::<u64>().map_err(|_| "head: bad length".to_string())?)
+                    length = Some(
+                        val.parse::<u64>()
+                            .map_err(|_| Error::malformed("head: bad length"))?,
+                    )
                 }
                 "tree" => tree_hex = Some(val.to_string()),
                 "identifier" => identifier = Some(val.to_string()),
@@ -130,28 +244,40 @@ impl PersistedTree {
                         .split_whitespace()
                         .map(|s| s.parse::<u64>())
                         .collect::<Result<Vec<_>, _>>()
-                        .map_err(|_| "head: bad layer_counts".to_string())?;
+                        .map_err(|_| Error::malformed("head: bad layer_counts"))?;
                     counts = Some(cs);
                 }
-                _ => return Err(format!("head: unknown key {}", key)),
+                _ => return Err(Error::malformed(format!("head: unknown key {}", key))),
             }
         }
 
         if version.as_deref() != Some(HEAD_VERSION) {
-            return Err("head: unsupported terrapin-tree version".into());
+            return Err(Error::malformed("head: unsupported terrapin-tree version"));
         }
         if block_size.as_deref() != Some(&BLOCK.to_string()) {
-            return Err("head: block_size must be 2097152".into());
+            return Err(Error::malformed("head: block_size must be 2097152"));
         }
-        let length = length.ok_or("head: missing length")?;
-        let tree_hex = tree_hex.ok_or("head: missing tree")?;
-        let identifier = identifier.ok_or("head: missing identifier")?;
-        let counts = counts.ok_or("head: missing layer_counts")?;
+        let length = length.ok_or_else(|| Error::malformed("head: missing length"))?;
+        let tree_hex = tree_hex.ok_or_else(|| Error::malformed("head: missing tree"))?;
+        let identifier = identifier.ok_or_else(|| Error::malformed("head: missing identifier"))?;
+        let counts = counts.ok_or_else(|| Error::malformed("head: missing layer_counts"))?;
+        PersistedTree::from_fields(profile, length, tree_hex, identifier, counts)
+    }
 
+    /// A tree of the given head fields, with no local `.blocks`.
+    fn from_fields(
+        profile: &'static dyn Profile,
+        length: u64,
+        tree_hex: String,
+        identifier: String,
+        counts: Vec<u64>,
+    ) -> Result<PersistedTree, Error> {
         // The tree shape is a total function of length; reject a header whose
         // declared counts disagree with it.
         if counts != derive_counts(length) {
-            return Err("head: layer_counts inconsistent with length".into());
+            return Err(Error::malformed(
+                "head: layer_counts inconsistent with length",
+            ));
         }
         let offsets = offsets_from_counts(&counts);
 
@@ -161,59 +287,104 @@ impl PersistedTree {
             identifier,
             counts,
             offsets,
-            blocks_path,
+            blocks_path: None,
+            blocks_offset: 0,
+            profile,
+            version: 1,
+            top_layer: None,
+            extensions: BTreeMap::new(),
         })
     }
 
+    /// The `terrapin-tree` version of the head: 1 or 2.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// For a version 2 head, check the local `.blocks` against the declared
+    /// size and top-layer hash, catching truncation before any validation. A
+    /// missing `.blocks` is left to fail when it is first read, as for
+    /// version 1.
+    fn check_declared(&self) -> Result<(), Error> {
+        let Some(top_layer) = &self.top_layer else {
+            return Ok(());
+        };
+        if self.blocks_path.as_ref().is_some_and(|p| !p.exists()) {
+            return Ok(());
+        }
+        let size = self.stored_blocks_size()?;
+        if size != self.blocks_size() {
+            return Err(Error::malformed(format!(
+                "blocks: {} bytes stored, head declares {}",
+                size,
+                self.blocks_size()
+            )));
+        }
+        let top = self.counts.len() - 1;
+        let hashes = self.layer_hashes(top, 0, self.counts[top])?;
+        if to_hex(&self.profile.g(&hashes.concat())) != *top_layer {
+            return Err(Error::malformed(
+                "blocks: top layer does not match the head's top_layer",
+            ));
+        }
+        Ok(())
+    }
+
     /// Assert this tree's identifier equals a trusted one obtained out-of-band
     /// (spec section 6 step 1). A tree forged for different data has a different
     /// identifier and is rejected here, closing the gap that `validate` alone —
     /// which only checks the tree's *own* self-consistency — leaves open.
-    pub fn check_against(&self, trusted_identifier: &str) -> Result<(), String> {
+    pub fn check_against(&self, trusted_identifier: &str) -> Result<(), Error> {
         if self.identifier != trusted_identifier {
-            return Err(format!(
+            return Err(Error::mismatch(format!(
                 "identifier mismatch: tree is {}, expected {}",
                 self.identifier, trusted_identifier
-            ));
+            )));
         }
         Ok(())
     }
 
-    fn root(&self) -> Result<[u8; 32], String> {
-        let raw = hex_to_32(&self.tree_hex).ok_or("head: tree not 64 hex")?;
-        Ok(raw)
+    /// The hash profile this tree was built with.
+    pub fn profile(&self) -> &'static dyn Profile {
+        self.profile
     }
 
     /// Verify the header binds to its identifier: `G(manifest) == identifier`
     /// (spec section 6 step 2). This anchors trust in the tree root.
-    fn check_identifier(&self) -> Result<[u8; 32], String> {
-        let root = self.root()?;
-        let recomputed = identifier_from_parts(self.length, &root);
-        if recomputed != self.identifier {
-            return Err("tree: identifier does not match manifest".into());
-        }
-        // Also confirm the manifest is itself canonical/parseable.
-        let _ = manifest_bytes(self.length, &self.tree_hex);
-        Ok(root)
+    fn check_identifier(&self) -> Result<PathVerifier<'static>, Error> {
+        PathVerifier::new(self.profile, &self.identifier, self.length, &self.tree_hex)
     }
 
-    fn read_blocks_slice(&self, byte_off: u64, len: usize) -> Result<Vec<u8>, String> {
-        let mut f = File::open(&self.blocks_path)
-            .map_err(|e| format!("cannot open {}: {}", self.blocks_path.display(), e))?;
-        f.seek(SeekFrom::Start(byte_off))
-            .map_err(|e| format!("blocks seek: {}", e))?;
+    fn read_blocks_slice(&self, byte_off: u64, len: usize) -> Result<Vec<u8>, Error> {
+        let path = self
+            .blocks_path
+            .as_ref()
+            .ok_or_else(|| Error::malformed("tree: no local .blocks file"))?;
+        let mut f = File::open(path)
+            .map_err(|e| Error::io(format!("cannot open {}: {}", path.display(), e)))?;
+        f.seek(SeekFrom::Start(self.blocks_offset + byte_off))
+            .map_err(|e| Error::io(format!("blocks seek: {}", e)))?;
         let mut buf = vec![0u8; len];
         f.read_exact(&mut buf)
-            .map_err(|e| format!("blocks truncated: {}", e))?;
+            .map_err(|e| Error::malformed(format!("blocks truncated: {}", e)))?;
         Ok(buf)
     }
 
-    /// Read the hash-file group at `layer` starting at hash index `group_start`.
-    fn read_group(&self, layer: usize, group_start: u64) -> Result<Vec<u8>, String> {
+    /// Byte range `(offset, len)` within `.blocks` of the hash-file group at
+    /// `layer` starting at hash index `group_start`.
+    pub fn group_range(&self, layer: usize, group_start: u64) -> (u64, usize) {
         let remaining = self.counts[layer] - group_start;
         let len_hashes = remaining.min(FANOUT as u64) as usize;
-        let byte_off = self.offsets[layer] + group_start * 32;
-        self.read_blocks_slice(byte_off, len_hashes * 32)
+        (self.offsets[layer] + group_start * 32, len_hashes * 32)
+    }
+
+    /// Start verifying individual data blocks (spec section 6 steps 2-5). The
+    /// identifier binding is checked once, here.
+    pub fn verifier(&self) -> Result<BlockVerifier<'_>, Error> {
+        Ok(BlockVerifier {
+            tree: self,
+            path: self.check_identifier()?,
+        })
     }
 
     /// Validate the byte range `[start, end)` of `data_path` against the tree,
@@ -224,38 +395,54 @@ impl PersistedTree {
         data_path: &Path,
         start: Option<u64>,
         end: Option<u64>,
-        mut writer: Option<&mut dyn Write>,
-    ) -> Result<(), String> {
-        let root = self.check_identifier()?;
-
-        let start = start.unwrap_or(0);
-        let end = end.unwrap_or(self.length);
-        if start > end || end > self.length {
-            return Err(format!(
-                "range {}..{} out of bounds for length {}",
-                start, end, self.length
-            ));
-        }
+        writer: Option<&mut dyn Write>,
+    ) -> Result<(), Error> {
+        // Header and range checks come before touching the data file.
+        self.check_identifier()?;
+        self.check_range(start, end)?;
 
         let mut data = File::open(data_path)
-            .map_err(|e| format!("cannot open {}: {}", data_path.display(), e))?;
+            .map_err(|e| Error::io(format!("cannot open {}: {}", data_path.display(), e)))?;
         let data_len = data
             .metadata()
-            .map_err(|e| format!("stat data: {}", e))?
+            .map_err(|e| Error::io(format!("stat data: {}", e)))?
             .len();
         if data_len != self.length {
-            return Err(format!(
+            return Err(Error::validation(format!(
                 "data length {} != tree length {}",
                 data_len, self.length
-            ));
+            )));
         }
 
+        self.validate_with(start, end, writer, |index, len| {
+            let mut buf = vec![0u8; len];
+            data.seek(SeekFrom::Start(index * BLOCK as u64))
+                .map_err(|e| Error::io(format!("data seek: {}", e)))?;
+            data.read_exact(&mut buf)
+                .map_err(|e| Error::io(format!("data read: {}", e)))?;
+            Ok(buf)
+        })
+    }
+
+    /// [`Self::validate`] over data blocks supplied by `read_block(index, len)`
+    /// instead of a data file — e.g. blocks kept apart in a deduplicating store.
+    /// Each block is verified before any of it reaches `writer`.
+    pub fn validate_with<F>(
+        &self,
+        start: Option<u64>,
+        end: Option<u64>,
+        mut writer: Option<&mut dyn Write>,
+        mut read_block: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(u64, usize) -> Result<Vec<u8>, Error>,
+    {
+        let mut verifier = self.verifier()?;
+        let (start, end) = self.check_range(start, end)?;
+
         // Empty dataset: a single empty leaf; nothing to stream.
         if self.length == 0 {
-            if g(b"") != root {
-                return Err("validation failed: empty dataset root mismatch".into());
-            }
-            return Ok(());
+            return verifier.verify_block(0, b"", |off, len| self.read_blocks_slice(off, len));
         }
 
         // Empty range: header already verified, no blocks to walk.
@@ -263,58 +450,15 @@ impl PersistedTree {
             return Ok(());
         }
 
-        let single_leaf = self.counts[0] == 1;
-        let nlayers = self.counts.len();
-        // Per-layer cache: (group_start_index, group_bytes, node = g(group)).
-        let mut cache: Vec<GroupCache> = vec![None; nlayers];
-
         let b_lo = start / BLOCK as u64;
         let b_hi = (end - 1) / BLOCK as u64;
 
         for i in b_lo..=b_hi {
             let block_off = i * BLOCK as u64;
-            let block_len = (self.length - block_off).min(BLOCK as u64) as usize;
-            let mut buf = vec![0u8; block_len];
-            data.seek(SeekFrom::Start(block_off))
-                .map_err(|e| format!("data seek: {}", e))?;
-            data.read_exact(&mut buf)
-                .map_err(|e| format!("data read: {}", e))?;
-
-            let mut h = g(&buf);
+            let block_len = verifier.block_len(i);
+            let buf = read_block(i, block_len)?;
 
-            if single_leaf {
-                if h != root {
-                    return Err(format!("validation failed at block {}", i));
-                }
-            } else {
-                let mut idx = i;
-                for (l, slot) in cache.iter_mut().enumerate().take(nlayers) {
-                    let gstart = (idx / FANOUT as u64) * FANOUT as u64;
-                    let posn = (idx - gstart) as usize;
-
-                    let need_reload = match slot {
-                        Some((gs, _, _)) => *gs != gstart,
-                        None => true,
-                    };
-                    if need_reload {
-                        let bytes = self.read_group(l, gstart)?;
-                        let node = g(&bytes);
-                        *slot = Some((gstart, bytes, node));
-                    }
-                    let (_, bytes, node) = slot.as_ref().unwrap();
-                    if bytes[posn * 32..posn * 32 + 32] != h[..] {
-                        return Err(format!(
-                            "validation failed at block {} (layer {})",
-                            i, l
-                        ));
-                    }
-                    h = *node;
-                    idx /= FANOUT as u64;
-                }
-                if h != root {
-                    return Err(format!("validation failed at block {} (root)", i));
-                }
-            }
+            verifier.verify_block(i, &buf, |off, len| self.read_blocks_slice(off, len))?;
 
             if let Some(w) = writer.as_mut() {
                 let s = start.max(block_off);
@@ -323,37 +467,486 @@ impl PersistedTree {
                     let lo = (s - block_off) as usize;
                     let hi = (e - block_off) as usize;
                     w.write_all(&buf[lo..hi])
-                        .map_err(|e| format!("write output: {}", e))?;
+                        .map_err(|e| Error::io(format!("write output: {}", e)))?;
                 }
             }
         }
         Ok(())
     }
+
+    /// Leaf hashes `first..first + count` (`g` of each data block), read from
+    /// the local `.blocks`. Unverified: this is what the tree claims, not a proof.
+    pub fn leaf_hashes(&self, first: u64, count: u64) -> Result<Vec<[u8; 32]>, Error> {
+        self.layer_hashes(0, first, count)
+    }
+
+    /// Hashes `first..first + count` of `layer`, read from the local
+    /// `.blocks`. Unverified, like [`Self::leaf_hashes`].
+    pub fn layer_hashes(
+        &self,
+        layer: usize,
+        first: u64,
+        count: u64,
+    ) -> Result<Vec<[u8; 32]>, Error> {
+        let have = *self.counts.get(layer).ok_or_else(|| {
+            Error::out_of_range(format!(
+                "layer {} out of range for {} layers",
+                layer,
+                self.counts.len()
+            ))
+        })?;
+        if first + count > have {
+            return Err(Error::out_of_range(format!(
+                "layer {} hashes {}..{} out of range for {} hashes",
+                layer,
+                first,
+                first + count,
+                have
+            )));
+        }
+        let bytes =
+            self.read_blocks_slice(self.offsets[layer] + first * 32, count as usize * 32)?;
+        Ok(bytes
+            .chunks_exact(32)
+            .map(|c| c.try_into().expect("32-byte chunk"))
+            .collect())
+    }
+
+    /// Byte offset of each layer within `.blocks`.
+    pub fn offsets(&self) -> &[u64] {
+        &self.offsets
+    }
+
+    /// The exact size `.blocks` must have: 32 bytes per hash of every layer.
+    pub fn blocks_size(&self) -> u64 {
+        self.counts.iter().sum::<u64>() * 32
+    }
+
+    /// Bytes of `.blocks` actually stored locally (after the header, for a
+    /// single-file artifact).
+    pub fn stored_blocks_size(&self) -> Result<u64, Error> {
+        let path = self
+            .blocks_path
+            .as_ref()
+            .ok_or_else(|| Error::malformed("tree: no local .blocks file"))?;
+        let len = std::fs::metadata(path)
+            .map_err(|e| Error::io(format!("stat {}: {}", path.display(), e)))?
+            .len();
+        Ok(len.saturating_sub(self.blocks_offset))
+    }
+
+    /// Where `.blocks` starts within its file: 0 for the two-file artifact,
+    /// the header size for a single-file one.
+    pub fn blocks_offset(&self) -> u64 {
+        self.blocks_offset
+    }
+
+    /// The canonical root manifest (spec section 5.1) the head describes.
+    pub fn manifest(&self) -> Vec<u8> {
+        manifest_bytes_with(self.profile, self.length, &self.tree_hex)
+    }
+
+    /// Whether the identifier binds to the manifest: `G(manifest) == identifier`.
+    pub fn binds(&self) -> bool {
+        self.check_identifier().is_ok()
+    }
+
+    /// Check the whole artifact without the data, e.g. before publishing or
+    /// mirroring it: the identifier binds, `.blocks` is exactly
+    /// [`Self::blocks_size`] bytes, every layer above the leaves is `g` of the
+    /// groups of the layer below (recomputed in parallel), and the top layer
+    /// reduces to the tree root. Leaf hashes are not checked: that needs the
+    /// data, see [`Self::validate`].
+    pub fn verify_structure(&self) -> Result<(), Error> {
+        self.check_identifier()?;
+        let size = self.stored_blocks_size()?;
+        if size != self.blocks_size() {
+            return Err(Error::malformed(format!(
+                "blocks: {} bytes stored, expected {}",
+                size,
+                self.blocks_size()
+            )));
+        }
+        self.check_declared()?;
+        let path = self
+            .blocks_path
+            .as_ref()
+            .expect("stored_blocks_size checked");
+
+        let top = self.counts.len() - 1;
+        for layer in 0..top {
+            let parents = self.layer_hashes(layer + 1, 0, self.counts[layer + 1])?;
+            self.verify_groups(path, layer, &parents)?;
+        }
+        let top_hashes = self.layer_hashes(top, 0, self.counts[top])?;
+        // A single leaf is the root itself; otherwise the top layer wraps once.
+        let root = if self.counts[0] == 1 {
+            top_hashes[0]
+        } else {
+            self.profile.g(&top_hashes.concat())
+        };
+        if hex_to_32(&self.tree_hex) != Some(root) {
+            return Err(Error::validation(format!(
+                "validation failed: layer {} does not reduce to the tree root",
+                top
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check `g` of each hash-file group of `layer` against `parents`, the
+    /// layer above, spreading the groups over worker threads.
+    fn verify_groups(&self, path: &Path, layer: usize, parents: &[[u8; 32]]) -> Result<(), Error> {
+        let next = AtomicU64::new(0);
+        let failed = AtomicBool::new(false);
+        let workers = available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(4)
+            .min(parents.len());
+        thread::scope(|s| {
+            let handles: Vec<_> = (0..workers)
+                .map(|_| {
+                    s.spawn(|| -> Result<(), Error> {
+                        let mut f = File::open(path).map_err(|e| {
+                            Error::io(format!("cannot open {}: {}", path.display(), e))
+                        })?;
+                        let mut buf = Vec::new();
+                        while !failed.load(Ordering::Relaxed) {
+                            let group = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(parent) = parents.get(group as usize) else {
+                                break;
+                            };
+                            let (off, len) = self.group_range(layer, group * FANOUT as u64);
+                            buf.resize(len, 0);
+                            f.seek(SeekFrom::Start(self.blocks_offset + off))
+                                .and_then(|_| f.read_exact(&mut buf))
+                                .map_err(|e| {
+                                    Error::malformed(format!("blocks truncated: {}", e))
+                                })?;
+                            if self.profile.g(&buf) != *parent {
+                                failed.store(true, Ordering::Relaxed);
+                                return Err(Error::validation(format!(
+                                    "validation failed: layer {} group {} does not hash to \
+                                     layer {} hash {}",
+                                    layer,
+                                    group,
+                                    layer + 1,
+                                    group
+                                )));
+                            }
+                        }
+                        Ok(())
+                    })
+                })
+                .collect();
+            handles.into_iter().try_for_each(|h| {
+                h.join()
+                    .map_err(|_| Error::other("structure worker panicked"))?
+            })
+        })
+    }
+
+    /// The verification path of the data block holding byte `offset`: the hash
+    /// it is checked against at each layer, leaf first, as read from the local
+    /// `.blocks` (unverified). The last step's group reduces to the root.
+    pub fn path(&self, offset: u64) -> Result<Vec<PathStep>, Error> {
+        if offset >= self.length.max(1) {
+            return Err(Error::out_of_range(format!(
+                "offset {} out of range for length {}",
+                offset, self.length
+            )));
+        }
+        let mut index = offset / BLOCK as u64;
+        let mut steps = Vec::with_capacity(self.counts.len());
+        for layer in 0..self.counts.len() {
+            steps.push(PathStep {
+                layer,
+                index,
+                group: index / FANOUT as u64,
+                position: index % FANOUT as u64,
+                blocks_offset: self.offsets[layer] + index * 32,
+                hash: self.layer_hashes(layer, index, 1)?[0],
+            });
+            index /= FANOUT as u64;
+        }
+        Ok(steps)
+    }
+
+    /// Resolve `[start, end)` defaults and bounds-check it.
+    fn check_range(&self, start: Option<u64>, end: Option<u64>) -> Result<(u64, u64), Error> {
+        let start = start.unwrap_or(0);
+        let end = end.unwrap_or(self.length);
+        if start > end || end > self.length {
+            return Err(Error::out_of_range(format!(
+                "range {}..{} out of bounds for length {}",
+                start, end, self.length
+            )));
+        }
+        Ok((start, end))
+    }
 }
 
-fn with_ext(name: &Path, ext: &str) -> PathBuf {
-    let mut s = name.as_os_str().to_os_string();
-    s.push(".");
-    s.push(ext);
-    PathBuf::from(s)
+/// One hash on a data block's verification path (see [`PersistedTree::path`]):
+/// hash `index` of `layer`, at `position` within hash-file group `group`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathStep {
+    pub layer: usize,
+    pub index: u64,
+    pub group: u64,
+    pub position: u64,
+    /// Byte offset of the hash within `.blocks`.
+    pub blocks_offset: u64,
+    pub hash: [u8; 32],
 }
 
-fn hex_to_32(s: &str) -> Option<[u8; 32]> {
-    if s.len() != 64 {
-        return None;
+/// Verifies data blocks one at a time against a tree head.
+///
+/// The hash-file groups on each block's path are pulled through a caller-supplied
+/// `fetch(offset, len)` over the `.blocks` byte space — a local file, an HTTP
+/// range request, anything — and one group per layer is cached, so a run of
+/// neighbouring blocks fetches each group once. Obtained from
+/// [`PersistedTree::verifier`]; the `std`-free engine is [`PathVerifier`].
+pub struct BlockVerifier<'t> {
+    tree: &'t PersistedTree,
+    path: PathVerifier<'static>,
+}
+
+impl BlockVerifier<'_> {
+    /// Exact length of data block `index` (the final block may be short).
+    pub fn block_len(&self, index: u64) -> usize {
+        self.path.block_len(index)
+    }
+
+    /// Verify `block` as data block `index`, recomputing `G` upward to the tree
+    /// root (spec section 6 steps 4-5).
+    pub fn verify_block<F>(&mut self, index: u64, block: &[u8], fetch: F) -> Result<(), Error>
+    where
+        F: FnMut(u64, usize) -> Result<Vec<u8>, Error>,
+    {
+        debug_assert_eq!(self.path.counts(), &self.tree.counts[..]);
+        self.path.verify_block(index, block, fetch)
+    }
+}
+
+/// Version 2 head keys before the extensions, in their required order.
+const V2_KEYS: [&str; 9] = [
+    "terrapin-tree",
+    "algorithm",
+    "block_size",
+    "length",
+    "tree",
+    "identifier",
+    "layer_counts",
+    "blocks_size",
+    "top_layer",
+];
+
+/// The `.head` text of `tree` in `format`.
+fn head_text(tree: &BuiltTree, format: &HeadFormat) -> io::Result<String> {
+    let counts_str = tree
+        .layers
+        .iter()
+        .map(|l| (l.len() / 32).to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let extensions = match format {
+        HeadFormat::V1 => {
+            return Ok(format!(
+                "terrapin-tree: {}\nalgorithm: {}\nblock_size: {}\nlength: {}\ntree: {}\nidentifier: {}\nlayer_counts: {}\n",
+                HEAD_VERSION,
+                tree.profile.name(),
+                BLOCK,
+                tree.length,
+                tree.tree_hex(),
+                tree.identifier(),
+                counts_str,
+            ))
+        }
+        HeadFormat::V2 { extensions } => extensions,
+    };
+    let top = tree.layers.last().expect("at least one layer");
+    let blocks_size: usize = tree.layers.iter().map(|l| l.len()).sum();
+    let values = [
+        "2".to_string(),
+        tree.profile.name().to_string(),
+        BLOCK.to_string(),
+        tree.length.to_string(),
+        tree.tree_hex(),
+        tree.identifier(),
+        counts_str,
+        blocks_size.to_string(),
+        to_hex(&tree.profile.g(top)),
+    ];
+    let mut text = String::new();
+    for (key, value) in V2_KEYS.iter().zip(&values) {
+        text += &format!("{}: {}\n", key, value);
+    }
+    for (key, value) in extensions {
+        check_extension(key, value).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        text += &format!("ext.{}: {}\n", key, value);
+    }
+    Ok(text)
+}
+
+/// Parse a version 2 head: the core keys exactly once each in [`V2_KEYS`]
+/// order with canonical values, then sorted, unique extension lines.
+fn parse_v2(text: &str) -> Result<PersistedTree, Error> {
+    if text.contains('\r') {
+        return Err(Error::malformed("head: CR in a version 2 head"));
+    }
+    let body = text
+        .strip_suffix('\n')
+        .ok_or_else(|| Error::malformed("head: missing final newline"))?;
+    let mut lines = body.split('\n');
+    let mut values = Vec::with_capacity(V2_KEYS.len());
+    for key in V2_KEYS {
+        let line = lines
+            .next()
+            .ok_or_else(|| Error::malformed(format!("head: missing {}", key)))?;
+        let value = line
+            .strip_prefix(key)
+            .and_then(|rest| rest.strip_prefix(": "))
+            .ok_or_else(|| {
+                Error::malformed(format!(
+                    "head: expected {} in canonical order, got {:?}",
+                    key, line
+                ))
+            })?;
+        values.push(value);
+    }
+
+    let profile = profile_by_name(values[1])
+        .ok_or_else(|| Error::malformed(format!("head: unsupported algorithm {}", values[1])))?;
+    if values[2] != BLOCK.to_string() {
+        return Err(Error::malformed("head: block_size must be 2097152"));
+    }
+    let length = canonical_u64(values[3], "length")?;
+    let tree_hex = canonical_hex(values[4], "tree")?;
+    let id_hex = values[5]
+        .strip_prefix(values[1])
+        .and_then(|rest| rest.strip_prefix(':'))
+        .ok_or_else(|| Error::malformed("head: identifier not <algorithm>:<hex>"))?;
+    canonical_hex(id_hex, "identifier")?;
+    let counts = values[6]
+        .split(' ')
+        .map(|c| canonical_u64(c, "layer_counts"))
+        .collect::<Result<Vec<_>, _>>()?;
+    let blocks_size = canonical_u64(values[7], "blocks_size")?;
+    let top_layer = canonical_hex(values[8], "top_layer")?;
+
+    let mut extensions = BTreeMap::new();
+    for line in lines {
+        let (key, value) = line
+            .strip_prefix("ext.")
+            .and_then(|l| l.split_once(": "))
+            .ok_or_else(|| Error::malformed(format!("head: bad line {:?}", line)))?;
+        check_extension(key, value)?;
+        let last = extensions.keys().next_back().map(String::as_str);
+        if last.is_some_and(|last| key <= last) {
+            return Err(Error::malformed(format!(
+                "head: ext.{} out of order or repeated",
+                key
+            )));
+        }
+        extensions.insert(key.to_string(), value.to_string());
+    }
+
+    let mut tree =
+        PersistedTree::from_fields(profile, length, tree_hex, values[5].to_string(), counts)?;
+    if blocks_size != tree.blocks_size() {
+        return Err(Error::malformed(
+            "head: blocks_size inconsistent with layer_counts",
+        ));
+    }
+    tree.version = 2;
+    tree.top_layer = Some(top_layer);
+    tree.extensions = extensions;
+    Ok(tree)
+}
+
+/// A decimal `u64` without sign, padding or leading zeros.
+fn canonical_u64(value: &str, key: &str) -> Result<u64, Error> {
+    value
+        .parse::<u64>()
+        .ok()
+        .filter(|n| n.to_string() == value)
+        .ok_or_else(|| Error::malformed(format!("head: bad {}", key)))
+}
+
+/// 64 lowercase hex digits.
+fn canonical_hex(value: &str, key: &str) -> Result<String, Error> {
+    let lower_hex = |b: u8| matches!(b, b'0'..=b'9' | b'a'..=b'f');
+    if value.len() == 64 && value.bytes().all(lower_hex) {
+        Ok(value.to_string())
+    } else {
+        Err(Error::malformed(format!(
+            "head: {} not 64 lowercase hex",
+            key
+        )))
+    }
+}
+
+/// Extension keys are `<namespace>.<name>`, dot-separated segments of
+/// `[a-z0-9_-]`; values are one line.
+fn check_extension(key: &str, value: &str) -> Result<(), Error> {
+    let segment_ok = |s: &str| {
+        !s.is_empty()
+            && s.bytes()
+                .all(|b| matches!(b, b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-'))
+    };
+    if !key.contains('.') || !key.split('.').all(segment_ok) {
+        return Err(Error::malformed(format!(
+            "head: bad extension key {:?}",
+            key
+        )));
     }
-    let mut out = [0u8; 32];
-    for (i, byte) in out.iter_mut().enumerate() {
-        *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).ok()?;
+    if value.contains(['\n', '\r']) {
+        return Err(Error::malformed(format!(
+            "head: extension {} value is not one line",
+            key
+        )));
     }
-    Some(out)
+    Ok(())
+}
+
+/// Whether `path` is a file starting like a single-file artifact.
+fn is_single(path: &Path) -> bool {
+    let mut magic = [0u8; SINGLE_MAGIC.len()];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .is_ok_and(|()| magic == SINGLE_MAGIC.as_bytes())
+}
+
+/// The declared `blocks_offset` of a single-file header.
+fn single_offset(prefix: &[u8]) -> Result<u64, Error> {
+    let rest = prefix
+        .strip_prefix(SINGLE_MAGIC.as_bytes())
+        .ok_or_else(|| Error::malformed("head: not a single-file tree"))?;
+    let line = rest
+        .strip_prefix(b"blocks_offset: ")
+        .and_then(|r| r.split(|&b| b == b'\n').next())
+        .ok_or_else(|| Error::malformed("head: missing blocks_offset"))?;
+    let offset = std::str::from_utf8(line)
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|&o| o > 0 && o % SINGLE_ALIGN == 0)
+        .ok_or_else(|| Error::malformed("head: bad blocks_offset"))?;
+    Ok(offset)
+}
+
+/// `name` with `.ext` appended (`a.b` gives `a.b.ext`, unlike `set_extension`).
+pub(crate) fn with_ext(name: &Path, ext: &str) -> PathBuf {
+    let mut s = name.as_os_str().to_os_string();
+    s.push(".");
+    s.push(ext);
+    PathBuf::from(s)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::builder::TreeBuilder;
-    use crate::manifest::identifier;
+    use terrapin_core::{g, hex_to_32, identifier};
 
     fn build(data: &[u8]) -> BuiltTree {
         let mut b = TreeBuilder::new();
@@ -397,7 +990,7 @@ mod tests {
         for (i, b) in raw.iter_mut().enumerate() {
             *b = (i as u8).wrapping_mul(7).wrapping_add(3);
         }
-        let h = crate::manifest::to_hex(&raw);
+        let h = terrapin_core::to_hex(&raw);
         assert_eq!(hex_to_32(&h), Some(raw));
     }
 
@@ -6,8 +6,11 @@
 mod common;
 use common::*;
 
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use terrapin::{
-    g, identifier, identifier_from_parts, to_hex, tree_root, BuiltTree, TreeBuilder, BLOCK, FANOUT,
+    g, identifier, identifier_from_parts, to_hex, tree_root, BuiltTree, Profile, TreeBuilder,
+    BLOCK, FANOUT, SHA256,
 };
 
 // Verifies: REQ-TB-003
@@ -151,3 +154,66 @@ fn tree_hex_equals_to_hex_tree_root() {
     let bt = build_tree(&data);
     assert_eq!(bt.tree_hex(), to_hex(&tree_root(&data)));
 }
+
+/// `n` distinct synthetic leaves.
+fn synthetic_leaves(n: usize) -> Vec<[u8; 32]> {
+    (0..n as u64).map(|i| g(&i.to_le_bytes())).collect()
+}
+
+// Verifies: REQ-TB-012
+#[test]
+fn eager_reduction_matches_oracle_at_fanout_boundaries() {
+    let all = synthetic_leaves(2 * FANOUT + 1);
+    for n in [FANOUT - 1, FANOUT, FANOUT + 1, 2 * FANOUT - 1, 2 * FANOUT, 2 * FANOUT + 1] {
+        let mut b = TreeBuilder::new();
+        for h in &all[..n] {
+            b.push_leaf(h);
+        }
+        let bt = b.build(n as u64 * BLOCK as u64);
+        let hf = all[..n].concat();
+        assert_eq!(bt.root, tree_root(&hf), "n {}", n);
+        if n > FANOUT {
+            let want: Vec<u8> = hf.chunks(BLOCK).flat_map(g).collect();
+            assert_eq!(bt.layers[1], want, "n {}", n);
+        } else {
+            assert_eq!(bt.layers.len(), 1, "n {}", n);
+        }
+    }
+}
+
+static G_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// `terrapin-sha256` counting its `g` calls.
+struct Counting;
+
+impl Profile for Counting {
+    fn name(&self) -> &'static str {
+        SHA256.name()
+    }
+
+    fn manifest_algorithm(&self) -> &'static str {
+        SHA256.manifest_algorithm()
+    }
+
+    fn g(&self, data: &[u8]) -> [u8; 32] {
+        G_CALLS.fetch_add(1, Ordering::Relaxed);
+        SHA256.g(data)
+    }
+}
+
+// Verifies: REQ-TB-013
+#[test]
+fn build_only_hashes_the_unfinished_tail() {
+    let all = synthetic_leaves(2 * FANOUT + 1);
+    // (leaves, g calls left for build): full groups were hashed while pushing.
+    for (n, left) in [(2 * FANOUT, 1), (2 * FANOUT + 1, 2), (FANOUT, 0)] {
+        let mut b = TreeBuilder::with_profile(&Counting);
+        for h in &all[..n] {
+            b.push_leaf(h);
+        }
+        let before = G_CALLS.load(Ordering::Relaxed);
+        let bt = b.build(n as u64 * BLOCK as u64);
+        assert_eq!(G_CALLS.load(Ordering::Relaxed) - before, left, "n {}", n);
+        assert_eq!(bt.root, tree_root(&all[..n].concat()), "n {}", n);
+    }
+}
@@ -0,0 +1,186 @@
+//! Integration tests for the identifier cache (feature `cache`): hits on an
+//! unchanged file, misses after metadata changes (any, for the database), and
+//! both backends.
+#![cfg(all(feature = "cache", unix))]
+
+mod common;
+use common::*;
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use terrapin::{identifier, CacheEntry, FileKey, IdCache};
+
+/// A data file and an entry for it, recorded in `cache`.
+fn cached_file(tag: &str, cache: &IdCache) -> (TmpPath, CacheEntry) {
+    let data = fill(1000, 41);
+    let f = tmp_file(tag, &data);
+    let entry = CacheEntry {
+        identifier: identifier(&data),
+        tree: Some(PathBuf::from("/some/dir/tree with space.terra")),
+    };
+    let key = FileKey::of(f.path()).unwrap();
+    assert!(cache.put(f.path(), &key, &entry).unwrap());
+    (f, entry)
+}
+
+/// Set the file's mtime to `t`.
+fn set_mtime(path: &std::path::Path, t: SystemTime) {
+    fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(t)
+        .unwrap();
+}
+
+// Verifies: REQ-CACHE-001
+#[test]
+fn db_entries_hit_per_file_and_profile() {
+    let db = TmpPath::new("cache-db");
+    let cache = IdCache::Db(db.path().to_path_buf());
+    let (a, entry_a) = cached_file("cache-a", &cache);
+    let (b, entry_b) = cached_file("cache-b", &cache);
+
+    assert_eq!(cache.get(a.path(), "terrapin-sha256").unwrap(), Some(entry_a.clone()));
+    assert_eq!(cache.get(b.path(), "terrapin-sha256").unwrap(), Some(entry_b));
+    assert_eq!(cache.get(a.path(), "terrapin-blake3").unwrap(), None);
+
+    // Re-recording replaces the entry rather than adding one.
+    let key = FileKey::of(a.path()).unwrap();
+    let bare = CacheEntry {
+        tree: None,
+        ..entry_a
+    };
+    assert!(cache.put(a.path(), &key, &bare).unwrap());
+    assert_eq!(cache.get(a.path(), "terrapin-sha256").unwrap(), Some(bare));
+    assert_eq!(fs::read_to_string(db.path()).unwrap().lines().count(), 2);
+
+    // A missing database is empty, not an error.
+    let empty = IdCache::Db(TmpPath::new("cache-none").path().join("ids"));
+    assert_eq!(empty.get(a.path(), "terrapin-sha256").unwrap(), None);
+}
+
+// Verifies: REQ-CACHE-002
+#[test]
+fn db_entries_miss_after_any_metadata_change() {
+    let db = TmpPath::new("cache-inval-db");
+    let cache = IdCache::Db(db.path().to_path_buf());
+    let get = |f: &TmpPath| cache.get(f.path(), "terrapin-sha256").unwrap();
+
+    // Same size, same mtime, different content: ctime gives it away.
+    let (f, _) = cached_file("cache-rewrite", &cache);
+    let mtime = fs::metadata(f.path()).unwrap().modified().unwrap();
+    std::thread::sleep(Duration::from_millis(20));
+    fs::write(f.path(), fill(1000, 42)).unwrap();
+    set_mtime(f.path(), mtime);
+    assert_eq!(get(&f), None);
+
+    // mtime alone.
+    let (f, _) = cached_file("cache-touch", &cache);
+    set_mtime(f.path(), SystemTime::UNIX_EPOCH + Duration::from_secs(1));
+    assert_eq!(get(&f), None);
+
+    // ctime alone (chmod).
+    let (f, _) = cached_file("cache-chmod", &cache);
+    std::thread::sleep(Duration::from_millis(20));
+    let mut perms = fs::metadata(f.path()).unwrap().permissions();
+    perms.set_readonly(true);
+    fs::set_permissions(f.path(), perms).unwrap();
+    assert_eq!(get(&f), None);
+
+    // Size, and replacement by another inode.
+    let (f, _) = cached_file("cache-grow", &cache);
+    fs::OpenOptions::new()
+        .append(true)
+        .open(f.path())
+        .and_then(|mut h| std::io::Write::write_all(&mut h, b"x"))
+        .unwrap();
+    assert_eq!(get(&f), None);
+    let (f, _) = cached_file("cache-replace", &cache);
+    let other = tmp_file("cache-replacement", &fill(1000, 41));
+    fs::rename(other.path(), f.path()).unwrap();
+    assert_eq!(get(&f), None);
+}
+
+// Verifies: REQ-CACHE-003
+#[test]
+fn nothing_is_recorded_for_a_file_changed_while_hashing() {
+    let db = TmpPath::new("cache-race-db");
+    let cache = IdCache::Db(db.path().to_path_buf());
+    let f = tmp_file("cache-race", b"before");
+    let before = FileKey::of(f.path()).unwrap();
+    fs::write(f.path(), b"after!!").unwrap();
+    let entry = CacheEntry {
+        identifier: identifier(b"before"),
+        tree: None,
+    };
+    assert!(!cache.put(f.path(), &before, &entry).unwrap());
+    assert_eq!(cache.get(f.path(), "terrapin-sha256").unwrap(), None);
+    assert!(!db.path().exists());
+}
+
+// Verifies: REQ-CACHE-004
+#[cfg(target_os = "linux")]
+#[test]
+fn xattr_entries_live_on_the_file() {
+    let cache = IdCache::Xattr;
+    let data = fill(500, 43);
+    let f = tmp_file("cache-xattr", &data);
+    let entry = CacheEntry {
+        identifier: identifier(&data),
+        tree: None,
+    };
+    let key = FileKey::of(f.path()).unwrap();
+    match cache.put(f.path(), &key, &entry) {
+        Ok(stored) => assert!(stored),
+        // The temp filesystem has no user attributes: nothing to test here.
+        Err(e) if e.message().contains("not supported") => return,
+        Err(e) => panic!("{}", e),
+    }
+    let hit = cache.get(f.path(), "terrapin-sha256").unwrap();
+    assert_eq!(hit.as_ref(), Some(&entry));
+    assert_eq!(cache.get(f.path(), "terrapin-blake3").unwrap(), None);
+
+    // As documented, an attribute entry does not check ctime: a chmod hits.
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(f.path(), fs::Permissions::from_mode(0o600)).unwrap();
+    assert_eq!(cache.get(f.path(), "terrapin-sha256").unwrap(), Some(entry));
+
+    // A copy is another inode and does not inherit the entry's validity.
+    let copy = TmpPath::new("cache-xattr-copy");
+    fs::copy(f.path(), copy.path()).unwrap();
+    assert_eq!(cache.get(copy.path(), "terrapin-sha256").unwrap(), None);
+
+    set_mtime(f.path(), SystemTime::UNIX_EPOCH + Duration::from_secs(1));
+    assert_eq!(cache.get(f.path(), "terrapin-sha256").unwrap(), None);
+}
+
+// Verifies: REQ-CACHE-005
+#[test]
+fn concurrent_db_writers_keep_each_others_entries() {
+    let db = TmpPath::new("cache-concurrent-db");
+    let cache = IdCache::Db(db.path().to_path_buf());
+    let files: Vec<(TmpPath, CacheEntry)> = std::thread::scope(|s| {
+        let writers: Vec<_> = (0..8)
+            .map(|t| {
+                let cache = &cache;
+                s.spawn(move || {
+                    (0..8)
+                        .map(|i| cached_file(&format!("cache-concurrent-{}-{}", t, i), cache))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        writers
+            .into_iter()
+            .flat_map(|w| w.join().unwrap())
+            .collect()
+    });
+    for (f, entry) in &files {
+        let hit = cache.get(f.path(), "terrapin-sha256").unwrap();
+        assert_eq!(hit.as_ref(), Some(entry), "{}", f.path().display());
+    }
+    assert_eq!(fs::read_to_string(db.path()).unwrap().lines().count(), 64);
+}
@@ -11,8 +11,8 @@ use std::io::{self, Write};
 use std::path::Path;
 
 use terrapin::{
-    derive_counts, g, identifier, identifier_from_parts, to_hex, tree_root, PersistedTree, BLOCK,
-    FANOUT,
+    derive_counts, g, identifier, identifier_from_parts, to_hex, tree_root, ErrorKind,
+    PersistedTree, BLOCK, FANOUT,
 };
 
 // ---------------------------------------------------------------------------
@@ -177,7 +177,7 @@ fn cat_emits_no_bytes_past_first_failure() {
     let err = pt
         .validate(dp.path(), Some(s), Some(e), Some(&mut out))
         .unwrap_err();
-    assert!(err.contains("validation failed"), "got: {}", err);
+    assert_eq!(err.kind(), ErrorKind::Validation, "got: {}", err);
 
     // Only the verified earlier block was emitted: exactly data[s..BLOCK].
     assert_eq!(out, &data[s as usize..BLOCK]);
@@ -203,7 +203,7 @@ fn cat_surfaces_writer_errors() {
     let err = pt
         .validate(dp.path(), None, None, Some(&mut w))
         .unwrap_err();
-    assert!(err.contains("write output"), "got: {}", err);
+    assert_eq!(err.kind(), ErrorKind::Io, "got: {}", err);
 }
 
 // ---------------------------------------------------------------------------
@@ -227,7 +227,7 @@ fn length_reinterpretation_is_prevented() {
     let dp2 = TmpPath::new("data2");
     std::fs::write(dp2.path(), &fill(2000, 8)).unwrap();
     let err = pt.validate(dp2.path(), None, None, None).unwrap_err();
-    assert!(err.contains("length"), "got: {}", err);
+    assert_eq!(err.kind(), ErrorKind::Validation, "got: {}", err);
 }
 
 // Verifies: REQ-SEC-002
@@ -248,11 +248,7 @@ fn forged_tree_fails_against_trusted_identifier() {
     let err = pt_forged
         .validate(_dp_b.path(), None, None, None)
         .unwrap_err();
-    assert!(
-        err.contains("identifier does not match manifest"),
-        "got: {}",
-        err
-    );
+    assert_eq!(err.kind(), ErrorKind::Malformed, "got: {}", err);
 }
 
 // Verifies: REQ-SEC-003
@@ -281,7 +277,7 @@ fn truncation_at_the_hasher_is_detected() {
     f.set_len(data.len() as u64 - 17).unwrap();
 
     let err = pt.validate(dp.path(), None, None, None).unwrap_err();
-    assert!(err.contains("length"), "got: {}", err);
+    assert_eq!(err.kind(), ErrorKind::Validation, "got: {}", err);
 }
 
 // Verifies: REQ-SEC-005
@@ -297,7 +293,7 @@ fn swapped_or_duplicated_blocks_are_detected() {
     std::fs::write(dp.path(), &tampered).unwrap();
 
     let err = pt.validate(dp.path(), None, None, None).unwrap_err();
-    assert!(err.contains("validation failed"), "got: {}", err);
+    assert_eq!(err.kind(), ErrorKind::Validation, "got: {}", err);
 }
 
 // Verifies: REQ-SEC-007
@@ -311,7 +307,7 @@ fn non_canonical_manifest_rejected_at_validation_boundary() {
         rewrite_head_line(&tp.with_ext("head"), "tree", "abcdef"); // not 64 hex
         let pt = PersistedTree::read(tp.path()).unwrap();
         let err = pt.validate(dp.path(), None, None, None).unwrap_err();
-        assert!(err.contains("64 hex"), "got: {}", err);
+        assert_eq!(err.kind(), ErrorKind::Malformed, "got: {}", err);
     }
 
     // (b) validate recomputes G(manifest) canonically: a mismatched (but
@@ -322,11 +318,7 @@ fn non_canonical_manifest_rejected_at_validation_boundary() {
         rewrite_head_line(&tp.with_ext("head"), "identifier", &other_id);
         let pt = PersistedTree::read(tp.path()).unwrap();
         let err = pt.validate(dp.path(), None, None, None).unwrap_err();
-        assert!(
-            err.contains("identifier does not match manifest"),
-            "got: {}",
-            err
-        );
+        assert_eq!(err.kind(), ErrorKind::Malformed, "got: {}", err);
     }
 }
 
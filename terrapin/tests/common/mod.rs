@@ -163,7 +163,8 @@ pub fn build_tree(data: &[u8]) -> BuiltTree {
 
 static COUNTER: AtomicU64 = AtomicU64::new(0);
 
-/// A unique temp path that removes itself (and `.head`/`.blocks` siblings) on drop.
+/// A unique temp path that removes itself (file or directory, and
+/// `.head`/`.blocks`/`.lock` siblings) on drop.
 pub struct TmpPath(pub PathBuf);
 
 impl TmpPath {
@@ -184,10 +185,19 @@ impl TmpPath {
     }
 }
 
+/// A fresh [`TmpPath`] holding `data`.
+pub fn tmp_file(tag: &str, data: &[u8]) -> TmpPath {
+    let f = TmpPath::new(tag);
+    std::fs::write(f.path(), data).unwrap();
+    f
+}
+
 impl Drop for TmpPath {
     fn drop(&mut self) {
         let _ = std::fs::remove_file(&self.0);
+        let _ = std::fs::remove_dir_all(&self.0);
         let _ = std::fs::remove_file(self.with_ext("head"));
         let _ = std::fs::remove_file(self.with_ext("blocks"));
+        let _ = std::fs::remove_file(self.with_ext("lock"));
     }
 }
@@ -0,0 +1,213 @@
+//! Integration tests for directory datasets (spec section 11): the canonical
+//! listing, the directory identifier, and member validation via
+//! `attest_dir` + `DirListing::member_tree`.
+
+mod common;
+use common::*;
+
+use std::path::Path;
+
+use terrapin::{
+    attest_dir, dir_listing, g, identifier, member_base, to_hex, DirEntry, DirListing, ErrorKind,
+    BLOCK, DIR_PREFIX,
+};
+
+/// Create `files` (relative path, contents) under a fresh temp directory.
+fn tree_of(tag: &str, files: &[(&str, &[u8])]) -> TmpPath {
+    let dir = TmpPath::new(tag);
+    for (rel, data) in files {
+        let p = dir.path().join(rel);
+        std::fs::create_dir_all(p.parent().unwrap()).unwrap();
+        std::fs::write(p, data).unwrap();
+    }
+    std::fs::create_dir_all(dir.path()).unwrap();
+    dir
+}
+
+fn entry(path: &str, length: u64) -> DirEntry {
+    DirEntry {
+        path: path.to_string(),
+        mode: 0o100644,
+        length,
+        identifier: identifier(&vec![0u8; length as usize]),
+    }
+}
+
+/// Remove `<base>.listing` and `<base>.d/`.
+fn cleanup_attestation(base: &Path) {
+    let _ = std::fs::remove_file(format!("{}.listing", base.display()));
+    let _ = std::fs::remove_dir_all(format!("{}.d", base.display()));
+}
+
+// Verifies: REQ-DIR-001
+#[test]
+fn dir_listing_is_sorted_canonical_and_commits_each_file() {
+    let big = fill(BLOCK + 17, 1);
+    let dir = tree_of(
+        "dir-listing",
+        &[
+            ("b.bin", b"bee"),
+            ("a/z.bin", &big),
+            ("a/y.bin", b""),
+            ("a-b", b"dash sorts before slash"),
+        ],
+    );
+    let listing = dir_listing(dir.path()).unwrap();
+    let paths: Vec<&str> = listing.entries().iter().map(|e| e.path.as_str()).collect();
+    assert_eq!(paths, vec!["a-b", "a/y.bin", "a/z.bin", "b.bin"]);
+
+    let z = listing.entry("a/z.bin").unwrap();
+    assert_eq!(z.length, big.len() as u64);
+    assert_eq!(z.identifier, identifier(&big));
+    let want = format!(
+        "terrapin-dir: sha256\n100644 {} {} a-b\n100644 0 {} a/y.bin\n100644 {} {} a/z.bin\n100644 3 {} b.bin\n",
+        23,
+        identifier(b"dash sorts before slash"),
+        identifier(b""),
+        big.len(),
+        identifier(&big),
+        identifier(b"bee"),
+    );
+    assert_eq!(String::from_utf8(listing.to_bytes()).unwrap(), want);
+    assert_eq!(
+        listing.identifier(),
+        format!("{}{}", DIR_PREFIX, to_hex(&g(want.as_bytes())))
+    );
+    assert_eq!(DirListing::parse(want.as_bytes()).unwrap(), listing);
+
+    // Content, name and executable bit all change the directory identifier.
+    let id = listing.identifier();
+    std::fs::write(dir.path().join("b.bin"), b"bef").unwrap();
+    assert_ne!(dir_listing(dir.path()).unwrap().identifier(), id);
+    std::fs::write(dir.path().join("b.bin"), b"bee").unwrap();
+    assert_eq!(dir_listing(dir.path()).unwrap().identifier(), id);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let p = dir.path().join("b.bin");
+        std::fs::set_permissions(&p, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let l = dir_listing(dir.path()).unwrap();
+        assert_eq!(l.entry("b.bin").unwrap().mode, 0o100755);
+        assert_ne!(l.identifier(), id);
+        std::fs::set_permissions(&p, std::fs::Permissions::from_mode(0o644)).unwrap();
+    }
+    std::fs::rename(dir.path().join("b.bin"), dir.path().join("c.bin")).unwrap();
+    assert_ne!(dir_listing(dir.path()).unwrap().identifier(), id);
+}
+
+// Verifies: REQ-DIR-002
+#[test]
+fn dir_listing_parse_rejects_non_canonical_forms() {
+    let ok = DirListing::new(vec![entry("b", 1), entry("a", 0)]).unwrap();
+    let text = String::from_utf8(ok.to_bytes()).unwrap();
+    assert!(DirListing::parse(text.as_bytes()).is_ok());
+
+    let (la, lb) = (text.lines().nth(1).unwrap(), text.lines().nth(2).unwrap());
+    let h = "terrapin-dir: sha256\n";
+    let bad = [
+        format!("{}{}\n", h, la).replace("terrapin-dir: sha256", "terrapin-dir: sha512"),
+        format!("{}{}\n{}\n", h, lb, la), // unsorted
+        format!("{}{}\n{}\n", h, la, la), // duplicate
+        format!("{}{}", h, la),           // no final newline
+        format!("{}{}\n", h, la.replacen("100644", "100600", 1)),
+        format!("{}{}\n", h, la.replacen(" 0 ", " 00 ", 1)),
+        format!("{}{}\n", h, la.replacen(" 0 ", "  0 ", 1)),
+        format!("{}{}\r\n", h, la),
+        format!("{}{}\n", h, la.replace(" a", " ../a")),
+        format!("{}{}\n", h, la.replace(" a", " /a")),
+        format!("{}{}\n", h, la.replace(" a", " x//a")),
+        format!("{}\n", h),
+    ];
+    for b in &bad {
+        assert!(DirListing::parse(b.as_bytes()).is_err(), "accepted {:?}", b);
+    }
+    // A path cannot be both a file and a directory.
+    assert!(DirListing::new(vec![entry("a", 0), entry("a/b", 0)]).is_err());
+    assert!(DirListing::new(vec![entry("a", 0), entry("a", 1)]).is_err());
+    // The empty directory is just the header.
+    let empty = DirListing::new(vec![]).unwrap();
+    assert_eq!(empty.to_bytes(), h.as_bytes());
+}
+
+// Verifies: REQ-DIR-003
+#[test]
+fn attested_dir_validates_single_member_via_its_tree() {
+    let shard = fill(2 * BLOCK + 5, 3);
+    let dir = tree_of(
+        "dir-attest",
+        &[("shards/0000.bin", &shard), ("README", b"hello")],
+    );
+    let base = TmpPath::new("dir-attest-out");
+    let listing = attest_dir(dir.path(), base.path()).unwrap();
+    assert_eq!(
+        listing.identifier(),
+        dir_listing(dir.path()).unwrap().identifier()
+    );
+
+    // Listing read back binds to the identifier; the member tree binds to its entry.
+    let read = DirListing::read(&base.with_ext("listing")).unwrap();
+    read.check_against(&listing.identifier()).unwrap();
+    let pt = read.member_tree(base.path(), "shards/0000.bin").unwrap();
+    let member = dir.path().join("shards/0000.bin");
+    let mut out = Vec::new();
+    pt.validate(&member, Some(BLOCK as u64), None, Some(&mut out))
+        .unwrap();
+    assert_eq!(out, &shard[BLOCK..]);
+
+    // Tampered member data fails; so does another member's tree swapped in.
+    let mut bad = shard.clone();
+    bad[BLOCK + 1] ^= 1;
+    std::fs::write(&member, &bad).unwrap();
+    assert!(pt.validate(&member, None, None, None).is_err());
+    let readme = member_base(base.path(), "README");
+    let shard_tree = member_base(base.path(), "shards/0000.bin");
+    for ext in ["head", "blocks"] {
+        std::fs::copy(
+            format!("{}.{}", readme.display(), ext),
+            format!("{}.{}", shard_tree.display(), ext),
+        )
+        .unwrap();
+    }
+    let err = read
+        .member_tree(base.path(), "shards/0000.bin")
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), ErrorKind::IdentifierMismatch);
+    assert!(read.member_tree(base.path(), "missing").is_err());
+    assert!(read
+        .check_against(&format!("{}{}", DIR_PREFIX, "0".repeat(64)))
+        .is_err());
+    cleanup_attestation(base.path());
+}
+
+// Verifies: REQ-DIR-003
+#[test]
+fn member_trees_cannot_collide_with_member_names() {
+    // File `a` next to a directory named like `a`'s tree files.
+    let dir = tree_of(
+        "dir-collide",
+        &[
+            ("a", b"file a"),
+            ("a.head/x", b"x"),
+            ("a.blocks", b"blocks"),
+        ],
+    );
+    let base = TmpPath::new("dir-collide-out");
+    let listing = attest_dir(dir.path(), base.path()).unwrap();
+    for e in listing.entries() {
+        let pt = listing.member_tree(base.path(), &e.path).unwrap();
+        pt.validate(&dir.path().join(&e.path), None, None, None)
+            .unwrap();
+    }
+    cleanup_attestation(base.path());
+}
+
+// Verifies: REQ-DIR-004
+#[cfg(unix)]
+#[test]
+fn dir_listing_rejects_symlinks() {
+    let dir = tree_of("dir-symlink", &[("real", b"x")]);
+    std::os::unix::fs::symlink(dir.path().join("real"), dir.path().join("link")).unwrap();
+    let err = dir_listing(dir.path()).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::Other);
+}
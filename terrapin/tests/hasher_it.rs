@@ -0,0 +1,87 @@
+//! Integration tests for the incremental `Hasher`: any `update` chunking, the
+//! `io::Write` impl and background hashing all produce the tree and identifier
+//! of the whole buffer.
+
+mod common;
+use common::*;
+
+use std::io::{self, Cursor, Write};
+
+use terrapin::{identifier, tree_root, Hasher, BLOCK};
+
+// Verifies: REQ-HS-001
+#[test]
+fn hasher_matches_identifier_at_block_boundaries() {
+    for len in [0, 1, BLOCK - 1, BLOCK, BLOCK + 1, 2 * BLOCK, 3 * BLOCK + 5] {
+        let data = fill(len, 2 * len as u64 + 1);
+        // One update, then odd-sized pieces straddling every boundary.
+        let mut whole = Hasher::new();
+        whole.update(&data);
+        assert_eq!(whole.length(), len as u64);
+        let bt = whole.finalize();
+        assert_eq!(bt.identifier(), identifier(&data), "len {}", len);
+        assert_eq!(bt.root, tree_root(&data), "len {}", len);
+
+        let mut pieces = Hasher::new();
+        for c in data.chunks(BLOCK / 3 + 1) {
+            pieces.update(c);
+        }
+        pieces.update(&[]);
+        assert_eq!(pieces.identifier(), identifier(&data), "len {}", len);
+    }
+}
+
+// Verifies: REQ-HS-002
+#[test]
+fn hasher_background_threads_match_inline() {
+    let data = fill(9 * BLOCK + 77, 3);
+    for threads in [0, 1, 2, 4] {
+        let mut h = Hasher::new().with_threads(threads);
+        for c in data.chunks(100_000) {
+            h.update(c);
+        }
+        let bt = h.finalize();
+        assert_eq!(bt.identifier(), identifier(&data), "threads {}", threads);
+        assert_eq!(bt.layers[0].len(), 10 * 32);
+    }
+    // Changing the thread count mid-stream keeps the blocks in flight.
+    let mut h = Hasher::new().with_threads(4);
+    h.update(&data[..3 * BLOCK + 5]);
+    h = h.with_threads(2);
+    h.update(&data[3 * BLOCK + 5..6 * BLOCK]);
+    h = h.with_threads(0);
+    h.update(&data[6 * BLOCK..]);
+    assert_eq!(h.identifier(), identifier(&data));
+    // Dropped unfinished: the workers shut down.
+    let mut h = Hasher::new().with_threads(3);
+    h.update(&data[..2 * BLOCK]);
+    drop(h);
+}
+
+// Verifies: REQ-HS-003
+#[test]
+fn hasher_is_an_io_write_sink() {
+    let data = fill(2 * BLOCK + 9, 5);
+    let mut h = Hasher::new();
+    let n = io::copy(&mut Cursor::new(&data), &mut h).unwrap();
+    h.flush().unwrap();
+    assert_eq!(n, data.len() as u64);
+    assert_eq!(h.identifier(), identifier(&data));
+
+    let mut h = Hasher::default();
+    write!(h, "hello {}", 42).unwrap();
+    assert_eq!(h.identifier(), identifier(b"hello 42"));
+}
+
+// Verifies: REQ-HS-004
+#[cfg(feature = "blake3")]
+#[test]
+fn hasher_uses_its_profile() {
+    let data = fill(BLOCK + 1, 7);
+    let mut h = Hasher::with_profile(terrapin::BLAKE3).with_threads(2);
+    h.update(&data);
+    assert_eq!(
+        h.identifier(),
+        terrapin::identifier_with(terrapin::BLAKE3, &data)
+    );
+}
@@ -0,0 +1,95 @@
+//! Integration tests for in-toto statements and DSSE envelopes (feature
+//! `intoto`).
+#![cfg(feature = "intoto")]
+
+use terrapin::{
+    build_from_reader_sync, pae, Envelope, InTotoStatement, SecretKey, SignatureAlgorithm, Subject,
+    PAYLOAD_TYPE, PREDICATE_TYPE, STATEMENT_TYPE,
+};
+
+fn statement() -> (InTotoStatement, String) {
+    let data = b"in-toto subject bytes".repeat(1000);
+    let bt = build_from_reader_sync(&data[..]).unwrap();
+    let st =
+        InTotoStatement::for_tree("data.bin", &bt.identifier(), bt.length, &bt.tree_hex()).unwrap();
+    (st, bt.identifier())
+}
+
+// Verifies: REQ-TOT-001
+#[test]
+fn statement_carries_identifier_as_subject_digest() {
+    let (st, id) = statement();
+    let v: serde_json::Value = serde_json::from_slice(&st.to_json()).unwrap();
+    assert_eq!(v["_type"], STATEMENT_TYPE);
+    assert_eq!(v["predicateType"], PREDICATE_TYPE);
+    assert_eq!(v["subject"][0]["name"], "data.bin");
+    let hex = id.strip_prefix("terrapin-sha256:").unwrap();
+    assert_eq!(v["subject"][0]["digest"]["terrapin-sha256"], hex);
+    assert_eq!(v["predicate"]["length"], 21000);
+
+    let parsed = InTotoStatement::parse(&st.to_json()).unwrap();
+    assert_eq!(parsed, st);
+    assert_eq!(parsed.identifier_for("data.bin").unwrap(), id);
+    assert!(parsed.identifier_for("other.bin").is_err());
+
+    // Subjects may carry other digests alongside; only terrapin ones count.
+    let mut s = Subject::new("x", &id).unwrap();
+    s.digest.insert("sha256".into(), "ab".repeat(32));
+    assert_eq!(s.identifier().unwrap(), id);
+    s.digest.remove("terrapin-sha256");
+    assert!(s.identifier().is_err());
+    assert!(Subject::new("x", "terrapin-sha256:XYZ").is_err());
+    assert!(InTotoStatement::parse(br#"{"_type":"https://in-toto.io/Statement/v0.1"}"#).is_err());
+}
+
+// Verifies: REQ-TOT-002
+#[test]
+fn envelope_signs_and_verifies_pae() {
+    assert_eq!(
+        pae("application/example", b"hello"),
+        b"DSSEv1 19 application/example 5 hello".to_vec()
+    );
+    let (st, id) = statement();
+    for alg in [SignatureAlgorithm::Ed25519, SignatureAlgorithm::EcdsaP256] {
+        let key = SecretKey::generate(alg).unwrap();
+        let public = key.public_key().unwrap();
+        let env = Envelope::sign(&st, &key).unwrap();
+        assert_eq!(env.payload_type, PAYLOAD_TYPE);
+        assert_eq!(env.signatures[0].keyid, public.key_id());
+
+        let parsed = Envelope::parse(&env.to_json()).unwrap();
+        assert_eq!(parsed, env);
+        let verified = parsed.verify(&public).unwrap();
+        assert_eq!(verified.identifier_for("data.bin").unwrap(), id);
+    }
+}
+
+// Verifies: REQ-TOT-003
+#[test]
+fn envelope_rejects_tampering_and_wrong_keys() {
+    let (st, _) = statement();
+    let key = SecretKey::generate(SignatureAlgorithm::Ed25519).unwrap();
+    let public = key.public_key().unwrap();
+    let env = Envelope::sign(&st, &key).unwrap();
+
+    let other = SecretKey::generate(SignatureAlgorithm::Ed25519).unwrap();
+    assert!(env.verify(&other.public_key().unwrap()).is_err());
+
+    let mut payload = env.clone();
+    let text = String::from_utf8(payload.payload).unwrap();
+    payload.payload = text.replace("data.bin", "evil.bin").into_bytes();
+    assert!(payload.verify(&public).is_err());
+
+    let mut retyped = env.clone();
+    retyped.payload_type = "application/json".into();
+    assert!(retyped.verify(&public).is_err());
+
+    let mut sig = env.clone();
+    sig.signatures[0].sig[3] ^= 1;
+    assert!(sig.verify(&public).is_err());
+
+    let mut unsigned = env.clone();
+    unsigned.signatures.clear();
+    assert!(unsigned.verify(&public).is_err());
+    assert!(Envelope::parse(b"{}").is_err());
+}
@@ -10,8 +10,8 @@ mod common;
 use common::*;
 
 use terrapin::{
-    g, identifier, identifier_from_parts, manifest_bytes, parse_manifest, to_hex, tree_root, BLOCK,
-    FANOUT,
+    g, g_many, identifier, identifier_from_parts, manifest_bytes, parse_manifest, to_hex,
+    tree_root, BLOCK, FANOUT,
 };
 
 // Known anchor constants (see spec section 3.0 / 5.3).
@@ -114,6 +114,29 @@ fn g_avalanche_on_single_bit_flip() {
     }
 }
 
+// Verifies: REQ-G-009
+#[test]
+fn g_many_matches_g_at_block_boundary_sizes() {
+    let sizes = [
+        0,
+        1,
+        BLOCK - 1,
+        BLOCK,
+        BLOCK + 1,
+        2 * BLOCK - 1,
+        2 * BLOCK,
+        2 * BLOCK + 1,
+    ];
+    let bufs: Vec<Vec<u8>> = sizes.iter().map(|&n| fill(n, 2 * n as u64 + 1)).collect();
+    let inputs: Vec<&[u8]> = bufs.iter().map(Vec::as_slice).collect();
+    let want: Vec<[u8; 32]> = inputs.iter().map(|d| g(d)).collect();
+    assert_eq!(g_many(&inputs), want);
+    // Every batch width, including one buffer and none.
+    for k in 0..=inputs.len() {
+        assert_eq!(g_many(&inputs[..k]), want[..k], "batch of {}", k);
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Hex helper
 // ---------------------------------------------------------------------------
@@ -0,0 +1,152 @@
+//! Integration tests for OCI image-layout export and verification (feature
+//! `oci`), against local layout directories.
+#![cfg(feature = "oci")]
+
+mod common;
+use common::*;
+
+use std::fs;
+use std::path::Path;
+
+use terrapin::{
+    identifier, oci_export, OciDataset, Sha256Context, ARTIFACT_TYPE, BLOCK, BLOCKS_MEDIA_TYPE,
+    DATA_MEDIA_TYPE, HEAD_MEDIA_TYPE, IDENTIFIER_ANNOTATION,
+};
+
+fn json(path: &Path) -> serde_json::Value {
+    serde_json::from_slice(&fs::read(path).unwrap()).unwrap()
+}
+
+fn blob(layout: &Path, d: &str) -> std::path::PathBuf {
+    layout
+        .join("blobs/sha256")
+        .join(d.strip_prefix("sha256:").unwrap())
+}
+
+fn export(tag: &str, data: &[u8]) -> (TmpPath, TmpPath) {
+    let file = tmp_file(tag, data);
+    let layout = TmpPath::new(&format!("{}-layout", tag));
+    oci_export(file.path(), layout.path(), Some("dataset")).unwrap();
+    (file, layout)
+}
+
+// Verifies: REQ-OCI-001
+#[test]
+fn export_writes_an_oci_image_layout() {
+    let data = fill(2 * BLOCK + 999, 11);
+    let (file, layout) = export("oci-layout", &data);
+    let l = layout.path();
+    assert_eq!(json(&l.join("oci-layout"))["imageLayoutVersion"], "1.0.0");
+    let index = json(&l.join("index.json"));
+    let entry = &index["manifests"][0];
+    assert_eq!(
+        entry["annotations"]["org.opencontainers.image.ref.name"],
+        "dataset"
+    );
+    let manifest = json(&blob(l, entry["digest"].as_str().unwrap()));
+    assert_eq!(manifest["artifactType"], ARTIFACT_TYPE);
+    assert_eq!(
+        manifest["annotations"][IDENTIFIER_ANNOTATION],
+        identifier(&data)
+    );
+
+    let layers = manifest["layers"].as_array().unwrap();
+    let types: Vec<_> = layers
+        .iter()
+        .map(|l| l["mediaType"].as_str().unwrap())
+        .collect();
+    assert_eq!(types, [DATA_MEDIA_TYPE, HEAD_MEDIA_TYPE, BLOCKS_MEDIA_TYPE]);
+    // Every blob is stored under the SHA-256 of its content.
+    for d in layers.iter().map(|l| l["digest"].as_str().unwrap()) {
+        let bytes = fs::read(blob(l, d)).unwrap();
+        assert_eq!(d, format!("sha256:{}", hex(&Sha256Context::digest(&bytes))));
+    }
+    assert_eq!(
+        fs::read(blob(l, layers[0]["digest"].as_str().unwrap())).unwrap(),
+        data
+    );
+    assert_eq!(layers[0]["size"], data.len() as u64);
+    drop(file);
+}
+
+// Verifies: REQ-OCI-002
+#[test]
+fn open_validates_whole_dataset_and_ranges() {
+    let data = fill(3 * BLOCK + 5, 13);
+    let (_file, layout) = export("oci-open", &data);
+    let ds = OciDataset::open(layout.path(), None).unwrap();
+    assert_eq!(ds.identifier, identifier(&data));
+    assert_eq!(ds.reference, "dataset");
+    ds.validate(None, None, None).unwrap();
+
+    let mut out = Vec::new();
+    ds.validate(
+        Some(BLOCK as u64 - 3),
+        Some(2 * BLOCK as u64 + 3),
+        Some(&mut out),
+    )
+    .unwrap();
+    assert_eq!(out, &data[BLOCK - 3..2 * BLOCK + 3]);
+    assert!(ds
+        .validate(Some(0), Some(data.len() as u64 + 1), None)
+        .is_err());
+    assert!(ds.tree().check_against(&identifier(b"other")).is_err());
+}
+
+// Verifies: REQ-OCI-003
+#[test]
+fn tampered_layers_are_rejected() {
+    let data = fill(2 * BLOCK + 1, 17);
+    let (_file, layout) = export("oci-tamper", &data);
+    let ds = OciDataset::open(layout.path(), None).unwrap();
+
+    // A flipped data byte fails only the ranges covering its block.
+    let mut bad = data.clone();
+    bad[BLOCK + 10] ^= 1;
+    fs::write(ds.data_path(), &bad).unwrap();
+    assert!(ds.validate(None, None, None).is_err());
+    assert!(ds
+        .validate(Some(BLOCK as u64), Some(BLOCK as u64 + 20), None)
+        .is_err());
+    ds.validate(Some(0), Some(100), None).unwrap();
+
+    // The head is checked against its digest before it is trusted.
+    let manifest_digest = ds.manifest_digest.clone();
+    let manifest = json(&blob(layout.path(), &manifest_digest));
+    let head = blob(
+        layout.path(),
+        manifest["layers"][1]["digest"].as_str().unwrap(),
+    );
+    let text = fs::read_to_string(&head).unwrap();
+    fs::write(&head, text.replace("length: ", "length: 1")).unwrap();
+    assert!(OciDataset::open(layout.path(), None).is_err());
+}
+
+// Verifies: REQ-OCI-004
+#[test]
+fn layouts_hold_several_datasets_by_ref() {
+    let layout = TmpPath::new("oci-multi");
+    let a = tmp_file("oci-multi-a", b"first dataset");
+    let b = tmp_file("oci-multi-b", &fill(BLOCK + 1, 19));
+    oci_export(a.path(), layout.path(), Some("a")).unwrap();
+    let eb = oci_export(b.path(), layout.path(), Some("b")).unwrap();
+
+    assert!(OciDataset::open(layout.path(), None).is_err());
+    assert!(OciDataset::open(layout.path(), Some("c")).is_err());
+    let ds = OciDataset::open(layout.path(), Some("b")).unwrap();
+    assert_eq!(ds.identifier, eb.identifier);
+    ds.validate(None, None, None).unwrap();
+
+    // Re-exporting under an existing ref replaces its index entry.
+    fs::write(a.path(), b"second version").unwrap();
+    let ea = oci_export(a.path(), layout.path(), Some("a")).unwrap();
+    let index = json(&layout.path().join("index.json"));
+    assert_eq!(index["manifests"].as_array().unwrap().len(), 2);
+    let ds = OciDataset::open(layout.path(), Some("a")).unwrap();
+    assert_eq!(ds.identifier, ea.identifier);
+    assert_eq!(ds.identifier, identifier(b"second version"));
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
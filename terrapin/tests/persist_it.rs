@@ -5,9 +5,13 @@
 mod common;
 use common::*;
 
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
-use terrapin::{derive_counts, identifier, BuiltTree, PersistedTree, TreeBuilder, BLOCK, FANOUT};
+use terrapin::{
+    derive_counts, identifier, BuiltTree, Error, ErrorKind, HeadFormat, PersistedTree, TreeBuilder,
+    BLOCK, FANOUT,
+};
 
 // ---------------------------------------------------------------------------
 // Local helpers (public API + std only).
@@ -30,7 +34,7 @@ fn add_ext(p: &Path, ext: &str) -> PathBuf {
 fn read_with_mangled_head(
     base: &TmpPath,
     f: impl FnOnce(String) -> String,
-) -> Result<PersistedTree, String> {
+) -> Result<PersistedTree, Error> {
     let hp = base.with_ext("head");
     let text = std::fs::read_to_string(&hp).unwrap();
     std::fs::write(&hp, f(text)).unwrap();
@@ -368,3 +372,383 @@ fn head_tree_hex_case_policy() {
         .expect("uppercase tree hex is accepted at read time");
     assert_eq!(pt.tree_hex, upper, "read stores the tree hex verbatim");
 }
+
+/// A synthetic two-layer tree (FANOUT+1 leaves) written under `tag`.
+fn two_layer_tree(tag: &str) -> (BuiltTree, TmpPath, PersistedTree) {
+    let n = FANOUT + 1;
+    let mut b = TreeBuilder::new();
+    for i in 0..n {
+        b.push_leaf(&terrapin::g(&(i as u64).to_le_bytes()));
+    }
+    let t = b.build(n as u64 * BLOCK as u64);
+    let base = TmpPath::new(tag);
+    PersistedTree::write(base.path(), &t).unwrap();
+    let pt = PersistedTree::read(base.path()).unwrap();
+    (t, base, pt)
+}
+
+// Verifies: REQ-PT-012
+#[test]
+fn inspection_exposes_layout_manifest_and_binding() {
+    let (t, base, pt) = two_layer_tree("inspect");
+    assert_eq!(pt.offsets(), [0, (FANOUT as u64 + 1) * 32]);
+    assert_eq!(pt.blocks_size(), (FANOUT as u64 + 3) * 32);
+    assert_eq!(
+        std::fs::metadata(base.with_ext("blocks")).unwrap().len(),
+        pt.blocks_size()
+    );
+    assert_eq!(
+        pt.manifest(),
+        terrapin::manifest_bytes(t.length, &t.tree_hex())
+    );
+    assert!(pt.binds());
+    assert_eq!(pt.layer_hashes(1, 1, 1).unwrap()[0], t.layers[1][32..64]);
+    assert!(pt.layer_hashes(1, 2, 1).is_err());
+    assert!(pt.layer_hashes(2, 0, 1).is_err());
+
+    let other = identifier(b"other");
+    let pt = read_with_mangled_head(&base, |s| {
+        let id = &s[s.find("identifier: ").unwrap() + 12..][..other.len()];
+        s.replace(id, &other)
+    })
+    .unwrap();
+    assert!(!pt.binds());
+    let pt = read_with_mangled_head(&base, |s| s.replace("length: ", "length: 1"));
+    assert!(pt.is_err(), "counts no longer fit the length");
+}
+
+// Verifies: REQ-PT-013
+#[test]
+fn path_names_group_and_position_per_layer() {
+    let (t, _base, pt) = two_layer_tree("path");
+    let last = FANOUT as u64;
+    let steps = pt.path(last * BLOCK as u64 + 7).unwrap();
+    assert_eq!(steps.len(), 2);
+    assert_eq!(
+        (steps[0].index, steps[0].group, steps[0].position),
+        (last, 1, 0)
+    );
+    assert_eq!(steps[0].blocks_offset, last * 32);
+    assert_eq!(steps[0].hash, t.layers[0][last as usize * 32..][..32]);
+    assert_eq!((steps[1].layer, steps[1].index), (1, 1));
+    assert_eq!((steps[1].group, steps[1].position), (0, 1));
+    assert_eq!(steps[1].hash, t.layers[1][32..64]);
+    assert_eq!(pt.path(5).unwrap()[0].position, 0);
+    assert!(pt.path(t.length).is_err());
+
+    // A single-block tree: the one leaf is the root.
+    let base = TmpPath::new("path-one");
+    PersistedTree::write(base.path(), &build_tree(b"abc")).unwrap();
+    let pt = PersistedTree::read(base.path()).unwrap();
+    let steps = pt.path(2).unwrap();
+    assert_eq!(steps.len(), 1);
+    assert_eq!(terrapin::to_hex(&steps[0].hash), pt.tree_hex);
+}
+
+/// Flip one bit of the `.blocks` byte at `at`.
+fn flip_blocks_byte(base: &TmpPath, at: u64) {
+    let path = base.with_ext("blocks");
+    let mut raw = std::fs::read(&path).unwrap();
+    raw[at as usize] ^= 1;
+    std::fs::write(&path, raw).unwrap();
+}
+
+// Verifies: REQ-PT-014
+#[test]
+fn verify_structure_accepts_well_formed_trees() {
+    let (_t, _base, pt) = two_layer_tree("structure");
+    pt.verify_structure().unwrap();
+    for (tag, len) in [("structure-empty", 0), ("structure-one", 100)] {
+        let base = TmpPath::new(tag);
+        PersistedTree::write(base.path(), &build_tree(&fill(len, 5))).unwrap();
+        PersistedTree::read(base.path())
+            .unwrap()
+            .verify_structure()
+            .unwrap();
+    }
+    let base = TmpPath::new("structure-three");
+    PersistedTree::write(base.path(), &build_tree(&fill(2 * BLOCK + 1, 6))).unwrap();
+    PersistedTree::read(base.path())
+        .unwrap()
+        .verify_structure()
+        .unwrap();
+}
+
+// Verifies: REQ-PT-015
+#[test]
+fn verify_structure_rejects_any_damage_to_blocks() {
+    // A leaf far from any validated range, in the second leaf group.
+    let (_t, base, pt) = two_layer_tree("structure-leaf");
+    flip_blocks_byte(&base, FANOUT as u64 * 32 + 3);
+    let e = pt.verify_structure().unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::Validation);
+    assert!(e.message().contains("layer 0 group 1"), "{}", e);
+
+    // The top layer no longer reduces to the root.
+    let (_t, base, pt) = two_layer_tree("structure-top");
+    flip_blocks_byte(&base, pt.offsets()[1]);
+    assert!(pt.verify_structure().is_err());
+
+    // Trailing garbage and truncation.
+    let (_t, base, pt) = two_layer_tree("structure-size");
+    let path = base.with_ext("blocks");
+    let mut raw = std::fs::read(&path).unwrap();
+    raw.push(0);
+    std::fs::write(&path, &raw).unwrap();
+    assert_eq!(
+        pt.verify_structure().unwrap_err().kind(),
+        ErrorKind::Malformed
+    );
+    raw.truncate(raw.len() - 33);
+    std::fs::write(&path, &raw).unwrap();
+    assert!(pt.verify_structure().is_err());
+
+    // A head whose identifier does not bind is refused up front.
+    let base = TmpPath::new("structure-bind");
+    PersistedTree::write(base.path(), &build_tree(b"abc")).unwrap();
+    let other = identifier(b"abd");
+    let pt = read_with_mangled_head(&base, |s| {
+        let id = &s[s.find("identifier: ").unwrap() + 12..][..other.len()];
+        s.replace(id, &other)
+    })
+    .unwrap();
+    assert!(pt.verify_structure().is_err());
+}
+
+// Verifies: REQ-PT-016
+#[test]
+fn single_file_artifact_round_trips_and_is_detected() {
+    let data = fill(2 * BLOCK + 300, 21);
+    let t = build_tree(&data);
+    let file = TmpPath::new("single");
+    PersistedTree::write_single(file.path(), &t).unwrap();
+    assert!(!file.with_ext("head").exists());
+
+    let raw = std::fs::read(file.path()).unwrap();
+    assert!(raw.starts_with(b"terrapin-tree-file: 1\nblocks_offset: 4096\nterrapin-tree: 1\n"));
+    assert_eq!(&raw[4096..], &t.layers.concat()[..]);
+
+    let pt = PersistedTree::read(file.path()).unwrap();
+    assert_eq!(pt.blocks_offset(), 4096);
+    assert_eq!(pt.identifier, t.identifier());
+    assert_eq!(pt.counts, counts_from_layers(&t));
+    assert_eq!(pt.stored_blocks_size().unwrap(), pt.blocks_size());
+    pt.verify_structure().unwrap();
+    assert_eq!(pt.leaf_hashes(2, 1).unwrap()[0], t.layers[0][64..96]);
+
+    let data_file = tmp_file("single-data", &data);
+    let mut out = Vec::new();
+    pt.validate(data_file.path(), Some(BLOCK as u64 - 1), Some(BLOCK as u64 + 1), Some(&mut out))
+        .unwrap();
+    assert_eq!(out, &data[BLOCK - 1..BLOCK + 1]);
+
+    // The header alone (e.g. range-fetched) gives the layout.
+    let remote = PersistedTree::from_single_header(&raw[..4096]).unwrap();
+    assert_eq!(remote.blocks_offset(), 4096);
+    assert_eq!(remote.offsets(), pt.offsets());
+    assert!(PersistedTree::from_single_header(&raw[..100]).is_err());
+
+    // A two-file artifact under the same base name takes precedence.
+    PersistedTree::write(file.path(), &build_tree(b"abc")).unwrap();
+    assert_eq!(
+        PersistedTree::read(file.path()).unwrap().identifier,
+        identifier(b"abc")
+    );
+}
+
+// Verifies: REQ-PT-017
+#[test]
+fn single_file_header_is_strict() {
+    let t = build_tree(&fill(100, 22));
+    let file = TmpPath::new("single-strict");
+    PersistedTree::write_single(file.path(), &t).unwrap();
+    let raw = std::fs::read(file.path()).unwrap();
+
+    let mut bad = raw.clone();
+    bad[4000] = b'x';
+    assert!(PersistedTree::from_single_header(&bad).is_err(), "padding not zero");
+    let text = String::from_utf8_lossy(&raw[..200]).replace("offset: 4096", "offset: 4000");
+    let mut bad = text.into_bytes();
+    bad.extend_from_slice(&raw[200..]);
+    assert!(PersistedTree::from_single_header(&bad).is_err(), "unaligned offset");
+
+    // Damage past the header is caught like in a .blocks file.
+    let mut bad = raw.clone();
+    bad.push(0);
+    std::fs::write(file.path(), &bad).unwrap();
+    let pt = PersistedTree::read(file.path()).unwrap();
+    assert!(pt.verify_structure().is_err());
+
+    std::fs::write(file.path(), b"not a tree").unwrap();
+    assert!(PersistedTree::read(file.path()).is_err());
+}
+
+/// A version 2 head format with the given extensions.
+fn v2(extensions: &[(&str, &str)]) -> HeadFormat {
+    HeadFormat::V2 {
+        extensions: extensions
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<BTreeMap<_, _>>(),
+    }
+}
+
+// Verifies: REQ-PT-018
+#[test]
+fn v2_head_is_canonical_and_carries_extensions() {
+    let t = build_tree(&fill(2 * BLOCK + 9, 31));
+    let base = TmpPath::new("v2");
+    let format = v2(&[("terrapin.media_type", "text/plain"), ("acme.build", "42")]);
+    PersistedTree::write_as(base.path(), &t, &format).unwrap();
+
+    let text = std::fs::read_to_string(base.with_ext("head")).unwrap();
+    let keys: Vec<&str> = text.lines().map(|l| l.split(": ").next().unwrap()).collect();
+    assert_eq!(
+        keys,
+        [
+            "terrapin-tree",
+            "algorithm",
+            "block_size",
+            "length",
+            "tree",
+            "identifier",
+            "layer_counts",
+            "blocks_size",
+            "top_layer",
+            "ext.acme.build",
+            "ext.terrapin.media_type",
+        ]
+    );
+    assert!(text.starts_with("terrapin-tree: 2\n"));
+    assert!(text.contains("blocks_size: 96\n"), "{}", text);
+    let top = terrapin::to_hex(&terrapin::g(&t.layers[0]));
+    assert!(text.contains(&format!("top_layer: {}\n", top)), "{}", text);
+
+    let pt = PersistedTree::read(base.path()).unwrap();
+    assert_eq!(pt.version(), 2);
+    assert_eq!(pt.identifier, t.identifier());
+    assert_eq!(pt.extensions["terrapin.media_type"], "text/plain");
+    assert_eq!(pt.extensions["acme.build"], "42");
+    pt.verify_structure().unwrap();
+
+    // The same tree as a single file, and over any channel.
+    let file = TmpPath::new("v2-single");
+    PersistedTree::write_single_as(file.path(), &t, &format).unwrap();
+    let single = PersistedTree::read(file.path()).unwrap();
+    assert_eq!(single.version(), 2);
+    single.verify_structure().unwrap();
+    assert_eq!(PersistedTree::from_head(&text).unwrap().extensions.len(), 2);
+
+    // Bad extension keys are refused on write.
+    let bad = TmpPath::new("v2-badext");
+    for key in ["nonamespace", "Upper.case", "a..b", "a.b c"] {
+        let e = PersistedTree::write_as(bad.path(), &t, &v2(&[(key, "v")])).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput, "{}", key);
+    }
+    assert!(PersistedTree::write_as(bad.path(), &t, &v2(&[("a.b", "x\ny")])).is_err());
+}
+
+// Verifies: REQ-PT-018
+#[test]
+fn large_v2_single_header_spans_its_declared_offset() {
+    let t = build_tree(&fill(BLOCK + 1, 34));
+    let file = TmpPath::new("v2-single-large");
+    let big = "x".repeat(6000);
+    PersistedTree::write_single_as(file.path(), &t, &v2(&[("acme.notes", &big)])).unwrap();
+
+    let raw = std::fs::read(file.path()).unwrap();
+    let offset = PersistedTree::single_header_len(&raw[..4096]).unwrap();
+    assert_eq!(offset, 8192);
+    assert!(PersistedTree::from_single_header(&raw[..4096]).is_err());
+    let remote = PersistedTree::from_single_header(&raw[..offset as usize]).unwrap();
+    assert_eq!(remote.extensions["acme.notes"], big);
+    assert_eq!(remote.identifier, t.identifier());
+    let local = PersistedTree::read(file.path()).unwrap();
+    assert_eq!(local.blocks_offset(), 8192);
+}
+
+// Verifies: REQ-PT-019
+#[test]
+fn v2_head_is_strict() {
+    let t = build_tree(&fill(BLOCK + 1, 32));
+    let base = TmpPath::new("v2-strict");
+    PersistedTree::write_as(base.path(), &t, &v2(&[("a.x", "1"), ("b.y", "2")])).unwrap();
+    let text = std::fs::read_to_string(base.with_ext("head")).unwrap();
+    assert!(PersistedTree::from_head(&text).is_ok());
+
+    let lines: Vec<&str> = text.lines().collect();
+    let join = |ls: &[&str]| ls.iter().map(|l| format!("{}\n", l)).collect::<String>();
+    let mut swapped = lines.clone();
+    swapped.swap(3, 4);
+    let mut ext_swapped = lines.clone();
+    ext_swapped.swap(9, 10);
+    let mut dup_ext = lines.clone();
+    dup_ext[10] = "ext.a.x: 3";
+    let mut dup_core = lines.clone();
+    dup_core.insert(4, lines[3]);
+    let mut unknown = lines.clone();
+    unknown.push("comment: hi");
+    let id_hex = &lines[5][lines[5].len() - 64..];
+    let cases = [
+        ("reordered", join(&swapped)),
+        ("extensions unsorted", join(&ext_swapped)),
+        ("duplicate extension", join(&dup_ext)),
+        ("duplicate field", join(&dup_core)),
+        ("unknown key", join(&unknown)),
+        ("CRLF", text.replace('\n', "\r\n")),
+        ("no final newline", text.trim_end().to_string()),
+        ("blank line", text.replace("ext.a.x", "\next.a.x")),
+        ("leading zero", text.replace("length: ", "length: 0")),
+        ("uppercase hex", text.replace(id_hex, &id_hex.to_uppercase())),
+        ("blocks_size", text.replace("blocks_size: 64", "blocks_size: 96")),
+        ("counts spacing", text.replace("layer_counts: 2", "layer_counts:  2")),
+        ("bad extension key", text.replace("ext.a.x", "ext.ax")),
+    ];
+    for (what, bad) in cases {
+        assert!(PersistedTree::from_head(&bad).is_err(), "{} accepted", what);
+    }
+}
+
+// Verifies: REQ-PT-020
+#[test]
+fn v2_open_catches_truncated_or_altered_blocks() {
+    let (t, _base, _pt) = two_layer_tree("v2-open-src");
+    let base = TmpPath::new("v2-open");
+    PersistedTree::write_as(base.path(), &t, &v2(&[])).unwrap();
+    PersistedTree::read(base.path()).unwrap();
+
+    let path = base.with_ext("blocks");
+    let raw = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &raw[..raw.len() - 32]).unwrap();
+    let e = PersistedTree::read(base.path()).err().unwrap();
+    assert_eq!(e.kind(), ErrorKind::Malformed);
+
+    let mut bad = raw.clone();
+    bad[raw.len() - 1] ^= 1;
+    std::fs::write(&path, &bad).unwrap();
+    let e = PersistedTree::read(base.path()).err().unwrap();
+    assert_eq!(e.kind(), ErrorKind::Malformed);
+
+    // Damage below the top layer is left to validation and verify_structure.
+    let mut bad = raw;
+    bad[3] ^= 1;
+    std::fs::write(&path, &bad).unwrap();
+    let pt = PersistedTree::read(base.path()).unwrap();
+    assert!(pt.verify_structure().is_err());
+}
+
+// Verifies: REQ-PT-021
+#[test]
+fn v1_heads_still_read_and_reject_duplicates() {
+    let t = build_tree(&fill(10, 33));
+    let base = TmpPath::new("v1-compat");
+    let pt = persist_and_read(&base, &t);
+    assert_eq!(pt.version(), 1);
+    assert!(pt.extensions.is_empty());
+    let text = std::fs::read_to_string(base.with_ext("head")).unwrap();
+    assert!(text.starts_with("terrapin-tree: 1\n"));
+    assert!(!text.contains("blocks_size"));
+
+    let e = read_with_mangled_head(&base, |s| format!("{}length: 10\n", s)).err().unwrap();
+    assert_eq!(e.kind(), ErrorKind::Malformed);
+}
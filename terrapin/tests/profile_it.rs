@@ -0,0 +1,133 @@
+//! Integration tests for hash profiles (spec section 12): the default
+//! `terrapin-sha256` profile behind the plain functions, and `terrapin-blake3`
+//! threaded through the builder, streaming, manifest and persisted tree.
+#![cfg(all(feature = "blake3", feature = "tokio"))]
+
+mod common;
+use common::*;
+
+use std::io::Cursor;
+
+use terrapin::{
+    build_from_reader_with, identifier, identifier_with, manifest_bytes_with, parse_manifest,
+    parse_manifest_with, profile_by_name, profile_of, to_hex, tree_root, tree_root_with, ErrorKind,
+    PersistedTree, TreeBuilder, BLAKE3, BLOCK, SHA256,
+};
+
+// Verifies: REQ-PRO-001
+#[test]
+fn default_profile_is_terrapin_sha256() {
+    for len in [0, 1, BLOCK, BLOCK + 1] {
+        let data = fill(len, 1);
+        assert_eq!(identifier_with(SHA256, &data), identifier(&data));
+        assert_eq!(tree_root_with(SHA256, &data), tree_root(&data));
+    }
+    assert_eq!(SHA256.name(), "terrapin-sha256");
+    assert_eq!(SHA256.manifest_algorithm(), "sha256");
+    assert_eq!(
+        profile_by_name("terrapin-blake3").unwrap().name(),
+        BLAKE3.name()
+    );
+    assert!(profile_by_name("terrapin-md5").is_none());
+    assert_eq!(
+        profile_of(&identifier(b"x")).unwrap().name(),
+        "terrapin-sha256"
+    );
+    assert!(profile_of("no-colon").is_none());
+}
+
+// Verifies: REQ-PRO-002
+#[test]
+fn blake3_profile_vectors_and_manifest() {
+    // Pinned: G(x) = blake3("blob <len>\0" + x).
+    assert_eq!(
+        to_hex(&BLAKE3.g(b"")),
+        "4a0b6603381c7c38c557e9ceb3cba8876f0a7863146e7309801352e80b073b65"
+    );
+    assert_eq!(
+        identifier_with(BLAKE3, b""),
+        "terrapin-blake3:0adc2c5cabf66363bc79151ee63e7333a97447986f2f40f81014b1a5e0d1db7b"
+    );
+    assert_eq!(
+        identifier_with(BLAKE3, b"hello world"),
+        "terrapin-blake3:8aaa6b72625ebc965c4a201880c7aff8665fafa95f9cdde8d734a45b6880efbb"
+    );
+
+    let tree = to_hex(&tree_root_with(BLAKE3, b"hello world"));
+    let m = manifest_bytes_with(BLAKE3, 11, &tree);
+    assert!(m.starts_with(b"terrapin: blake3\n"));
+    assert_eq!(parse_manifest_with(BLAKE3, &m).unwrap(), (11, tree.clone()));
+    // A manifest only parses under its own profile.
+    assert!(parse_manifest(&m).is_err());
+    assert!(parse_manifest_with(BLAKE3, &manifest_bytes_with(SHA256, 11, &tree)).is_err());
+}
+
+// Verifies: REQ-PRO-003
+#[tokio::test]
+async fn blake3_streaming_matches_reference_and_persists() {
+    for len in [0, BLOCK, 3 * BLOCK + 9] {
+        let data = fill(len, 2);
+        let built = build_from_reader_with(BLAKE3, Cursor::new(data.clone()))
+            .await
+            .unwrap();
+        assert_eq!(
+            built.identifier(),
+            identifier_with(BLAKE3, &data),
+            "len {}",
+            len
+        );
+        assert_ne!(built.identifier(), identifier(&data));
+
+        // The in-memory builder under the same profile agrees.
+        let mut b = TreeBuilder::with_profile(BLAKE3);
+        if data.is_empty() {
+            b.push_leaf(&BLAKE3.g(b""));
+        }
+        for chunk in data.chunks(BLOCK) {
+            b.push_leaf(&BLAKE3.g(chunk));
+        }
+        assert_eq!(b.build(len as u64).root, built.root);
+
+        // The head records the profile; validation runs under it.
+        let dp = tmp_file("blake3-data", &data);
+        let base = TmpPath::new("blake3-tree");
+        PersistedTree::write(base.path(), &built).unwrap();
+        let head = std::fs::read_to_string(base.with_ext("head")).unwrap();
+        assert!(head.contains("algorithm: terrapin-blake3\n"));
+        let pt = PersistedTree::read(base.path()).unwrap();
+        assert_eq!(pt.profile().name(), "terrapin-blake3");
+        pt.check_against(&built.identifier()).unwrap();
+        pt.validate(dp.path(), None, None, None).unwrap();
+        if len > 0 {
+            let mut bad = data.clone();
+            bad[len - 1] ^= 1;
+            std::fs::write(dp.path(), &bad).unwrap();
+            assert!(pt.validate(dp.path(), None, None, None).is_err());
+        }
+    }
+}
+
+// Verifies: REQ-PRO-004
+#[test]
+fn head_with_mismatched_profile_fails_identifier_binding() {
+    let data = fill(BLOCK + 1, 3);
+    let base = TmpPath::new("profile-swap");
+    PersistedTree::write(base.path(), &build_tree(&data)).unwrap();
+    let hp = base.with_ext("head");
+    let head = std::fs::read_to_string(&hp).unwrap();
+    // Relabelling a sha256 tree as blake3 cannot pass: G differs.
+    std::fs::write(
+        &hp,
+        head.replace("algorithm: terrapin-sha256", "algorithm: terrapin-blake3"),
+    )
+    .unwrap();
+    let pt = PersistedTree::read(base.path()).unwrap();
+    assert!(pt.verifier().is_err());
+    std::fs::write(
+        &hp,
+        head.replace("algorithm: terrapin-sha256", "algorithm: terrapin-md5"),
+    )
+    .unwrap();
+    let err = PersistedTree::read(base.path()).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::Malformed);
+}
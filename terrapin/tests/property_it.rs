@@ -9,12 +9,12 @@
 mod common;
 use common::*;
 
+#[cfg(feature = "tokio")]
 use std::io::Cursor;
 
-use terrapin::{
-    build_from_reader, g, identifier, identifier_from_reader, PersistedTree, TreeBuilder, BLOCK,
-    FANOUT,
-};
+#[cfg(feature = "tokio")]
+use terrapin::{build_from_reader, identifier_from_reader};
+use terrapin::{g, identifier, Hasher, PersistedTree, TreeBuilder, BLOCK, FANOUT};
 
 /// Pick a pseudo-random dataset length: mostly small (fast), occasionally a
 /// multi-block size that exercises the recursion above a single leaf.
@@ -29,6 +29,7 @@ fn rand_len(r: &mut Rng) -> usize {
 }
 
 // Verifies: REQ-PR-001
+#[cfg(feature = "tokio")]
 #[tokio::test]
 async fn streaming_id_equals_in_memory_id() {
     for seed in 0..40u64 {
@@ -48,6 +49,7 @@ async fn streaming_id_equals_in_memory_id() {
 }
 
 // Verifies: REQ-PR-002
+#[cfg(feature = "tokio")]
 #[tokio::test]
 async fn random_chunking_does_not_change_identifier() {
     for seed in 0..40u64 {
@@ -72,6 +74,7 @@ async fn random_chunking_does_not_change_identifier() {
 }
 
 // Verifies: REQ-PR-003
+#[cfg(feature = "tokio")]
 #[tokio::test]
 async fn random_valid_range_validates_and_cat_equals_slice() {
     for seed in 0..30u64 {
@@ -214,3 +217,30 @@ fn random_leaf_streams_satisfy_layer_relation() {
         }
     }
 }
+
+// Verifies: REQ-PR-007
+#[test]
+fn random_update_chunking_does_not_change_identifier() {
+    for seed in 0..40u64 {
+        let mut r = Rng::new(seed.wrapping_mul(0x2545F4914F6CDD1D) ^ 0x3C);
+        let len = rand_len(&mut r);
+        let data = fill(len, seed ^ 0x5678);
+
+        // Random piece sizes in [0, len+1], some empty; every fourth seed
+        // hashes in the background.
+        let mut h = Hasher::new().with_threads(if seed % 4 == 0 { 3 } else { 0 });
+        let mut at = 0;
+        while at < len {
+            let n = (r.below(len as u64 + 2) as usize).min(len - at);
+            h.update(&data[at..at + n]);
+            at += n;
+        }
+        assert_eq!(
+            h.identifier(),
+            identifier(&data),
+            "seed {} len {}",
+            seed,
+            len
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Integration tests for SPDX / CycloneDX SBOMs (feature `sbom`).
+#![cfg(feature = "sbom")]
+
+mod common;
+use common::*;
+
+use terrapin::{identifier, ErrorKind, Sbom, SbomEntry, SbomFormat, BLOCK};
+
+const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+fn attested(tag: &str, data: &[u8]) -> (TmpPath, TmpPath, SbomEntry) {
+    let file = tmp_file(tag, data);
+    let base = TmpPath::new(&format!("{}-tree", tag));
+    let entry = SbomEntry::attest(file.path(), "data/file.bin", base.path(), true).unwrap();
+    (file, base, entry)
+}
+
+// Verifies: REQ-SBM-001
+#[test]
+fn entries_carry_identifier_and_sha256() {
+    let (_file, base, entry) = attested("sbom-abc", b"abc");
+    assert_eq!(entry.identifier, identifier(b"abc"));
+    assert_eq!(entry.sha256.as_deref(), Some(ABC_SHA256));
+    assert!(base.with_ext("head").exists());
+
+    let data = fill(2 * BLOCK + 77, 3);
+    let (file, base, entry) = attested("sbom-big", &data);
+    assert_eq!(entry.identifier, identifier(&data));
+    let plain = SbomEntry::attest(file.path(), "x", base.path(), false).unwrap();
+    assert_eq!(plain.sha256, None);
+}
+
+// Verifies: REQ-SBM-002
+#[test]
+fn spdx_and_cyclonedx_round_trip() {
+    let (_f1, _b1, a) = attested("sbom-rt1", b"abc");
+    let (_f2, _b2, mut b) = attested("sbom-rt2", &fill(BLOCK + 1, 5));
+    b.name = "weights/model.safetensors".into();
+    b.sha256 = None;
+    let sbom = Sbom {
+        name: "models".into(),
+        entries: vec![a.clone(), b.clone()],
+    };
+
+    let spdx = sbom.to_json(SbomFormat::Spdx);
+    let v: serde_json::Value = serde_json::from_slice(&spdx).unwrap();
+    assert_eq!(v["spdxVersion"], "SPDX-2.3");
+    assert_eq!(
+        v["packages"][0]["externalRefs"][0]["referenceLocator"],
+        a.identifier
+    );
+    assert_eq!(v["packages"][0]["checksums"][0]["algorithm"], "SHA256");
+    assert!(v["packages"][1].get("checksums").is_none());
+    assert_eq!(Sbom::parse(&spdx).unwrap(), sbom);
+
+    let cdx = sbom.to_json(SbomFormat::CycloneDx);
+    let v: serde_json::Value = serde_json::from_slice(&cdx).unwrap();
+    assert_eq!(v["bomFormat"], "CycloneDX");
+    assert_eq!(v["specVersion"], "1.5");
+    assert_eq!(v["components"][0]["hashes"][0]["content"], ABC_SHA256);
+    assert_eq!(v["components"][1]["properties"][0]["value"], b.identifier);
+    assert_eq!(Sbom::parse(&cdx).unwrap(), sbom);
+
+    // Components without a terrapin identifier are not entries.
+    let foreign = br#"{"bomFormat":"CycloneDX","specVersion":"1.5",
+        "components":[{"type":"library","name":"serde"}]}"#;
+    assert!(Sbom::parse(foreign).unwrap().entries.is_empty());
+    assert!(Sbom::parse(b"{}").is_err());
+}
+
+// Verifies: REQ-SBM-003
+#[test]
+fn validate_uses_tree_or_rehashes() {
+    let data = fill(BLOCK + 300, 7);
+    let (file, base, entry) = attested("sbom-val", &data);
+    entry.validate(file.path(), base.path()).unwrap();
+    let missing = TmpPath::new("sbom-val-none");
+    entry.validate(file.path(), missing.path()).unwrap();
+
+    let mut bad = data.clone();
+    bad[BLOCK + 5] ^= 1;
+    std::fs::write(file.path(), &bad).unwrap();
+    assert!(entry.validate(file.path(), base.path()).is_err());
+    assert!(entry.validate(file.path(), missing.path()).is_err());
+
+    // Same terrapin identifier but a wrong listed SHA-256 is rejected.
+    std::fs::write(file.path(), &data).unwrap();
+    let mut wrong_sha = entry.clone();
+    wrong_sha.sha256 = Some(ABC_SHA256.into());
+    let err = wrong_sha
+        .validate(file.path(), missing.path())
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), ErrorKind::Validation);
+    // It is compared when a tree is present too.
+    let err = wrong_sha.validate(file.path(), base.path()).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::Validation);
+}
@@ -0,0 +1,138 @@
+//! Integration tests for signed statements (feature `sign`): the canonical
+//! statement, key and signature files, and verification.
+#![cfg(feature = "sign")]
+
+mod common;
+use common::*;
+
+use terrapin::{identifier, PublicKey, SecretKey, SignatureAlgorithm, SignedStatement, Statement};
+
+const ALGORITHMS: [SignatureAlgorithm; 2] =
+    [SignatureAlgorithm::Ed25519, SignatureAlgorithm::EcdsaP256];
+
+fn statement() -> Statement {
+    Statement::new(&identifier(b"hello terrapin"), 14)
+        .unwrap()
+        .with_metadata("source", "https://example.com/data.bin")
+        .unwrap()
+        .with_metadata("build.id", "42")
+        .unwrap()
+}
+
+// Verifies: REQ-SIG-001
+#[test]
+fn statement_bytes_are_canonical() {
+    let st = statement();
+    let text = String::from_utf8(st.to_bytes()).unwrap();
+    assert_eq!(
+        text,
+        format!(
+            "terrapin-statement: 1\nidentifier: {}\nlength: 14\n\
+             meta.build.id: 42\nmeta.source: https://example.com/data.bin\n",
+            st.identifier
+        )
+    );
+    assert_eq!(Statement::parse(text.as_bytes()).unwrap(), st);
+
+    let id = &st.identifier;
+    for bad in [
+        format!("terrapin-statement: 1\nidentifier: {}\nlength: 014\n", id),
+        format!("terrapin-statement: 1\nidentifier: {}\nlength: 14", id),
+        format!("terrapin-statement: 2\nidentifier: {}\nlength: 14\n", id),
+        format!("terrapin-statement: 1\nlength: 14\nidentifier: {}\n", id),
+        format!(
+            "terrapin-statement: 1\nidentifier: {}\nlength: 14\nmeta.b: 1\nmeta.a: 2\n",
+            id
+        ),
+        format!(
+            "terrapin-statement: 1\nidentifier: {}\nlength: 14\nmeta.a: 1\nmeta.a: 1\n",
+            id
+        ),
+        format!(
+            "terrapin-statement: 1\nidentifier: {}\nlength: 14\nmeta.a:  x\n",
+            id
+        ),
+        format!(
+            "terrapin-statement: 1\nidentifier: {}\nlength: 14\nextra: x\n",
+            id
+        ),
+        "terrapin-statement: 1\nidentifier: terrapin-sha256:ABC\nlength: 14\n".to_string(),
+    ] {
+        assert!(Statement::parse(bad.as_bytes()).is_err(), "{:?}", bad);
+    }
+    assert!(Statement::new("not-an-identifier", 1).is_err());
+    assert!(st.clone().with_metadata("Upper", "x").is_err());
+    assert!(st.clone().with_metadata("k", "two\nlines").is_err());
+    assert!(st.clone().with_metadata("k", "").is_err());
+}
+
+// Verifies: REQ-SIG-002
+#[test]
+fn sign_then_verify_round_trips() {
+    for alg in ALGORITHMS {
+        let key = SecretKey::generate(alg).unwrap();
+        let public = key.public_key().unwrap();
+        let signed = key.sign(&statement()).unwrap();
+        assert_eq!(signed.verify(&public).unwrap(), &statement());
+
+        let parsed = SignedStatement::parse(&signed.to_text()).unwrap();
+        assert_eq!(parsed, signed);
+        assert_eq!(parsed.verify(&public).unwrap(), &statement());
+
+        // Key files round-trip too.
+        let key2 = SecretKey::parse(&key.to_text()).unwrap();
+        assert_eq!(key2.public_key().unwrap(), public);
+        assert_eq!(PublicKey::parse(&public.to_text()).unwrap(), public);
+        let resigned = key2.sign(&statement()).unwrap();
+        resigned.verify(&public).unwrap();
+    }
+}
+
+// Verifies: REQ-SIG-003
+#[test]
+fn tampering_or_wrong_key_fails_verification() {
+    for alg in ALGORITHMS {
+        let key = SecretKey::generate(alg).unwrap();
+        let public = key.public_key().unwrap();
+        let other = SecretKey::generate(alg).unwrap().public_key().unwrap();
+        let signed = key.sign(&statement()).unwrap();
+        assert!(signed.verify(&other).is_err());
+
+        let text = signed.to_text();
+        let forged = text.replace("length: 14", "length: 15");
+        let forged = SignedStatement::parse(&forged).unwrap();
+        assert!(forged.verify(&public).is_err());
+
+        let mut flipped = signed.clone();
+        flipped.signature[0] ^= 1;
+        assert!(flipped.verify(&public).is_err());
+
+        let other_id = Statement::new(&identifier(b"other"), 5).unwrap();
+        let mut swapped = signed.clone();
+        swapped.statement = other_id;
+        assert!(swapped.verify(&public).is_err());
+    }
+    let ed = SecretKey::generate(SignatureAlgorithm::Ed25519).unwrap();
+    let p256 = SecretKey::generate(SignatureAlgorithm::EcdsaP256).unwrap();
+    let signed = ed.sign(&statement()).unwrap();
+    assert!(signed.verify(&p256.public_key().unwrap()).is_err());
+}
+
+// Verifies: REQ-SIG-002
+#[test]
+fn key_files_write_and_read() {
+    let base = tmp_file("sign-key", b"old");
+    let key = SecretKey::generate(SignatureAlgorithm::Ed25519).unwrap();
+    // Overwriting a world-readable file still leaves it private.
+    key.write(base.path()).unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(base.path()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    let read = SecretKey::read(base.path()).unwrap();
+    assert_eq!(read.public_key().unwrap(), key.public_key().unwrap());
+    assert!(PublicKey::read(base.path()).is_err());
+    assert!(SignedStatement::parse("terrapin-statement: 1\n").is_err());
+}
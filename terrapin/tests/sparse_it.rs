@@ -0,0 +1,232 @@
+//! Integration tests for sparse-file and zero-block aware hashing
+//! (`build_from_file_sync` / `build_from_file`, [`SparseSource`], and
+//! `zero_identifier`), against the in-memory reference.
+
+mod common;
+use common::*;
+
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+
+use terrapin::{
+    build_from_file_sync, build_from_file_sync_with, build_from_reader_sync, identifier,
+    zero_identifier, SparseSource, BLOCK, SHA256,
+};
+
+/// Data written at an offset of a sparse file.
+type Extent<'a> = (usize, &'a [u8]);
+
+/// A file of `len` bytes, zero (a hole) except for `data` written at each
+/// offset; returns it with the same bytes in memory.
+fn sparse_file(tag: &str, len: usize, data: &[Extent]) -> (TmpPath, Vec<u8>) {
+    let f = tmp_file(tag, &[]);
+    let mut file = File::options().write(true).open(f.path()).unwrap();
+    file.set_len(len as u64).unwrap();
+    let mut bytes = vec![0u8; len];
+    for (off, d) in data {
+        file.seek(SeekFrom::Start(*off as u64)).unwrap();
+        file.write_all(d).unwrap();
+        bytes[*off..off + d.len()].copy_from_slice(d);
+    }
+    (f, bytes)
+}
+
+/// An in-memory source that reports `regions` as its data and counts the
+/// bytes actually read.
+struct Regions {
+    inner: Cursor<Vec<u8>>,
+    regions: Vec<(u64, u64)>,
+    read: u64,
+}
+
+impl Read for Regions {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for Regions {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl SparseSource for Regions {
+    fn data_region(&mut self, offset: u64) -> io::Result<Option<(u64, u64)>> {
+        Ok(self
+            .regions
+            .iter()
+            .find(|(_, end)| *end > offset)
+            .map(|&(start, end)| (start.max(offset), end)))
+    }
+}
+
+/// Counts bytes read from a real file, passing its holes through.
+struct Counting {
+    file: File,
+    read: u64,
+}
+
+impl Read for Counting {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.file.read(buf)?;
+        self.read += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for Counting {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+impl SparseSource for Counting {
+    fn data_region(&mut self, offset: u64) -> io::Result<Option<(u64, u64)>> {
+        self.file.data_region(offset)
+    }
+}
+
+// Verifies: REQ-SP-001
+#[test]
+fn file_build_matches_in_memory_reference_for_sparse_files() {
+    let b = BLOCK;
+    let cases: &[(usize, &[Extent])] = &[
+        (0, &[]),
+        (100, &[]),
+        (5 * b + 7, &[]),
+        (5 * b + 7, &[(5 * b + 6, b"z")]),
+        (
+            6 * b,
+            &[(0, b"head"), (3 * b - 2, b"straddles"), (6 * b - 1, b"!")],
+        ),
+        (4 * b + 3, &[(2 * b, &[7u8; 4096])]),
+    ];
+    for (i, (len, data)) in cases.iter().enumerate() {
+        let (f, bytes) = sparse_file(&format!("sparse-{}", i), *len, data);
+        let bt = build_from_file_sync(File::open(f.path()).unwrap()).unwrap();
+        assert_eq!(bt.identifier(), identifier(&bytes), "case {}", i);
+        assert_eq!(bt.length, *len as u64);
+        let again = build_from_reader_sync(File::open(f.path()).unwrap()).unwrap();
+        assert_eq!(bt.layers, again.layers, "case {}", i);
+    }
+}
+
+// Verifies: REQ-SP-001
+#[cfg(feature = "tokio")]
+#[tokio::test(flavor = "multi_thread")]
+async fn async_file_build_matches_sync() {
+    let (f, bytes) = sparse_file("sparse-async", 3 * BLOCK + 5, &[(BLOCK + 9, b"data")]);
+    let bt = terrapin::build_from_file(File::open(f.path()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(bt.identifier(), identifier(&bytes));
+}
+
+// Verifies: REQ-SP-002
+#[test]
+fn blocks_wholly_in_holes_are_not_read() {
+    let b = BLOCK as u64;
+    let mut data = vec![0u8; 6 * BLOCK + 10];
+    data[BLOCK + 5] = 1;
+    data[4 * BLOCK - 1] = 2;
+    data[4 * BLOCK] = 3;
+    let want = identifier(&data);
+    // Blocks 1, 3 and 4 touch data; 0, 2, 5 and the 10-byte tail do not.
+    let regions = vec![(b + 5, b + 6), (4 * b - 1, 4 * b + 1)];
+    let mut source = Regions {
+        inner: Cursor::new(data),
+        regions,
+        read: 0,
+    };
+    let bt = build_from_file_sync_with(SHA256, &mut source).unwrap();
+    assert_eq!(bt.identifier(), want);
+    assert_eq!(source.read, 3 * b);
+
+    // Reporting no holes at all is always correct, just slower.
+    source.regions = vec![(0, u64::MAX)];
+    source.read = 0;
+    let bt = build_from_file_sync_with(SHA256, &mut source).unwrap();
+    assert_eq!(bt.identifier(), want);
+    assert_eq!(source.read, 6 * b + 10);
+}
+
+// Verifies: REQ-SP-003
+#[cfg(target_os = "linux")]
+#[test]
+fn real_holes_are_skipped_where_the_filesystem_reports_them() {
+    let len = 16 * BLOCK;
+    let (f, bytes) = sparse_file("sparse-real", len, &[(5 * BLOCK + 1, b"x")]);
+    let mut file = File::open(f.path()).unwrap();
+    let reports_holes = file.data_region(0).unwrap() != Some((0, u64::MAX));
+    let mut counting = Counting { file, read: 0 };
+    let bt = build_from_file_sync_with(SHA256, &mut counting).unwrap();
+    assert_eq!(bt.identifier(), identifier(&bytes));
+    if reports_holes {
+        assert!(counting.read < len as u64 / 2, "read {}", counting.read);
+    }
+}
+
+// Verifies: REQ-SP-004
+#[test]
+fn zero_blocks_and_zero_datasets_hash_as_the_reference() {
+    // Written (not sparse) zero blocks between data blocks, streamed.
+    let mut data = fill(5 * BLOCK + 3, 51);
+    data[BLOCK..3 * BLOCK].fill(0);
+    data[4 * BLOCK..].fill(0);
+    let bt = build_from_reader_sync(Cursor::new(data.clone())).unwrap();
+    assert_eq!(bt.identifier(), identifier(&data));
+
+    for n in [0, 1, BLOCK, 3 * BLOCK + 1] {
+        let zeros = vec![0u8; n];
+        assert_eq!(zero_identifier(n as u64), identifier(&zeros), "{}", n);
+        let bt = build_from_reader_sync(Cursor::new(zeros.clone())).unwrap();
+        assert_eq!(bt.identifier(), zero_identifier(n as u64), "{}", n);
+        #[cfg(feature = "blake3")]
+        assert_eq!(
+            terrapin::zero_identifier_with(terrapin::BLAKE3, n as u64),
+            terrapin::identifier_with(terrapin::BLAKE3, &zeros),
+            "{}",
+            n
+        );
+    }
+}
+
+/// Hash `data` written into the named pipe at `path` from another thread.
+#[cfg(unix)]
+fn through_fifo(
+    path: &std::path::Path,
+    data: &[u8],
+    build: impl FnOnce(File) -> io::Result<terrapin::BuiltTree>,
+) -> String {
+    let writer = {
+        let (path, data) = (path.to_path_buf(), data.to_vec());
+        std::thread::spawn(move || std::fs::write(path, data).unwrap())
+    };
+    let bt = build(File::open(path).unwrap()).unwrap();
+    writer.join().unwrap();
+    bt.identifier()
+}
+
+// Verifies: REQ-SP-005
+#[cfg(unix)]
+#[test]
+fn named_pipes_are_hashed_as_streams() {
+    let fifo = TmpPath::new("sparse-fifo");
+    let made = std::process::Command::new("mkfifo")
+        .arg(fifo.path())
+        .status()
+        .unwrap();
+    assert!(made.success());
+    let data = fill(2 * BLOCK + 11, 52);
+    let want = identifier(&data);
+    assert_eq!(through_fifo(fifo.path(), &data, build_from_file_sync), want);
+    #[cfg(feature = "tokio")]
+    {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let build = |f| rt.block_on(terrapin::build_from_file(f));
+        assert_eq!(through_fifo(fifo.path(), &data, build), want);
+    }
+}
@@ -0,0 +1,297 @@
+//! Integration tests for the content-addressed [`terrapin::Store`]: ingest,
+//! deduplication by identifier and (in block mode) by data block, verified
+//! range reads, and garbage collection.
+
+mod common;
+use common::*;
+
+use terrapin::{identifier, to_hex, Error, ErrorKind, Store, StoreMode, BLOCK};
+
+/// Whole data blocks filled from `seeds` (odd: `fill` ignores the low bit),
+/// then a `tail`-byte partial block.
+fn blocks_of(seeds: &[u64], tail: usize) -> Vec<u8> {
+    let mut v = Vec::new();
+    for &seed in seeds {
+        v.extend(fill(BLOCK, seed));
+    }
+    v.extend(fill(tail, 99));
+    v
+}
+
+/// Number of block files under `<store>/blocks`.
+fn block_files(dir: &TmpPath) -> usize {
+    std::fs::read_dir(dir.path().join("blocks"))
+        .unwrap()
+        .map(|d| std::fs::read_dir(d.unwrap().path()).unwrap().count())
+        .sum()
+}
+
+/// Verified bytes of `[start, end)` of `id`.
+fn get(st: &Store, id: &str, start: Option<u64>, end: Option<u64>) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    st.get(id, start, end, Some(&mut out))?;
+    Ok(out)
+}
+
+// Verifies: REQ-STO-001
+#[test]
+fn store_add_files_object_by_identifier_and_reads_back() {
+    let dir = TmpPath::new("store-add");
+    let st = Store::open(dir.path()).unwrap();
+    let data = fill(2 * BLOCK + 4321, 1);
+    let f = tmp_file("store-add-src", &data);
+
+    let added = st.add(f.path(), Some("ds")).unwrap();
+    assert_eq!(added.identifier, identifier(&data));
+    assert_eq!(added.length, data.len() as u64);
+    assert!(!added.deduplicated);
+    assert_eq!(st.resolve("ds").unwrap(), added.identifier);
+    assert_eq!(get(&st, &added.identifier, None, None).unwrap(), data);
+
+    let (s, e) = (BLOCK as u64 - 7, BLOCK as u64 + 9);
+    assert_eq!(
+        get(&st, &added.identifier, Some(s), Some(e)).unwrap(),
+        &data[s as usize..e as usize]
+    );
+    // The default ref name is the file name.
+    let again = st.add(f.path(), None).unwrap();
+    let name = f.path().file_name().unwrap().to_str().unwrap();
+    assert_eq!(st.resolve(name).unwrap(), again.identifier);
+    // `:` marks an identifier, so no ref name may hold one.
+    let err = st.add(f.path(), Some("model:v2")).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::Malformed);
+}
+
+// Verifies: REQ-STO-002
+#[test]
+fn store_deduplicates_identical_content() {
+    let dir = TmpPath::new("store-dedup");
+    let st = Store::open(dir.path()).unwrap();
+    let data = fill(BLOCK + 10, 2);
+    let a = tmp_file("store-dedup-a", &data);
+    let b = tmp_file("store-dedup-b", &data);
+
+    let first = st.add(a.path(), Some("a")).unwrap();
+    let second = st.add(b.path(), Some("b")).unwrap();
+    assert!(!first.deduplicated);
+    assert!(second.deduplicated);
+    assert_eq!(first.identifier, second.identifier);
+
+    let entries = st.list().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].identifier, first.identifier);
+    assert_eq!(entries[0].refs, vec!["a".to_string(), "b".to_string()]);
+}
+
+// Verifies: REQ-STO-003
+#[test]
+fn store_get_detects_corrupt_and_misfiled_objects() {
+    let dir = TmpPath::new("store-corrupt");
+    let st = Store::open(dir.path()).unwrap();
+    let data = fill(3 * BLOCK, 3);
+    let f = tmp_file("store-corrupt-src", &data);
+    let id = st.add(f.path(), Some("ds")).unwrap().identifier;
+
+    // Flip a byte in block 1 of the stored copy.
+    let stored = st.data_path(&id).unwrap();
+    let mut bytes = std::fs::read(&stored).unwrap();
+    bytes[BLOCK + 11] ^= 0xff;
+    std::fs::write(&stored, &bytes).unwrap();
+
+    let err = get(&st, &id, Some(BLOCK as u64), Some(BLOCK as u64 + 1)).unwrap_err();
+    assert_eq!((err.kind(), err.block()), (ErrorKind::Validation, Some(1)));
+    // Untouched blocks still read back.
+    assert_eq!(get(&st, &id, Some(0), Some(100)).unwrap(), &data[..100]);
+
+    // An object directory renamed under another identifier is rejected.
+    let other = format!("terrapin-sha256:{}", "0".repeat(64));
+    let obj = stored.parent().unwrap();
+    std::fs::rename(obj, obj.with_file_name("0".repeat(64))).unwrap();
+    let err = get(&st, &other, None, None).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::IdentifierMismatch);
+
+    assert!(get(&st, "not-an-identifier", None, None).is_err());
+}
+
+// Verifies: REQ-STO-004
+#[test]
+fn store_gc_removes_only_unreferenced_objects() {
+    let dir = TmpPath::new("store-gc");
+    let st = Store::open(dir.path()).unwrap();
+    let v1 = tmp_file("store-gc-v1", &fill(1000, 4));
+    let v2 = tmp_file("store-gc-v2", &fill(2000, 5));
+    let keep = tmp_file("store-gc-keep", &fill(3000, 6));
+
+    let id1 = st.add(v1.path(), Some("ds")).unwrap().identifier;
+    // Re-pointing the ref orphans the first version.
+    let id2 = st.add(v2.path(), Some("ds")).unwrap().identifier;
+    let id3 = st.add(keep.path(), Some("other")).unwrap().identifier;
+    assert_eq!(st.gc().unwrap(), vec![id1.clone()]);
+    assert!(st.data_path(&id1).is_err());
+
+    st.remove_ref("other").unwrap();
+    assert_eq!(st.gc().unwrap(), vec![id3]);
+    let left: Vec<String> = st
+        .list()
+        .unwrap()
+        .into_iter()
+        .map(|e| e.identifier)
+        .collect();
+    assert_eq!(left, vec![id2]);
+    assert!(st.gc().unwrap().is_empty());
+}
+
+// Verifies: REQ-STO-006
+#[test]
+fn block_store_keeps_shared_blocks_once() {
+    let dir = TmpPath::new("store-blocks");
+    let st = Store::init(dir.path(), StoreMode::Blocks).unwrap();
+    // Two snapshots differing in one block; the first repeats a block too.
+    let v1 = blocks_of(&[1, 3, 1, 5], 500);
+    let v2 = blocks_of(&[1, 3, 1, 7], 500);
+    let (f1, f2) = (
+        tmp_file("store-blocks-v1", &v1),
+        tmp_file("store-blocks-v2", &v2),
+    );
+
+    let id1 = st.add(f1.path(), Some("v1")).unwrap().identifier;
+    let id2 = st.add(f2.path(), Some("v2")).unwrap().identifier;
+    assert!(
+        st.data_path(&id1).is_err(),
+        "no whole data file in block mode"
+    );
+    assert_eq!(get(&st, &id1, None, None).unwrap(), v1);
+    assert_eq!(get(&st, &id2, None, None).unwrap(), v2);
+    let (s, e) = (2 * BLOCK as u64 - 5, 3 * BLOCK as u64 + 5);
+    assert_eq!(
+        get(&st, &id2, Some(s), Some(e)).unwrap(),
+        &v2[s as usize..e as usize]
+    );
+
+    // Unique blocks: seeds 1, 3, 5, 7 and the shared tail.
+    assert_eq!(block_files(&dir), 5);
+    let stats = st.stats().unwrap();
+    assert_eq!(stats.objects, 2);
+    assert_eq!(stats.blocks, 10);
+    assert_eq!(stats.unique_blocks, 5);
+    assert_eq!(stats.logical_bytes, (v1.len() + v2.len()) as u64);
+    assert_eq!(stats.stored_bytes, 4 * BLOCK as u64 + 500);
+    assert!(stats.dedup_ratio() > 1.9);
+
+    // Reopening picks the mode up from the store's config.
+    assert_eq!(Store::open(dir.path()).unwrap().mode(), StoreMode::Blocks);
+}
+
+// Verifies: REQ-STO-007
+#[test]
+fn block_store_verifies_blocks_and_collects_unused_ones() {
+    let dir = TmpPath::new("store-blocks-gc");
+    let st = Store::init(dir.path(), StoreMode::Blocks).unwrap();
+    let v1 = blocks_of(&[9, 11], 10);
+    let v2 = blocks_of(&[9, 13], 10);
+    let (f1, f2) = (tmp_file("store-bgc-v1", &v1), tmp_file("store-bgc-v2", &v2));
+    let id1 = st.add(f1.path(), Some("v1")).unwrap().identifier;
+    let id2 = st.add(f2.path(), Some("v2")).unwrap().identifier;
+    assert_eq!(block_files(&dir), 4);
+
+    // A tampered shared block fails verification in both datasets.
+    let shared = dir
+        .path()
+        .join("blocks")
+        .join(&to_hex(&terrapin::g(&v1[..BLOCK]))[..2])
+        .join(to_hex(&terrapin::g(&v1[..BLOCK])));
+    let good = std::fs::read(&shared).unwrap();
+    let mut bad = good.clone();
+    bad[9] ^= 1;
+    std::fs::write(&shared, &bad).unwrap();
+    for id in [&id1, &id2] {
+        let err = get(&st, id, Some(0), Some(10)).unwrap_err();
+        assert_eq!((err.kind(), err.block()), (ErrorKind::Validation, Some(0)));
+    }
+    std::fs::write(&shared, &good).unwrap();
+
+    // Dropping v1 frees only the block v2 does not use.
+    st.remove_ref("v1").unwrap();
+    assert_eq!(st.gc().unwrap(), vec![id1]);
+    assert_eq!(block_files(&dir), 3);
+    assert_eq!(get(&st, &id2, None, None).unwrap(), v2);
+
+    // A missing block is an error, not a short read.
+    std::fs::remove_file(&shared).unwrap();
+    assert!(get(&st, &id2, None, None).is_err());
+}
+
+// Verifies: REQ-STO-008
+#[test]
+fn store_init_refuses_to_change_mode() {
+    let dir = TmpPath::new("store-mode");
+    let st = Store::open(dir.path()).unwrap();
+    assert_eq!(st.mode(), StoreMode::Whole);
+    let f = tmp_file("store-mode-src", &fill(100, 8));
+    st.add(f.path(), None).unwrap();
+
+    // An uninitialised store that already holds whole objects stays whole.
+    let err = Store::init(dir.path(), StoreMode::Blocks).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::Malformed);
+    assert!(Store::init(dir.path(), StoreMode::Whole).is_ok());
+
+    let blocks = TmpPath::new("store-mode-blocks");
+    Store::init(blocks.path(), StoreMode::Blocks).unwrap();
+    assert!(Store::init(blocks.path(), StoreMode::Whole).is_err());
+}
+
+// Verifies: REQ-STO-010
+#[test]
+fn store_gc_waits_for_in_flight_adds() {
+    let dir = TmpPath::new("store-gc-race");
+    let st = Store::open(dir.path()).unwrap();
+
+    // An add in flight holds the lock shared while its staging exists.
+    let lock = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(dir.path().join("lock"))
+        .unwrap();
+    lock.lock_shared().unwrap();
+    let stage = dir.path().join("tmp").join("add-in-flight");
+    std::fs::create_dir(&stage).unwrap();
+    std::fs::write(stage.join("data"), b"partial").unwrap();
+    let (done, finished) = std::sync::mpsc::channel();
+    std::thread::scope(|s| {
+        s.spawn(|| done.send(st.gc().unwrap()).unwrap());
+        let waited = finished.recv_timeout(std::time::Duration::from_millis(200));
+        assert!(waited.is_err(), "gc ran during an add");
+        assert!(stage.join("data").exists());
+        drop(lock);
+        assert!(finished.recv().unwrap().is_empty());
+    });
+    // Once no add holds the lock, leftover staging is abandoned.
+    assert!(!stage.exists());
+
+    // Adds racing collections: every ref ends up on a readable object.
+    let sources: Vec<TmpPath> = (0..16)
+        .map(|i| tmp_file(&format!("store-gc-race-{}", i), &fill(5000 + i, i as u64)))
+        .collect();
+    std::thread::scope(|s| {
+        let adders: Vec<_> = sources
+            .chunks(4)
+            .map(|chunk| {
+                let st = &st;
+                s.spawn(move || {
+                    for f in chunk {
+                        st.add(f.path(), None).unwrap();
+                    }
+                })
+            })
+            .collect();
+        while !adders.iter().all(|a| a.is_finished()) {
+            st.gc().unwrap();
+        }
+    });
+    for (i, f) in sources.iter().enumerate() {
+        let name = f.path().file_name().unwrap().to_str().unwrap();
+        let id = st.resolve(name).unwrap();
+        assert_eq!(get(&st, &id, None, None).unwrap(), fill(5000 + i, i as u64));
+    }
+}
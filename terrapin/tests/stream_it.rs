@@ -2,14 +2,17 @@
 //! (`build_from_reader` / `identifier_from_reader`). Covers the BlockReader
 //! splitting rules (spec §4.1) and the streaming/parallel guarantees (§2.1),
 //! comparing every streamed result against the in-memory reference.
+#![cfg(feature = "tokio")]
 
 mod common;
 use common::*;
 
 use std::io::{self, Cursor, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use terrapin::{
-    build_from_reader, g, identifier, identifier_from_reader, tree_root, BuiltTree, BLOCK, FANOUT,
+    build_from_reader, build_from_reader_with, g, identifier, identifier_from_reader, tree_root,
+    BuiltTree, Profile, TreeBuilder, BLOCK, FANOUT, SHA256,
 };
 
 // ---------------------------------------------------------------------------
@@ -277,3 +280,61 @@ async fn concurrent_builds_agree() {
         assert_eq!(h.await.unwrap(), want);
     }
 }
+
+// ---------------------------------------------------------------------------
+// Batched hashing (g_many).
+// ---------------------------------------------------------------------------
+
+static LARGEST_BATCH: AtomicUsize = AtomicUsize::new(0);
+
+/// `terrapin-sha256` claiming three lanes and recording its largest batch.
+struct ThreeLanes;
+
+impl Profile for ThreeLanes {
+    fn name(&self) -> &'static str {
+        SHA256.name()
+    }
+
+    fn manifest_algorithm(&self) -> &'static str {
+        SHA256.manifest_algorithm()
+    }
+
+    fn g(&self, data: &[u8]) -> [u8; 32] {
+        SHA256.g(data)
+    }
+
+    fn g_many(&self, inputs: &[&[u8]]) -> Vec<[u8; 32]> {
+        LARGEST_BATCH.fetch_max(inputs.len(), Ordering::Relaxed);
+        SHA256.g_many(inputs)
+    }
+
+    fn lanes(&self) -> usize {
+        3
+    }
+}
+
+// Verifies: REQ-SB-013
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn batched_leaves_and_reduction_match_reference() {
+    for len in [0, 1, BLOCK, 2 * BLOCK + 1, 3 * BLOCK, 7 * BLOCK + 5] {
+        let data = fill(len, 2 * len as u64 + 1);
+        let bt = build_from_reader_with(&ThreeLanes, Cursor::new(data.clone()))
+            .await
+            .unwrap();
+        assert_eq!(bt.identifier(), identifier(&data), "len {}", len);
+    }
+    assert_eq!(LARGEST_BATCH.load(Ordering::Relaxed), 3);
+
+    // Upper layer: FANOUT + 1 leaves reduce as two groups in one batch.
+    let mut b = TreeBuilder::with_profile(&ThreeLanes);
+    let mut hf = Vec::new();
+    for i in 0..=FANOUT as u64 {
+        let mut h = [0u8; 32];
+        h[..8].copy_from_slice(&i.to_le_bytes());
+        b.push_leaf(&h);
+        hf.extend_from_slice(&h);
+    }
+    let bt = b.build(FANOUT as u64 * BLOCK as u64 + 1);
+    assert_eq!(bt.layers[1].len(), 64);
+    assert_eq!(bt.root, g(&[g(&hf[..BLOCK]), g(&hf[BLOCK..])].concat()));
+}
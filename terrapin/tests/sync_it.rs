@@ -0,0 +1,50 @@
+//! Integration tests for the runtime-free build path (`build_from_reader_sync`):
+//! plain `#[test]` functions with no async runtime, compared against the
+//! in-memory reference like the async streaming tests.
+
+mod common;
+use common::*;
+
+use std::io::Cursor;
+
+use terrapin::{
+    build_from_reader_sync, build_from_reader_sync_with, identifier, tree_root, BLOCK, SHA256,
+};
+
+// Verifies: REQ-SY-001
+#[test]
+fn sync_build_matches_in_memory_reference() {
+    for len in [0, 1, BLOCK - 1, BLOCK, BLOCK + 1, 2 * BLOCK, 5 * BLOCK + 7] {
+        let data = fill(len, 2 * len as u64 + 1);
+        let bt = build_from_reader_sync(Cursor::new(data.clone())).unwrap();
+        assert_eq!(bt.identifier(), identifier(&data), "len {}", len);
+        assert_eq!(bt.root, tree_root(&data), "len {}", len);
+        assert_eq!(bt.length, len as u64);
+    }
+    let data = fill(BLOCK + 3, 7);
+    let bt = build_from_reader_sync_with(SHA256, Cursor::new(data.clone())).unwrap();
+    assert_eq!(bt.identifier(), identifier(&data));
+}
+
+// Verifies: REQ-SY-002
+#[test]
+fn sync_build_reassembles_short_and_interrupted_reads() {
+    let data = fill(3 * BLOCK + 12345, 9);
+    let want = identifier(&data);
+    for chunk in [1000, 65536, BLOCK - 1, BLOCK + 1] {
+        let bt = build_from_reader_sync(Choppy::new(data.clone(), chunk)).unwrap();
+        assert_eq!(bt.identifier(), want, "chunk {}", chunk);
+    }
+    let bt = build_from_reader_sync(InterruptOnce::new(data.clone())).unwrap();
+    assert_eq!(bt.identifier(), want);
+}
+
+// Verifies: REQ-SY-003
+#[test]
+fn sync_build_surfaces_read_errors() {
+    for ok_bytes in [0, BLOCK / 2, BLOCK, 6 * BLOCK + 1] {
+        let err = build_from_reader_sync(ErrAfter::new(ok_bytes)).err();
+        assert!(err.is_some(), "ok_bytes {}", ok_bytes);
+        assert_eq!(err.unwrap().to_string(), "boom");
+    }
+}
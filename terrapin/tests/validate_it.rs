@@ -13,7 +13,10 @@
 mod common;
 use common::*;
 
-use terrapin::{g, identifier_from_parts, to_hex, BuiltTree, PersistedTree, TreeBuilder, BLOCK, FANOUT};
+use terrapin::{
+    g, identifier_from_parts, to_hex, BuiltTree, ErrorKind, PersistedTree, TreeBuilder, BLOCK,
+    FANOUT,
+};
 
 // ---------------------------------------------------------------------------
 // Local helpers (only public API + common helpers).
@@ -186,6 +189,68 @@ fn two_layer_sparse_file_range_validates() {
     pt.validate(dp.path(), Some(0), Some(BLOCK as u64), None).unwrap();
 }
 
+// Verifies: REQ-VAL-013
+#[test]
+fn head_only_verifier_checks_blocks_with_supplied_groups() {
+    let data = multi();
+    let (dp, base, _pt) = persist(&data, "data");
+    let head = std::fs::read_to_string(base.with_ext("head")).unwrap();
+    let blocks = std::fs::read(base.with_ext("blocks")).unwrap();
+
+    // A head from "elsewhere": no local .blocks, groups come from the caller.
+    let pt = PersistedTree::from_head(&head).unwrap();
+    assert!(pt.validate(dp.path(), None, None, None).is_err());
+
+    let mut fetches = 0;
+    let mut fetch = |off: u64, len: usize| {
+        fetches += 1;
+        Ok(blocks[off as usize..off as usize + len].to_vec())
+    };
+    let mut v = pt.verifier().unwrap();
+    for (i, chunk) in data.chunks(BLOCK).enumerate() {
+        assert_eq!(v.block_len(i as u64), chunk.len());
+        v.verify_block(i as u64, chunk, &mut fetch).unwrap();
+    }
+    // Wrong position and wrong length are both rejected.
+    assert!(v.verify_block(1, &data[..BLOCK], &mut fetch).is_err());
+    assert!(v.verify_block(3, &data[3 * BLOCK..data.len() - 1], &mut fetch).is_err());
+    // One leaf group, fetched once and cached across all blocks.
+    assert_eq!(fetches, 1);
+}
+
+// Verifies: REQ-VAL-014
+#[test]
+fn validate_with_reads_blocks_from_caller_and_matches_leaf_hashes() {
+    let data = multi();
+    let (_dp, _base, pt) = persist(&data, "data");
+    let leaves = pt.leaf_hashes(0, pt.counts[0]).unwrap();
+    let want: Vec<[u8; 32]> = data.chunks(BLOCK).map(g).collect();
+    assert_eq!(leaves, want);
+    assert!(pt.leaf_hashes(1, pt.counts[0]).is_err());
+
+    let source = |i: u64, len: usize| {
+        let s = i as usize * BLOCK;
+        Ok(data[s..s + len].to_vec())
+    };
+    let (s, e) = (BLOCK as u64 - 3, 3 * BLOCK as u64 + 3);
+    let mut out = Vec::new();
+    pt.validate_with(Some(s), Some(e), Some(&mut out), source)
+        .unwrap();
+    assert_eq!(out, &data[s as usize..e as usize]);
+
+    // A supplied block that does not hash to its leaf is rejected.
+    let mut bad = data.clone();
+    bad[2 * BLOCK] ^= 1;
+    let err = pt
+        .validate_with(None, None, None, |i, len| {
+            let s = i as usize * BLOCK;
+            Ok(bad[s..s + len].to_vec())
+        })
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Validation);
+    assert_eq!(err.block(), Some(2));
+}
+
 // ===========================================================================
 // Failure cases (spec section 6, section 7).
 // ===========================================================================